@@ -0,0 +1,44 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use kvstructs::bloom::{BloomFilter, BloomFilterBuilder};
+use kvstructs::Key;
+
+const NUM_KEYS: u32 = 10_000;
+
+fn key(i: u32) -> Key {
+    Key::from(format!("key{:08}", i)).with_timestamp(1)
+}
+
+fn build(bits_per_key: usize) -> BloomFilter {
+    let mut builder = BloomFilterBuilder::new(bits_per_key);
+    for i in 0..NUM_KEYS {
+        builder.add(key(i));
+    }
+    builder.finish()
+}
+
+fn bench_bloom(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bloom");
+    // the false positive rates are checked by the unit tests of the filter.
+    for bits_per_key in [5, 10, 20] {
+        let filter = build(bits_per_key);
+
+        group.bench_with_input(
+            BenchmarkId::new("build", bits_per_key),
+            &bits_per_key,
+            |b, n| b.iter(|| build(black_box(*n))),
+        );
+
+        let hit = key(NUM_KEYS / 2);
+        let miss = key(NUM_KEYS * 2);
+        group.bench_with_input(BenchmarkId::new("hit", bits_per_key), &filter, |b, f| {
+            b.iter(|| f.may_contain(black_box(&hit)))
+        });
+        group.bench_with_input(BenchmarkId::new("miss", bits_per_key), &filter, |b, f| {
+            b.iter(|| f.may_contain(black_box(&miss)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_bloom);
+criterion_main!(benches);
@@ -0,0 +1,251 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use kvstructs::batch::WriteBatch;
+use kvstructs::bytes::{Bytes, BytesMut};
+use kvstructs::{Entry, Header, Key, Value, ValueExt};
+
+const NUM_ENTRIES: usize = 1_000;
+
+/// The allocating encoders replaced by `encode_to`, kept here as the baseline.
+mod before {
+    use kvstructs::bytes::{Buf, Bytes};
+    use kvstructs::{Header, KeyExt, ValueExt, MAX_HEADER_SIZE};
+
+    const CASTAGNOLI: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_ISCSI);
+
+    fn put_uvarint(buf: &mut Vec<u8>, mut x: u64) -> usize {
+        let mut i = 0;
+        while x >= 0x80 {
+            buf.push((x as u8) | 0x80);
+            x >>= 7;
+            i += 1;
+        }
+        buf.push(x as u8);
+        i + 1
+    }
+
+    fn uvarint_allocate(x: u64) -> Vec<u8> {
+        let mut vec = Vec::with_capacity(10);
+        put_uvarint(&mut vec, x);
+        vec
+    }
+
+    pub fn header_encode(h: &Header) -> (usize, Vec<u8>) {
+        let mut buf = Vec::with_capacity(MAX_HEADER_SIZE);
+        buf.push(h.get_meta());
+        buf.push(h.get_user_meta());
+        let mut index = 2;
+        index += put_uvarint(&mut buf, h.get_key_len() as u64);
+        index += put_uvarint(&mut buf, h.get_value_len() as u64);
+        index += put_uvarint(&mut buf, h.get_expires_at());
+        (index, buf)
+    }
+
+    pub fn value_encode(val: &impl ValueExt, buf: &mut [u8]) {
+        buf[0] = val.get_meta();
+        buf[1] = val.get_user_meta();
+        let expires_at = uvarint_allocate(val.get_expires_at());
+        buf[2..2 + expires_at.len()].copy_from_slice(&expires_at);
+        let data = val.parse_value();
+        buf[2 + expires_at.len()..2 + expires_at.len() + data.len()].copy_from_slice(data);
+    }
+
+    pub fn value_to_encoded(val: &impl ValueExt) -> Bytes {
+        let mut data = Vec::with_capacity(10);
+        data.push(val.get_meta());
+        data.push(val.get_user_meta());
+        put_uvarint(&mut data, val.get_expires_at());
+        let meta = Bytes::from(data);
+        let val = val.parse_value_to_bytes();
+        let enc_len = meta.len() + val.len();
+        meta.chain(val).copy_to_bytes(enc_len)
+    }
+
+    pub fn vlog_encode(key: &impl KeyExt, val: &impl ValueExt, dst: &mut Vec<u8>) -> u32 {
+        let key = key.as_bytes();
+        let data = val.parse_value();
+        let header = Header::new(
+            val.get_meta(),
+            val.get_user_meta(),
+            key.len() as u32,
+            data.len() as u32,
+            val.get_expires_at(),
+        );
+        let start = dst.len();
+        let (_, h) = header_encode(&header);
+        dst.extend_from_slice(&h);
+        dst.extend_from_slice(key);
+        dst.extend_from_slice(data);
+        let crc = CASTAGNOLI.checksum(&dst[start..]);
+        dst.extend_from_slice(&crc.to_be_bytes());
+        (dst.len() - start) as u32
+    }
+
+    pub fn write_batch_put(batch: &mut Vec<u8>, key: &impl KeyExt, val: &impl ValueExt) {
+        let key = key.as_bytes();
+        put_uvarint(batch, key.len() as u64);
+        batch.extend_from_slice(key);
+        let sz = val.encoded_size() as usize;
+        put_uvarint(batch, sz as u64);
+        let start = batch.len();
+        batch.resize(start + sz, 0);
+        value_encode(val, &mut batch[start..]);
+    }
+}
+
+fn entries(value_size: usize) -> Vec<Entry> {
+    (0..NUM_ENTRIES)
+        .map(|i| {
+            let key = Key::from(format!("key{:08}", i)).with_timestamp(i as u64);
+            let val = Value::new()
+                .set_user_meta(1)
+                .set_expires_at(1_700_000_000)
+                .set_data(Bytes::from(vec![i as u8; value_size]));
+            Entry::new_from_kv(key, val)
+        })
+        .collect()
+}
+
+fn bench_header(c: &mut Criterion) {
+    let mut group = c.benchmark_group("header");
+    let h = Header::new(0x40, 1, 16, 4096, 1_700_000_000);
+    group.bench_function("encode_before", |b| {
+        b.iter(|| before::header_encode(black_box(&h)))
+    });
+    group.bench_function("encode", |b| b.iter(|| black_box(&h).encode()));
+    group.bench_function("encode_to_bytes", |b| {
+        b.iter(|| black_box(&h).encode_to_bytes())
+    });
+    let mut buf = Vec::with_capacity(64);
+    group.bench_function("encode_to", |b| {
+        b.iter(|| {
+            buf.clear();
+            black_box(&h).encode_to(&mut buf)
+        })
+    });
+    group.finish();
+}
+
+fn bench_value(c: &mut Criterion) {
+    let mut group = c.benchmark_group("value");
+    for value_size in [16, 256, 4096] {
+        let ents = entries(value_size);
+        group.bench_with_input(
+            BenchmarkId::new("to_encoded_before", value_size),
+            &ents,
+            |b, ents| {
+                b.iter(|| {
+                    for ent in ents {
+                        black_box(before::value_to_encoded(ent.get_value()));
+                    }
+                })
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("to_encoded", value_size),
+            &ents,
+            |b, ents| {
+                b.iter(|| {
+                    for ent in ents {
+                        black_box(ent.get_value().to_encoded());
+                    }
+                })
+            },
+        );
+
+        let mut buf = BytesMut::new();
+        group.bench_with_input(
+            BenchmarkId::new("encode_to", value_size),
+            &ents,
+            |b, ents| {
+                b.iter(|| {
+                    buf.clear();
+                    for ent in ents {
+                        ent.get_value().encode_to(&mut buf);
+                    }
+                    black_box(buf.len())
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_write_path(c: &mut Criterion) {
+    let mut group = c.benchmark_group("write");
+    for value_size in [16, 256, 4096] {
+        let ents = entries(value_size);
+
+        // the baseline must produce the same bytes
+        let (mut log_before, mut log) = (Vec::new(), Vec::new());
+        let (mut batch_before, mut batch) = (vec![0; 4], WriteBatch::new());
+        for (i, ent) in ents.iter().enumerate() {
+            before::vlog_encode(ent.get_key(), ent.get_value(), &mut log_before);
+            ent.encode_to(&mut log);
+            before::write_batch_put(&mut batch_before, ent.get_key(), ent.get_value());
+            batch_before[..4].copy_from_slice(&(i as u32 + 1).to_le_bytes());
+            batch.put_entry(ent);
+        }
+        assert_eq!(log_before, log);
+        assert_eq!(batch_before, batch.as_bytes());
+
+        group.bench_with_input(
+            BenchmarkId::new("vlog_before", value_size),
+            &ents,
+            |b, ents| {
+                let mut log = Vec::new();
+                b.iter(|| {
+                    log.clear();
+                    for ent in ents {
+                        before::vlog_encode(ent.get_key(), ent.get_value(), &mut log);
+                    }
+                    black_box(log.len())
+                })
+            },
+        );
+
+        group.bench_with_input(BenchmarkId::new("vlog", value_size), &ents, |b, ents| {
+            let mut log = Vec::new();
+            b.iter(|| {
+                log.clear();
+                for ent in ents {
+                    ent.encode_to(&mut log);
+                }
+                black_box(log.len())
+            })
+        });
+
+        group.bench_with_input(
+            BenchmarkId::new("write_batch_before", value_size),
+            &ents,
+            |b, ents| {
+                b.iter(|| {
+                    let mut batch = vec![0; 4];
+                    for (i, ent) in ents.iter().enumerate() {
+                        before::write_batch_put(&mut batch, ent.get_key(), ent.get_value());
+                        batch[..4].copy_from_slice(&(i as u32 + 1).to_le_bytes());
+                    }
+                    black_box(batch.len())
+                })
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("write_batch", value_size),
+            &ents,
+            |b, ents| {
+                b.iter(|| {
+                    let mut batch = WriteBatch::new();
+                    for ent in ents {
+                        batch.put_entry(ent);
+                    }
+                    black_box(batch.len())
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_header, bench_value, bench_write_path);
+criterion_main!(benches);
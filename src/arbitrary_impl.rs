@@ -0,0 +1,111 @@
+//! Arbitrary support.
+//!
+//! Keys start with one of [`SHARED_KEY_PREFIXES`] so that generated keys often share
+//! prefixes, and carry a timestamp half of the time unless the user key is empty, as the
+//! timestamp of an empty user key cannot be parsed. Values have any combination of [`OP`]
+//! bits, expirations which are zero, seconds-sized or any `u64`, and version 0 as the version
+//! is not encoded.
+use crate::bytes::Bytes;
+use crate::{EncodedValue, Entry, Header, Key, Value, ValueExt, OP, SHARED_KEY_PREFIXES};
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+fn arbitrary_expires_at(u: &mut Unstructured<'_>) -> Result<u64> {
+    Ok(match u.int_in_range(0..=2u8)? {
+        0 => 0,
+        1 => u.arbitrary::<u32>()? as u64,
+        _ => u.arbitrary()?,
+    })
+}
+
+impl<'a> Arbitrary<'a> for OP {
+    #[inline]
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(OP::from_bits_truncate(u.arbitrary()?))
+    }
+
+    #[inline]
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        u8::size_hint(depth)
+    }
+}
+
+impl<'a> Arbitrary<'a> for Key {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let prefix = *u.choose(SHARED_KEY_PREFIXES)?;
+        let suffix = <&[u8]>::arbitrary(u)?;
+        let mut data = prefix.to_vec();
+        data.extend_from_slice(suffix);
+        let with_timestamp = !data.is_empty() && u.arbitrary()?;
+        let key = Key::from(data);
+        if with_timestamp {
+            Ok(key.with_timestamp(u.arbitrary()?))
+        } else {
+            Ok(key)
+        }
+    }
+}
+
+impl<'a> Arbitrary<'a> for Value {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Value::new()
+            .set_meta(OP::arbitrary(u)?.bits())
+            .set_user_meta(u.arbitrary()?)
+            .set_expires_at(arbitrary_expires_at(u)?)
+            .set_data(Bytes::copy_from_slice(u.arbitrary()?)))
+    }
+}
+
+impl<'a> Arbitrary<'a> for EncodedValue {
+    #[inline]
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Value::arbitrary(u).map(|v| v.to_encoded())
+    }
+}
+
+impl<'a> Arbitrary<'a> for Entry {
+    #[inline]
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Entry::new_from_kv(u.arbitrary()?, u.arbitrary()?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for Header {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Header::new(
+            OP::arbitrary(u)?.bits(),
+            u.arbitrary()?,
+            u.arbitrary()?,
+            u.arbitrary()?,
+            arbitrary_expires_at(u)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::KeyExt;
+
+    #[test]
+    fn test_arbitrary() {
+        let data = (0..4096u32)
+            .map(|i| (i.wrapping_mul(2654435761) >> 13) as u8)
+            .collect::<Vec<_>>();
+        let mut u = Unstructured::new(&data);
+        while !u.is_empty() {
+            let ent = Entry::arbitrary(&mut u).unwrap();
+            let key = ent.get_key().as_bytes();
+            assert!(SHARED_KEY_PREFIXES.iter().any(|p| key.starts_with(p)));
+
+            let val = ent.get_value();
+            assert_eq!(
+                OP::from_bits(val.get_meta()).unwrap().bits(),
+                val.get_meta()
+            );
+            assert_eq!(val.to_encoded().decode_value(), *val);
+
+            let h = Header::arbitrary(&mut u).unwrap();
+            assert_eq!(Header::decode(&h.encode().1).1, h);
+        }
+    }
+}
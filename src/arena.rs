@@ -0,0 +1,462 @@
+use crate::raw_pointer::{RawEntryPointer, RawKeyPointer, RawValuePointer};
+use crate::{
+    binary_put_uvariant_to_bufmut, Error, Key, KeyExt, KeyRef, Value, ValueExt, ValueRef,
+    MAX_VARINT_LEN64,
+};
+use alloc::boxed::Box;
+use alloc::vec;
+use bytes::Bytes;
+use core::ptr::NonNull;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// The offset 0 is reserved as the null offset.
+const RESERVED: u32 = 1;
+
+const MAX_CAPACITY: usize = (u32::MAX - 7) as usize;
+
+/// 1 for meta, 1 for user meta, at most 10 for the uvarint encoded expires_at.
+const MAX_VALUE_INFO_LEN: usize = 2 + MAX_VARINT_LEN64;
+
+/// Arena is a lock-free bump allocator over a fixed-size buffer, which can be shared
+/// across threads. The memory is only released when the arena is dropped.
+///
+/// Keys and values put into the arena are returned as [`ArenaKey`], [`ArenaValue`] and
+/// [`ArenaEntry`], which borrow the arena, so they can never outlive the memory they point to.
+///
+/// [`ArenaKey`]: struct.ArenaKey.html
+/// [`ArenaValue`]: struct.ArenaValue.html
+/// [`ArenaEntry`]: struct.ArenaEntry.html
+pub struct Arena {
+    ptr: NonNull<u64>,
+    /// capacity in bytes
+    cap: u32,
+    /// the allocated offset
+    n: AtomicU32,
+}
+
+// Safety: all the allocations are done by atomic operations, and the allocated
+// regions never overlap.
+unsafe impl Send for Arena {}
+unsafe impl Sync for Arena {}
+
+impl core::fmt::Debug for Arena {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Arena")
+            .field("len", &self.len())
+            .field("cap", &self.cap)
+            .finish()
+    }
+}
+
+impl Arena {
+    /// Returns an arena which can hold `cap` bytes, the capacity will be rounded up to
+    /// the multiple of 8, and is limited to `u32::MAX` bytes.
+    pub fn new(cap: usize) -> Self {
+        let words = cap.min(MAX_CAPACITY).div_ceil(8).max(1);
+        let buf: Box<[u64]> = vec![0u64; words].into_boxed_slice();
+        let cap = (buf.len() * 8) as u32;
+        // Safety: the pointer of a boxed slice is never null.
+        let ptr = unsafe { NonNull::new_unchecked(Box::into_raw(buf) as *mut u64) };
+        Self {
+            ptr,
+            cap,
+            n: AtomicU32::new(RESERVED),
+        }
+    }
+
+    /// Returns the number of bytes allocated.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.n.load(Ordering::Acquire).min(self.cap) as usize
+    }
+
+    /// Returns true if nothing has been allocated.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == RESERVED as usize
+    }
+
+    /// Returns the capacity of the arena in bytes.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.cap as usize
+    }
+
+    /// Returns the number of bytes can be allocated.
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.capacity() - self.len()
+    }
+
+    /// Copies the key into the arena.
+    ///
+    /// Returns an error if the arena does not have enough space.
+    pub fn put_key(&self, key: impl KeyExt) -> Result<ArenaKey<'_>, Error> {
+        let data = key.as_bytes();
+        let offset = self.allocate_or_err(data.len(), 1)?;
+        // Safety: the region is allocated just now, and is not shared with others.
+        unsafe {
+            self.write_bytes(offset, data);
+            Ok(ArenaKey {
+                offset,
+                data: self.get_bytes(offset, data.len() as u32),
+            })
+        }
+    }
+
+    /// Encodes the value (meta, user meta, expires_at and value data) into the arena.
+    ///
+    /// Returns an error if the arena does not have enough space.
+    pub fn put_value(&self, val: impl ValueExt) -> Result<ArenaValue<'_>, Error> {
+        let mut info = [0u8; MAX_VALUE_INFO_LEN];
+        let info = encode_value_info(&val, &mut info);
+        let data = val.parse_value();
+        let size = info.len() + data.len();
+        let offset = self.allocate_or_err(size, 1)?;
+        // Safety: the region is allocated just now, and is not shared with others.
+        unsafe {
+            self.write_bytes(offset, info);
+            self.write_bytes(offset + info.len() as u32, data);
+            let encoded = self.get_bytes(offset, size as u32);
+            Ok(ArenaValue {
+                offset,
+                encoded,
+                val: ValueRef::decode_value_ref(encoded),
+            })
+        }
+    }
+
+    /// Copies the key and encodes the value into the arena.
+    ///
+    /// Returns an error if the arena does not have enough space.
+    #[inline]
+    pub fn put_entry(&self, key: impl KeyExt, val: impl ValueExt) -> Result<ArenaEntry<'_>, Error> {
+        Ok(ArenaEntry {
+            key: self.put_key(key)?,
+            val: self.put_value(val)?,
+        })
+    }
+
+    /// Allocates `size` bytes aligned to `align`, returns the offset of the allocated region,
+    /// or `None` if there is not enough space.
+    pub(crate) fn allocate(&self, size: u32, align: u32) -> Option<u32> {
+        debug_assert!(align.is_power_of_two() && align <= 8);
+        let mut cur = self.n.load(Ordering::Acquire);
+        loop {
+            let start = (cur as u64 + align as u64 - 1) & !(align as u64 - 1);
+            let end = start + size as u64;
+            if end > self.cap as u64 {
+                return None;
+            }
+
+            match self
+                .n
+                .compare_exchange_weak(cur, end as u32, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => return Some(start as u32),
+                Err(n) => cur = n,
+            }
+        }
+    }
+
+    /// Same as [`allocate`], but returns [`Error::ArenaFull`] if there is not enough space.
+    ///
+    /// [`allocate`]: #method.allocate
+    /// [`Error::ArenaFull`]: ../enum.Error.html#variant.ArenaFull
+    #[inline]
+    pub(crate) fn allocate_or_err(&self, size: usize, align: u32) -> Result<u32, Error> {
+        u32::try_from(size)
+            .ok()
+            .and_then(|sz| self.allocate(sz, align))
+            .ok_or_else(|| Error::ArenaFull {
+                required: size,
+                remaining: self.remaining(),
+            })
+    }
+
+    /// Returns the raw pointer of the offset.
+    ///
+    /// # Safety
+    /// The offset must be returned by [`allocate`], and the caller must not create
+    /// references which alias with the other writers.
+    ///
+    /// [`allocate`]: #method.allocate
+    #[inline]
+    pub(crate) unsafe fn get_pointer(&self, offset: u32) -> *mut u8 {
+        debug_assert!(offset < self.cap);
+        (self.ptr.as_ptr() as *mut u8).add(offset as usize)
+    }
+
+    /// Returns the byte slice at the offset.
+    ///
+    /// # Safety
+    /// The region must be allocated and fully written before, and must not be written anymore.
+    #[inline]
+    pub(crate) unsafe fn get_bytes(&self, offset: u32, len: u32) -> &[u8] {
+        if len == 0 {
+            return &[];
+        }
+        debug_assert!(offset as u64 + len as u64 <= self.cap as u64);
+        core::slice::from_raw_parts(self.get_pointer(offset), len as usize)
+    }
+
+    /// Copies the bytes into the region at the offset.
+    ///
+    /// # Safety
+    /// The region must be allocated with enough size, and must not be read by others yet.
+    #[inline]
+    pub(crate) unsafe fn write_bytes(&self, offset: u32, src: &[u8]) {
+        if src.is_empty() {
+            return;
+        }
+        debug_assert!(offset as u64 + src.len() as u64 <= self.cap as u64);
+        core::ptr::copy_nonoverlapping(src.as_ptr(), self.get_pointer(offset), src.len());
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        let words = self.cap as usize / 8;
+        // Safety: the pointer and length are the same as the boxed slice created in `new`.
+        unsafe {
+            drop(Box::from_raw(core::ptr::slice_from_raw_parts_mut(
+                self.ptr.as_ptr(),
+                words,
+            )));
+        }
+    }
+}
+
+/// Encodes the meta, user meta and expires_at into the buffer, returns the encoded slice.
+#[inline]
+fn encode_value_info<'a>(val: &impl ValueExt, buf: &'a mut [u8]) -> &'a [u8] {
+    buf[0] = val.get_meta();
+    buf[1] = val.get_user_meta();
+    let n = binary_put_uvariant_to_bufmut(&mut &mut buf[2..], val.get_expires_at());
+    &buf[..2 + n]
+}
+
+/// ArenaKey is a key stored in an [`Arena`], it cannot outlive the arena.
+///
+/// [`Arena`]: struct.Arena.html
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ArenaKey<'a> {
+    offset: u32,
+    data: &'a [u8],
+}
+
+impl<'a> ArenaKey<'a> {
+    /// Returns the offset of the key in the arena
+    #[inline]
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// Returns the number of bytes contained in this key.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns true if the key has a length of 0.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Returns the underlying bytes, which live as long as the arena.
+    #[inline]
+    pub fn as_slice(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Returns a [`KeyRef`] which lives as long as the arena.
+    ///
+    /// [`KeyRef`]: ../struct.KeyRef.html
+    #[inline]
+    pub fn to_key_ref(&self) -> KeyRef<'a> {
+        KeyRef::new(self.data)
+    }
+
+    /// Copy the key to a new [`Key`].
+    ///
+    /// [`Key`]: ../struct.Key.html
+    #[inline]
+    pub fn to_key(&self) -> Key {
+        Key::copy_from_slice(self.data)
+    }
+
+    /// Returns a [`RawKeyPointer`] to the key, which is valid as long as the arena.
+    ///
+    /// [`RawKeyPointer`]: ../raw_pointer/struct.RawKeyPointer.html
+    #[inline]
+    pub fn as_raw_pointer(&self) -> RawKeyPointer {
+        RawKeyPointer::from(self.to_key_ref())
+    }
+}
+
+impl KeyExt for ArenaKey<'_> {
+    #[inline]
+    fn as_bytes(&self) -> &[u8] {
+        self.data
+    }
+}
+
+/// ArenaValue is an encoded value stored in an [`Arena`], it cannot outlive the arena.
+///
+/// [`Arena`]: struct.Arena.html
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ArenaValue<'a> {
+    offset: u32,
+    encoded: &'a [u8],
+    val: ValueRef<'a>,
+}
+
+impl<'a> ArenaValue<'a> {
+    /// Returns the offset of the encoded value in the arena
+    #[inline]
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// Returns the encoded value (meta, user meta, expires_at and value data)
+    #[inline]
+    pub fn encoded_bytes(&self) -> &'a [u8] {
+        self.encoded
+    }
+
+    /// Returns a [`ValueRef`] which lives as long as the arena.
+    ///
+    /// [`ValueRef`]: ../struct.ValueRef.html
+    #[inline]
+    pub fn to_value_ref(&self) -> ValueRef<'a> {
+        self.val
+    }
+
+    /// Copy the value to a new [`Value`].
+    ///
+    /// [`Value`]: ../struct.Value.html
+    #[inline]
+    pub fn to_value(&self) -> Value {
+        self.val.to_value()
+    }
+
+    /// Returns a [`RawValuePointer`] to the value, which is valid as long as the arena.
+    ///
+    /// [`RawValuePointer`]: ../raw_pointer/struct.RawValuePointer.html
+    #[inline]
+    pub fn as_raw_pointer(&self) -> RawValuePointer {
+        // Safety: the encoded value lives as long as the arena.
+        unsafe { RawValuePointer::new(self.encoded.as_ptr(), self.encoded.len() as u32) }
+    }
+}
+
+impl ValueExt for ArenaValue<'_> {
+    #[inline]
+    fn as_value_ref(&self) -> ValueRef<'_> {
+        self.val
+    }
+
+    #[inline]
+    fn parse_value(&self) -> &[u8] {
+        self.val.parse_value()
+    }
+
+    #[inline]
+    fn parse_value_to_bytes(&self) -> Bytes {
+        self.val.parse_value_to_bytes()
+    }
+
+    #[inline]
+    fn get_meta(&self) -> u8 {
+        self.val.get_meta()
+    }
+
+    #[inline]
+    fn get_user_meta(&self) -> u8 {
+        self.val.get_user_meta()
+    }
+
+    #[inline]
+    fn get_expires_at(&self) -> u64 {
+        self.val.get_expires_at()
+    }
+}
+
+/// ArenaEntry is a key and an encoded value stored in an [`Arena`], it cannot outlive the arena.
+///
+/// [`Arena`]: struct.Arena.html
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ArenaEntry<'a> {
+    key: ArenaKey<'a>,
+    val: ArenaValue<'a>,
+}
+
+impl<'a> ArenaEntry<'a> {
+    /// Get the key
+    #[inline]
+    pub fn key(&self) -> ArenaKey<'a> {
+        self.key
+    }
+
+    /// Get the value
+    #[inline]
+    pub fn value(&self) -> ArenaValue<'a> {
+        self.val
+    }
+
+    /// Returns a [`RawEntryPointer`] to the entry, which is valid as long as the arena.
+    ///
+    /// [`RawEntryPointer`]: ../raw_pointer/struct.RawEntryPointer.html
+    #[inline]
+    pub fn as_raw_pointer(&self) -> RawEntryPointer {
+        // Safety: the key and the value live as long as the arena.
+        unsafe {
+            RawEntryPointer::new(
+                self.key.as_raw_pointer(),
+                self.val.as_raw_pointer(),
+                0,
+                0,
+                0,
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::raw_pointer::OwnedKeyPointer;
+
+    #[test]
+    fn test_arena() {
+        let arena = Arena::new(64);
+        assert!(arena.is_empty());
+
+        let key = Key::from("key").with_timestamp(1);
+        let val = Value::from("value").set_meta(1).set_expires_at(300);
+        let ent = arena.put_entry(key.clone(), val.clone()).unwrap();
+        assert_eq!(ent.key().as_slice(), key.as_slice());
+        assert_eq!(ent.key().parse_timestamp(), 1);
+        assert_eq!(ent.value().to_value(), val);
+        assert_eq!(
+            ent.value().encoded_bytes(),
+            val.to_encoded().leak_data().as_ref()
+        );
+
+        let raw = ent.as_raw_pointer();
+        unsafe {
+            assert_eq!(raw.key(), key);
+            assert_eq!(raw.value().to_value(), val);
+        }
+
+        assert!(matches!(
+            arena.put_key([0u8; 64]),
+            Err(Error::ArenaFull { required: 64, .. })
+        ));
+
+        let owned = OwnedKeyPointer::from(key.clone());
+        let raw = owned.as_raw_key_pointer();
+        assert_eq!(unsafe { raw.as_key_ref() }, key);
+    }
+}
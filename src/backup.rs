@@ -0,0 +1,613 @@
+use crate::{checksum, Error};
+
+/// The magic number at the start of backup files
+pub const BACKUP_MAGIC: [u8; 4] = *b"KVSB";
+
+/// The current version of the backup format
+pub const BACKUP_FORMAT_VERSION: u16 = 1;
+
+/// The size of the backup file header
+pub const BACKUP_HEADER_SIZE: usize = 36;
+
+/// The size of the header of each block in a backup file
+pub const BACKUP_BLOCK_HEADER_SIZE: usize = 8;
+
+/// The default target size of the blocks in a backup file
+pub const DEFAULT_BACKUP_BATCH_SIZE: usize = 1 << 20;
+
+/// The compression of the blocks in a backup file
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+#[repr(u8)]
+pub enum Compression {
+    /// Blocks are not compressed
+    #[default]
+    None = 0,
+    /// Blocks are compressed by snappy
+    #[cfg(feature = "snappy")]
+    Snappy = 1,
+}
+
+impl TryFrom<u8> for Compression {
+    type Error = Error;
+
+    #[inline]
+    fn try_from(c: u8) -> Result<Self, Self::Error> {
+        match c {
+            0 => Ok(Self::None),
+            #[cfg(feature = "snappy")]
+            1 => Ok(Self::Snappy),
+            _ => Err(Error::corruption("backup: unsupported compression")),
+        }
+    }
+}
+
+/// The header of a backup file.
+///
+/// ```text
+/// +-------+----------------+-------------+----------+----------+-------------+----------+----------+
+/// | magic | format version | compression | reserved |  since   | max version |  count   |   crc    |
+/// +-------+----------------+-------------+----------+----------+-------------+----------+----------+
+/// |  4B   |    u16 (LE)    |     u8      |    u8    | u64 (LE) |  u64 (LE)   | u64 (LE) | u32 (LE) |
+/// +-------+----------------+-------------+----------+----------+-------------+----------+----------+
+/// ```
+///
+/// The crc is the CRC-32 (Castagnoli) checksum of all the other fields.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct BackupHeader {
+    format_version: u16,
+    compression: Compression,
+    since: u64,
+    max_version: u64,
+    count: u64,
+}
+
+impl BackupHeader {
+    /// Get the version of the backup format
+    #[inline]
+    pub fn get_format_version(&self) -> u16 {
+        self.format_version
+    }
+
+    /// Get the compression of the blocks
+    #[inline]
+    pub fn get_compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// Get the timestamp the backup is taken since, only versions newer than it are backed up.
+    /// Zero means a full backup.
+    #[inline]
+    pub fn get_since(&self) -> u64 {
+        self.since
+    }
+
+    /// Get the maximum version in the backup, which is the `since` of the next incremental backup
+    #[inline]
+    pub fn get_max_version(&self) -> u64 {
+        self.max_version
+    }
+
+    /// Get the number of entries in the backup
+    #[inline]
+    pub fn get_count(&self) -> u64 {
+        self.count
+    }
+
+    /// Encodes the header
+    pub fn encode(&self) -> [u8; BACKUP_HEADER_SIZE] {
+        let mut buf = [0; BACKUP_HEADER_SIZE];
+        buf[..4].copy_from_slice(&BACKUP_MAGIC);
+        buf[4..6].copy_from_slice(&self.format_version.to_le_bytes());
+        buf[6] = self.compression as u8;
+        buf[8..16].copy_from_slice(&self.since.to_le_bytes());
+        buf[16..24].copy_from_slice(&self.max_version.to_le_bytes());
+        buf[24..32].copy_from_slice(&self.count.to_le_bytes());
+        let crc = checksum(&buf[..32]);
+        buf[32..].copy_from_slice(&crc.to_le_bytes());
+        buf
+    }
+
+    /// Decodes the header, the format version must not be newer than [`BACKUP_FORMAT_VERSION`].
+    ///
+    /// [`BACKUP_FORMAT_VERSION`]: constant.BACKUP_FORMAT_VERSION.html
+    pub fn decode(src: &[u8]) -> Result<Self, Error> {
+        if src.len() < BACKUP_HEADER_SIZE {
+            return Err(Error::corruption("backup: header too short"));
+        }
+        if src[..4] != BACKUP_MAGIC {
+            return Err(Error::corruption("backup: bad magic"));
+        }
+        let u64_at = |i: usize| {
+            let mut b = [0; 8];
+            b.copy_from_slice(&src[i..i + 8]);
+            u64::from_le_bytes(b)
+        };
+        let expected = u32::from_le_bytes([src[32], src[33], src[34], src[35]]);
+        let actual = checksum(&src[..32]);
+        if expected != actual {
+            return Err(Error::ChecksumMismatch { expected, actual });
+        }
+
+        let format_version = u16::from_le_bytes([src[4], src[5]]);
+        if format_version == 0 || format_version > BACKUP_FORMAT_VERSION {
+            return Err(Error::corruption("backup: unsupported format version"));
+        }
+        Ok(Self {
+            format_version,
+            compression: Compression::try_from(src[6])?,
+            since: u64_at(8),
+            max_version: u64_at(16),
+            count: u64_at(24),
+        })
+    }
+}
+
+/// Options to write backups.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct BackupOptions {
+    since: u64,
+    compression: Compression,
+    batch_size: usize,
+}
+
+impl Default for BackupOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BackupOptions {
+    /// Returns the default options, a full backup without compression in blocks of
+    /// [`DEFAULT_BACKUP_BATCH_SIZE`].
+    ///
+    /// [`DEFAULT_BACKUP_BATCH_SIZE`]: constant.DEFAULT_BACKUP_BATCH_SIZE.html
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            since: 0,
+            compression: Compression::None,
+            batch_size: DEFAULT_BACKUP_BATCH_SIZE,
+        }
+    }
+
+    /// Set the timestamp to back up since, only versions newer than it are backed up.
+    /// Pass the max version of the previous backup to take an incremental backup.
+    #[inline]
+    pub fn set_since(mut self, since: u64) -> Self {
+        self.since = since;
+        self
+    }
+
+    /// Get the timestamp to back up since
+    #[inline]
+    pub fn get_since(&self) -> u64 {
+        self.since
+    }
+
+    /// Set the compression of the blocks
+    #[inline]
+    pub fn set_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Get the compression of the blocks
+    #[inline]
+    pub fn get_compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// Set the target size of the blocks before compression, which is also the size of
+    /// the batches of entries read by restores.
+    #[inline]
+    pub fn set_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Get the target size of the blocks
+    #[inline]
+    pub fn get_batch_size(&self) -> usize {
+        self.batch_size
+    }
+}
+
+cfg_std! {
+    use crate::iterator::Iterator;
+    use crate::pb::{KVList, KV};
+    use crate::{Entry, KeyExt, ValueExt};
+    use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+
+    impl Compression {
+        fn compress(&self, data: Vec<u8>) -> Result<Vec<u8>, Error> {
+            match self {
+                Self::None => Ok(data),
+                #[cfg(feature = "snappy")]
+                Self::Snappy => snap::raw::Encoder::new()
+                    .compress_vec(&data)
+                    .map_err(|_| Error::corruption("backup: block too large to compress")),
+            }
+        }
+
+        fn decompress(&self, data: Vec<u8>) -> Result<Vec<u8>, Error> {
+            match self {
+                Self::None => Ok(data),
+                #[cfg(feature = "snappy")]
+                Self::Snappy => snap::raw::Decoder::new()
+                    .decompress_vec(&data)
+                    .map_err(|_| Error::corruption("backup: bad snappy block")),
+            }
+        }
+    }
+
+    /// BackupWriter writes a backup file, which is a [`BackupHeader`] followed by blocks.
+    ///
+    /// Each block is a [`KVList`] encoded by the protobuf wire format of Badger, optionally
+    /// compressed, and framed as
+    ///
+    /// ```text
+    /// +----------+----------+---------+
+    /// |  length  |   crc    | payload |
+    /// +----------+----------+---------+
+    /// | u32 (LE) | u32 (LE) |         |
+    /// +----------+----------+---------+
+    /// ```
+    ///
+    /// where crc is the CRC-32 (Castagnoli) checksum of the (compressed) payload.
+    ///
+    /// The header is rewritten with the max version and the number of entries by [`finish`],
+    /// a backup which is not finished fails to restore.
+    ///
+    /// [`BackupHeader`]: struct.BackupHeader.html
+    /// [`KVList`]: ../pb/struct.KVList.html
+    /// [`finish`]: #method.finish
+    #[derive(Debug)]
+    pub struct BackupWriter<W> {
+        dst: W,
+        start: u64,
+        opts: BackupOptions,
+        max_version: u64,
+        count: u64,
+        pending: KVList,
+        pending_size: usize,
+    }
+
+    impl<W: Write + Seek> BackupWriter<W> {
+        /// Returns a BackupWriter writing at the current position of `dst`
+        pub fn new(mut dst: W, opts: BackupOptions) -> Result<Self, Error> {
+            let start = dst.stream_position()?;
+            let mut w = Self {
+                dst,
+                start,
+                max_version: opts.since,
+                opts,
+                count: 0,
+                pending: KVList::new(),
+                pending_size: 0,
+            };
+            let header = w.header();
+            w.dst.write_all(&header.encode())?;
+            Ok(w)
+        }
+
+        /// Adds a key-value pair, versions which are not newer than the since timestamp of
+        /// the options are skipped. Returns whether the pair is added.
+        pub fn add(&mut self, key: impl KeyExt, val: impl ValueExt) -> Result<bool, Error> {
+            let version = key.parse_timestamp();
+            if version <= self.opts.since {
+                return Ok(false);
+            }
+            let kv = KV::from_kv(key, val);
+            self.pending_size += kv.get_key().len() + kv.get_value().len();
+            self.pending.push(kv);
+            self.max_version = self.max_version.max(version);
+            self.count += 1;
+            if self.pending_size >= self.opts.batch_size {
+                self.flush_block()?;
+            }
+            Ok(true)
+        }
+
+        /// Backs up the key-value pairs from the current position of the iterator to the end,
+        /// returns the number of pairs added.
+        ///
+        /// Deletions are backed up as well, so restoring an incremental backup replays them.
+        /// Returns an error if the iterator fails, the backup should not be finished then.
+        pub fn backup<I, K, V>(&mut self, iter: &mut I) -> Result<u64, Error>
+        where
+            I: Iterator<K, V>,
+            K: KeyExt,
+            V: ValueExt,
+        {
+            let mut added = 0;
+            while iter.valid() {
+                if let Some((k, v)) = iter.entry() {
+                    if self.add(k, v)? {
+                        added += 1;
+                    }
+                }
+                iter.next();
+            }
+            if let Some(e) = iter.error() {
+                return Err(Error::corruption(format!("backup: iterator failed: {}", e)));
+            }
+            Ok(added)
+        }
+
+        /// Get the maximum version added, or the since timestamp of the options if nothing
+        /// is added, which is the since timestamp of the next incremental backup.
+        #[inline]
+        pub fn get_max_version(&self) -> u64 {
+            self.max_version
+        }
+
+        /// Get the number of entries added
+        #[inline]
+        pub fn get_count(&self) -> u64 {
+            self.count
+        }
+
+        /// Writes the pending block and the final header, returns the destination.
+        pub fn finish(mut self) -> Result<W, Error> {
+            self.flush_block()?;
+            let end = self.dst.stream_position()?;
+            self.dst.seek(SeekFrom::Start(self.start))?;
+            let header = self.header();
+            self.dst.write_all(&header.encode())?;
+            self.dst.seek(SeekFrom::Start(end))?;
+            self.dst.flush()?;
+            Ok(self.dst)
+        }
+
+        fn header(&self) -> BackupHeader {
+            BackupHeader {
+                format_version: BACKUP_FORMAT_VERSION,
+                compression: self.opts.compression,
+                since: self.opts.since,
+                max_version: self.max_version,
+                count: self.count,
+            }
+        }
+
+        fn flush_block(&mut self) -> Result<(), Error> {
+            if self.pending.is_empty() {
+                return Ok(());
+            }
+            let payload = self.opts.compression.compress(self.pending.encode())?;
+            let len = u32::try_from(payload.len())
+                .map_err(|_| Error::corruption("backup: block too large"))?;
+            let mut frame = [0; BACKUP_BLOCK_HEADER_SIZE];
+            frame[..4].copy_from_slice(&len.to_le_bytes());
+            frame[4..].copy_from_slice(&checksum(&payload).to_le_bytes());
+            self.dst.write_all(&frame)?;
+            self.dst.write_all(&payload)?;
+            self.pending = KVList::new();
+            self.pending_size = 0;
+            Ok(())
+        }
+    }
+
+    /// RestoreReader reads the entries of a backup file written by [`BackupWriter`], one
+    /// block (batch) at a time.
+    ///
+    /// [`BackupWriter`]: struct.BackupWriter.html
+    #[derive(Debug)]
+    pub struct RestoreReader<R> {
+        src: R,
+        header: BackupHeader,
+        read: u64,
+    }
+
+    impl RestoreReader<std::io::BufReader<std::fs::File>> {
+        /// Opens the backup file at the path
+        #[inline]
+        pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self, Error> {
+            Self::new(std::io::BufReader::new(std::fs::File::open(path)?))
+        }
+    }
+
+    impl<R: Read> RestoreReader<R> {
+        /// Returns a RestoreReader reading from the current position of `src`, the header is
+        /// read and validated.
+        pub fn new(mut src: R) -> Result<Self, Error> {
+            let mut buf = [0; BACKUP_HEADER_SIZE];
+            src.read_exact(&mut buf).map_err(|e| match e.kind() {
+                ErrorKind::UnexpectedEof => Error::corruption("backup: header too short"),
+                _ => e.into(),
+            })?;
+            Ok(Self {
+                src,
+                header: BackupHeader::decode(&buf)?,
+                read: 0,
+            })
+        }
+
+        /// Get the header of the backup
+        #[inline]
+        pub fn get_header(&self) -> &BackupHeader {
+            &self.header
+        }
+
+        /// Reads the next batch of entries, returns `None` after the last one.
+        pub fn next_batch(&mut self) -> Result<Option<Vec<Entry>>, Error> {
+            let mut frame = [0; BACKUP_BLOCK_HEADER_SIZE];
+            let n = read_full(&mut self.src, &mut frame)?;
+            if n == 0 {
+                if self.read != self.header.count {
+                    return Err(Error::corruption("backup: entry count mismatch"));
+                }
+                return Ok(None);
+            }
+            if n < BACKUP_BLOCK_HEADER_SIZE {
+                return Err(Error::corruption("backup: truncated block"));
+            }
+
+            let len = u32::from_le_bytes([frame[0], frame[1], frame[2], frame[3]]) as u64;
+            let expected = u32::from_le_bytes([frame[4], frame[5], frame[6], frame[7]]);
+            // do not trust the length to allocate.
+            let mut payload = Vec::new();
+            (&mut self.src).take(len).read_to_end(&mut payload)?;
+            if payload.len() as u64 != len {
+                return Err(Error::corruption("backup: truncated block"));
+            }
+            let actual = checksum(&payload);
+            if expected != actual {
+                return Err(Error::ChecksumMismatch { expected, actual });
+            }
+
+            let list = KVList::decode(&self.header.compression.decompress(payload)?)?;
+            self.read += list.len() as u64;
+            if self.read > self.header.count {
+                return Err(Error::corruption("backup: entry count mismatch"));
+            }
+            Ok(Some(list.entries().collect()))
+        }
+    }
+
+    /// Reads until the buffer is full or the end of the reader, returns the number of bytes read.
+    fn read_full(src: &mut impl Read, buf: &mut [u8]) -> Result<usize, Error> {
+        let mut n = 0;
+        while n < buf.len() {
+            match src.read(&mut buf[n..]) {
+                Ok(0) => break,
+                Ok(m) => n += m,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+    use crate::bytes::Bytes;
+    use crate::iterator::test::VecIterator;
+    use crate::table::{Table, TableBuilder, TableOptions};
+    use crate::{Key, KeyExt, Value, ValueExt, OP};
+    use std::io::Cursor;
+
+    fn table(versions: &[u64]) -> Table {
+        let mut builder = TableBuilder::new(TableOptions::default());
+        for i in 0..100u32 {
+            for ts in versions.iter().rev() {
+                let key = Key::from(format!("key{:03}", i).into_bytes()).with_timestamp(*ts);
+                let val = if i % 10 == 0 && *ts > 1 {
+                    Value::new().set_meta(OP::BIT_DELETE.bits())
+                } else {
+                    Value::new()
+                        .set_user_meta(*ts as u8)
+                        .set_data(Bytes::from(vec![i as u8; 64]))
+                };
+                builder.add(key, val);
+            }
+        }
+        Table::from_bytes(builder.finish(), &TableOptions::default()).unwrap()
+    }
+
+    fn backup(table: &Table, opts: BackupOptions) -> (Vec<u8>, u64) {
+        let mut w = BackupWriter::new(Cursor::new(Vec::new()), opts).unwrap();
+        let mut iter = table.iter();
+        iter.rewind();
+        w.backup(&mut iter).unwrap();
+        let max_version = w.get_max_version();
+        (w.finish().unwrap().into_inner(), max_version)
+    }
+
+    fn restore(data: &[u8]) -> Result<Vec<Entry>, Error> {
+        let mut r = RestoreReader::new(data)?;
+        let mut entries = Vec::new();
+        while let Some(batch) = r.next_batch()? {
+            assert!(!batch.is_empty());
+            entries.extend(batch);
+        }
+        Ok(entries)
+    }
+
+    #[test]
+    fn test_backup() {
+        let t = table(&[1, 2]);
+        let opts = BackupOptions::new().set_batch_size(1024);
+        let (full, max_version) = backup(&t, opts);
+        assert_eq!(max_version, 2);
+        let header = RestoreReader::new(full.as_slice()).unwrap().header;
+        assert_eq!(header.get_count(), 200);
+        assert_eq!(header.get_since(), 0);
+
+        let entries = restore(&full).unwrap();
+        assert_eq!(entries.len(), 200);
+        let mut iter = t.iter();
+        iter.rewind();
+        for ent in &entries {
+            let (k, v) = iter.entry().unwrap();
+            assert_eq!(ent.get_key().as_bytes(), k.as_bytes());
+            assert_eq!(ent.get_value().get_version(), k.parse_timestamp());
+            assert_eq!(ent.get_value().as_value_ref(), v.as_value_ref());
+            iter.next();
+        }
+
+        // incremental backup since the full one
+        let t = table(&[1, 2, 3]);
+        let (inc, max_version) = backup(&t, opts.set_since(max_version));
+        assert_eq!(max_version, 3);
+        let entries = restore(&inc).unwrap();
+        assert_eq!(entries.len(), 100);
+        assert!(entries.iter().all(|e| e.get_key().parse_timestamp() == 3));
+        assert_eq!(
+            entries
+                .iter()
+                .filter(|e| e.get_value().is_deleted_or_expired(0))
+                .count(),
+            10
+        );
+
+        let mut corrupted = full.clone();
+        corrupted[BACKUP_HEADER_SIZE + BACKUP_BLOCK_HEADER_SIZE + 1] ^= 1;
+        assert!(matches!(
+            restore(&corrupted),
+            Err(Error::ChecksumMismatch { .. })
+        ));
+        assert!(restore(&full[..full.len() - 1]).is_err());
+        assert!(restore(&full[..BACKUP_HEADER_SIZE - 1]).is_err());
+
+        // a backup which is not finished
+        let mut w = BackupWriter::new(Cursor::new(Vec::new()), opts).unwrap();
+        w.add(Key::copy_from_slice(b"k").with_timestamp(1), Value::new())
+            .unwrap();
+        w.flush_block().unwrap();
+        assert!(restore(w.dst.get_ref()).is_err());
+
+        // nothing is newer than the since timestamp
+        let (_, max_version) = backup(&t, opts.set_since(5));
+        assert_eq!(max_version, 5);
+    }
+
+    #[test]
+    fn test_backup_iterator_error() {
+        let data = (0..10u64)
+            .map(|i| {
+                let key = Key::from(format!("key{:03}", i).into_bytes()).with_timestamp(1);
+                (key, Value::from("v"))
+            })
+            .collect();
+        let mut iter = VecIterator::new(data).fail_at(5);
+        iter.rewind();
+        let mut w = BackupWriter::new(Cursor::new(Vec::new()), BackupOptions::new()).unwrap();
+        assert!(matches!(w.backup(&mut iter), Err(Error::Corruption(_))));
+        assert_eq!(w.get_count(), 5);
+    }
+
+    #[cfg(feature = "snappy")]
+    #[test]
+    fn test_backup_snappy() {
+        let t = table(&[1]);
+        let (plain, _) = backup(&t, BackupOptions::new());
+        let (compressed, _) = backup(
+            &t,
+            BackupOptions::new().set_compression(Compression::Snappy),
+        );
+        assert!(compressed.len() < plain.len());
+        assert_eq!(restore(&compressed).unwrap(), restore(&plain).unwrap());
+    }
+}
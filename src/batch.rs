@@ -0,0 +1,241 @@
+use crate::{
+    binary_put_uvariant_to_bufmut, binary_uvarint, check_encoded_value, decode_uvarint, Entry,
+    Error, KeyExt, KeyRef, Value, ValueExt, ValueRef, OP,
+};
+use alloc::vec::Vec;
+
+/// The size of the entry count at the beginning of an encoded [`WriteBatch`].
+///
+/// [`WriteBatch`]: struct.WriteBatch.html
+pub const WRITE_BATCH_HEADER_SIZE: usize = 4;
+
+/// WriteBatch holds a sequence of key-value pairs which should be applied atomically.
+///
+/// The batch is kept in the encoded form, which looks like
+///
+/// ```text
+/// +-----------+------------+-----+--------------+---------------+-----+
+/// |   count   | key length | key | value length | encoded value | ... |
+/// +-----------+------------+-----+--------------+---------------+-----+
+/// | u32 (LE)  |  uvarint   |     |   uvarint    |               |     |
+/// +-----------+------------+-----+--------------+---------------+-----+
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct WriteBatch {
+    data: Vec<u8>,
+    count: u32,
+}
+
+impl Default for WriteBatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WriteBatch {
+    /// Returns an empty WriteBatch
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            data: alloc::vec![0; WRITE_BATCH_HEADER_SIZE],
+            count: 0,
+        }
+    }
+
+    /// Appends a key (with timestamp) and its value
+    pub fn put(&mut self, key: impl KeyExt, val: impl ValueExt) {
+        let key = key.as_bytes();
+        binary_put_uvariant_to_bufmut(&mut self.data, key.len() as u64);
+        self.data.extend_from_slice(key);
+
+        binary_put_uvariant_to_bufmut(&mut self.data, val.encoded_size() as u64);
+        val.encode_to(&mut self.data);
+
+        self.count += 1;
+        self.data[..WRITE_BATCH_HEADER_SIZE].copy_from_slice(&self.count.to_le_bytes());
+    }
+
+    /// Appends the key and value of the entry
+    #[inline]
+    pub fn put_entry(&mut self, ent: &Entry) {
+        self.put(ent.get_key(), ent.get_value().as_value_ref())
+    }
+
+    /// Appends a deletion marker of the key (with timestamp)
+    #[inline]
+    pub fn delete(&mut self, key: impl KeyExt) {
+        self.put(key, Value::new().set_meta(OP::BIT_DELETE.bits()))
+    }
+
+    /// Returns the number of entries in the batch
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.count as usize
+    }
+
+    /// Returns true if the batch has no entry
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Returns the size of the encoded batch
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Removes all the entries, the allocated memory is kept.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.data.clear();
+        self.data.resize(WRITE_BATCH_HEADER_SIZE, 0);
+        self.count = 0;
+    }
+
+    /// Returns the encoded batch
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        self.data.as_slice()
+    }
+
+    /// Decodes a batch from the encoded form, the whole batch is validated.
+    pub fn decode(data: &[u8]) -> Result<Self, Error> {
+        if data.len() < WRITE_BATCH_HEADER_SIZE {
+            return Err(Error::corruption("batch: too short"));
+        }
+        let count = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+
+        let mut cursor = WRITE_BATCH_HEADER_SIZE;
+        let mut found = 0u32;
+        while cursor < data.len() {
+            let (klen, n) = decode_uvarint(&data[cursor..])?;
+            cursor += n;
+            cursor = cursor
+                .checked_add(klen as usize)
+                .filter(|end| *end <= data.len())
+                .ok_or_else(|| Error::corruption("batch: bad key length"))?;
+
+            let (vlen, n) = decode_uvarint(&data[cursor..])?;
+            cursor += n;
+            let start = cursor;
+            cursor = cursor
+                .checked_add(vlen as usize)
+                .filter(|end| *end <= data.len())
+                .ok_or_else(|| Error::corruption("batch: bad value length"))?;
+            check_encoded_value(&data[start..cursor])?;
+            found += 1;
+        }
+
+        if found != count {
+            return Err(Error::corruption("batch: entry count mismatch"));
+        }
+        Ok(Self {
+            data: data.to_vec(),
+            count,
+        })
+    }
+
+    /// Returns an iterator over the entries in the batch, in the order they were added.
+    #[inline]
+    pub fn iter(&self) -> WriteBatchIter<'_> {
+        WriteBatchIter {
+            data: &self.data[WRITE_BATCH_HEADER_SIZE..],
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a WriteBatch {
+    type Item = (KeyRef<'a>, ValueRef<'a>);
+    type IntoIter = WriteBatchIter<'a>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// WriteBatchIter yields the keys and values in a [`WriteBatch`].
+///
+/// The version of yielded values is set to the timestamp of their keys.
+///
+/// [`WriteBatch`]: struct.WriteBatch.html
+#[derive(Debug, Clone)]
+pub struct WriteBatchIter<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> core::iter::Iterator for WriteBatchIter<'a> {
+    type Item = (KeyRef<'a>, ValueRef<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+
+        // the batch is validated when built or decoded.
+        let (klen, n) = binary_uvarint(self.data);
+        let (key, rest) = self.data[n..].split_at(klen as usize);
+        let (vlen, n) = binary_uvarint(rest);
+        let (val, rest) = rest[n..].split_at(vlen as usize);
+        self.data = rest;
+
+        let key = KeyRef::new(key);
+        let mut val = ValueRef::decode_value_ref(val);
+        val.set_version(key.parse_timestamp());
+        Some((key, val))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Key, KeyExt};
+    use bytes::Bytes;
+
+    #[test]
+    fn test_write_batch() {
+        let mut batch = WriteBatch::new();
+        assert!(batch.is_empty());
+        assert_eq!(
+            WriteBatch::decode(batch.as_bytes()).unwrap().as_bytes(),
+            batch.as_bytes()
+        );
+
+        batch.put(
+            Key::from("a").with_timestamp(3),
+            Value::new()
+                .set_user_meta(7)
+                .set_expires_at(1000)
+                .set_data(Bytes::from("va")),
+        );
+        batch.delete(Key::from("b").with_timestamp(4));
+        batch.put_entry(&Entry::new_from_kv(
+            Key::from("c").with_timestamp(5),
+            Value::new().set_data(Bytes::from("vc")),
+        ));
+        assert_eq!(batch.len(), 3);
+
+        let decoded = WriteBatch::decode(batch.as_bytes()).unwrap();
+        assert_eq!(decoded, batch);
+        let entries = decoded.iter().collect::<Vec<_>>();
+        assert_eq!(entries[0].0.parse_key(), b"a");
+        assert_eq!(entries[0].1.get_user_meta(), 7);
+        assert_eq!(entries[0].1.get_expires_at(), 1000);
+        assert_eq!(entries[0].1.get_version(), 3);
+        assert_eq!(entries[0].1.parse_value(), b"va");
+        assert_eq!(entries[1].0.parse_timestamp(), 4);
+        assert_eq!(entries[1].1.get_meta(), OP::BIT_DELETE.bits());
+        assert_eq!(entries[2].1.parse_value(), b"vc");
+
+        let data = batch.as_bytes();
+        assert!(WriteBatch::decode(&data[..data.len() - 3]).is_err());
+        let mut bad = data.to_vec();
+        bad[0] = 4;
+        assert!(WriteBatch::decode(&bad).is_err());
+
+        batch.clear();
+        assert!(batch.is_empty());
+        assert_eq!(batch.size(), WRITE_BATCH_HEADER_SIZE);
+    }
+}
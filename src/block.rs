@@ -0,0 +1,691 @@
+use crate::bytes::{BufMut, Bytes};
+use crate::iterator::{seek_to_next_user_key, Iterator, SeekFrom};
+use crate::{
+    binary_put_uvariant_to_bufmut, check_encoded_value, checksum, compare_key_in, decode_uvarint,
+    EncodedValue, Error, Key, KeyExt, KeyRef, Value, ValueExt, ValueRef,
+};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::mem;
+
+/// The default number of entries between two restart points
+pub const DEFAULT_RESTART_INTERVAL: usize = 16;
+
+/// num_restarts (u32) + checksum (u32)
+const BLOCK_TRAILER_SIZE: usize = 8;
+
+/// BlockBuilder builds a data block from sorted key-value pairs.
+///
+/// Keys are prefix-compressed against the previous key, every [`restart_interval`] entries
+/// a restart point stores the full key, so that [`BlockIterator`] can binary search them.
+///
+/// ```text
+/// +---------+---------+-----+----------------+------------------+---------------+
+/// | entry 0 | entry 1 | ... | restarts (u32) | num_restarts u32 | checksum u32  |
+/// +---------+---------+-----+----------------+------------------+---------------+
+///
+/// entry: | shared (uvarint) | non_shared (uvarint) | value_len (uvarint) | key delta | value |
+/// ```
+///
+/// All the fixed-size integers are little-endian, the checksum is the CRC-32 (Castagnoli)
+/// of everything before it, and the value is the [`EncodedValue`].
+///
+/// [`restart_interval`]: #method.set_restart_interval
+/// [`BlockIterator`]: struct.BlockIterator.html
+/// [`EncodedValue`]: ../struct.EncodedValue.html
+#[derive(Debug, Clone)]
+pub struct BlockBuilder {
+    buf: Vec<u8>,
+    restarts: Vec<u32>,
+    restart_interval: usize,
+    /// the number of entries since the last restart point
+    counter: usize,
+    num_entries: usize,
+    last_key: Vec<u8>,
+}
+
+impl Default for BlockBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BlockBuilder {
+    /// Returns an empty BlockBuilder with [`DEFAULT_RESTART_INTERVAL`].
+    ///
+    /// [`DEFAULT_RESTART_INTERVAL`]: constant.DEFAULT_RESTART_INTERVAL.html
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            buf: Vec::new(),
+            restarts: vec![0],
+            restart_interval: DEFAULT_RESTART_INTERVAL,
+            counter: 0,
+            num_entries: 0,
+            last_key: Vec::new(),
+        }
+    }
+
+    /// Set the number of entries between two restart points (at least 1)
+    #[inline]
+    pub fn set_restart_interval(mut self, interval: usize) -> Self {
+        self.restart_interval = interval.max(1);
+        self
+    }
+
+    /// Get the number of entries between two restart points
+    #[inline]
+    pub fn get_restart_interval(&self) -> usize {
+        self.restart_interval
+    }
+
+    /// Appends a key-value pair to the block.
+    ///
+    /// The keys must be added in increasing order, see [`compare_key`].
+    ///
+    /// [`compare_key`]: ../fn.compare_key.html
+    #[inline]
+    pub fn add(&mut self, key: impl KeyExt, val: &EncodedValue) {
+        self.add_raw(key.as_bytes(), val.data.as_ref())
+    }
+
+    /// Appends a key and the raw value bytes to the block.
+    pub(crate) fn add_raw(&mut self, key: &[u8], val: &[u8]) {
+        debug_assert!(
+            self.num_entries == 0 || compare_key_in(&self.last_key, key) == Ordering::Less,
+            "kvstructs: keys must be added to the block in increasing order"
+        );
+
+        let shared = if self.counter < self.restart_interval {
+            self.last_key.longest_prefix(key).len()
+        } else {
+            self.restarts.push(self.buf.len() as u32);
+            self.counter = 0;
+            0
+        };
+
+        let non_shared = &key[shared..];
+        binary_put_uvariant_to_bufmut(&mut self.buf, shared as u64);
+        binary_put_uvariant_to_bufmut(&mut self.buf, non_shared.len() as u64);
+        binary_put_uvariant_to_bufmut(&mut self.buf, val.len() as u64);
+        self.buf.extend_from_slice(non_shared);
+        self.buf.extend_from_slice(val);
+
+        self.last_key.truncate(shared);
+        self.last_key.extend_from_slice(non_shared);
+        self.counter += 1;
+        self.num_entries += 1;
+    }
+
+    /// Returns the last key added to the block
+    #[inline]
+    pub fn last_key(&self) -> &[u8] {
+        self.last_key.as_slice()
+    }
+
+    /// Returns the number of entries in the block
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.num_entries
+    }
+
+    /// Returns true if no entry has been added
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.num_entries == 0
+    }
+
+    /// Returns the size of the block if it is finished now
+    #[inline]
+    pub fn estimated_size(&self) -> usize {
+        self.buf.len() + self.restarts.len() * mem::size_of::<u32>() + BLOCK_TRAILER_SIZE
+    }
+
+    /// Finishes the block and returns the encoded bytes, the builder is reset and can be reused.
+    pub fn finish(&mut self) -> Bytes {
+        let mut buf = mem::take(&mut self.buf);
+        buf.reserve(self.restarts.len() * mem::size_of::<u32>() + BLOCK_TRAILER_SIZE);
+        for restart in &self.restarts {
+            buf.put_u32_le(*restart);
+        }
+        buf.put_u32_le(self.restarts.len() as u32);
+        let crc = checksum(&buf);
+        buf.put_u32_le(crc);
+        self.reset();
+        Bytes::from(buf)
+    }
+
+    /// Clears the builder
+    #[inline]
+    pub fn reset(&mut self) {
+        self.buf.clear();
+        self.restarts.clear();
+        self.restarts.push(0);
+        self.counter = 0;
+        self.num_entries = 0;
+        self.last_key.clear();
+    }
+}
+
+/// Block is a data block built by [`BlockBuilder`], whose checksum has been verified.
+///
+/// [`BlockBuilder`]: struct.BlockBuilder.html
+#[derive(Debug, Clone)]
+pub struct Block {
+    data: Bytes,
+    restarts_offset: usize,
+    num_restarts: usize,
+}
+
+impl Block {
+    /// Decodes a block, returns an error if the checksum does not match or the layout is invalid.
+    pub fn new(data: Bytes) -> Result<Self, Error> {
+        if data.len() < BLOCK_TRAILER_SIZE + mem::size_of::<u32>() {
+            return Err(Error::corruption("block: too short"));
+        }
+
+        let crc_offset = data.len() - mem::size_of::<u32>();
+        let expected = read_u32_le(&data, crc_offset);
+        let actual = checksum(&data[..crc_offset]);
+        if expected != actual {
+            return Err(Error::ChecksumMismatch { expected, actual });
+        }
+
+        let num_restarts_offset = crc_offset - mem::size_of::<u32>();
+        let num_restarts = read_u32_le(&data, num_restarts_offset) as usize;
+        match num_restarts.checked_mul(mem::size_of::<u32>()) {
+            Some(sz) if num_restarts > 0 && sz <= num_restarts_offset => Ok(Self {
+                restarts_offset: num_restarts_offset - sz,
+                num_restarts,
+                data,
+            }),
+            _ => Err(Error::corruption("block: invalid number of restart points")),
+        }
+    }
+
+    /// Returns the size of the encoded block
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns the number of restart points
+    #[inline]
+    pub fn num_restarts(&self) -> usize {
+        self.num_restarts
+    }
+
+    /// Returns the encoded block
+    #[inline]
+    pub fn as_bytes(&self) -> &Bytes {
+        &self.data
+    }
+
+    /// Returns an iterator over the block, call [`rewind`] or [`seek`] to position it.
+    ///
+    /// [`rewind`]: ../iterator/trait.Iterator.html#tymethod.rewind
+    /// [`seek`]: ../iterator/trait.Iterator.html#tymethod.seek
+    #[inline]
+    pub fn iter(&self) -> BlockIterator {
+        BlockIterator {
+            block: self.clone(),
+            current: self.restarts_offset,
+            next: self.restarts_offset,
+            restart_index: self.num_restarts,
+            key: Vec::new(),
+            value: (0, 0),
+            scratch: Vec::new(),
+            err: None,
+        }
+    }
+
+    /// Returns the offset of the restart point.
+    #[inline]
+    fn restart_point(&self, idx: usize) -> Result<usize, Error> {
+        let offset = read_u32_le(
+            &self.data,
+            self.restarts_offset + idx * mem::size_of::<u32>(),
+        );
+        if offset as usize > self.restarts_offset {
+            return Err(Error::corruption("block: invalid restart point"));
+        }
+        Ok(offset as usize)
+    }
+
+    /// Decodes and checks the entry at offset.
+    fn decode_entry(&self, offset: usize) -> Result<EntryInfo, Error> {
+        let buf = &self.data[offset..self.restarts_offset];
+        let (shared, n1) = decode_uvarint(buf)?;
+        let (non_shared, n2) = decode_uvarint(&buf[n1..])?;
+        let (val_len, n3) = decode_uvarint(&buf[n1 + n2..])?;
+        let header = n1 + n2 + n3;
+        let entry_len = (header as u64)
+            .checked_add(non_shared)
+            .and_then(|n| n.checked_add(val_len))
+            .filter(|n| *n <= buf.len() as u64)
+            .ok_or_else(|| Error::corruption("block: entry overflows the block"))?;
+
+        let key_start = offset + header;
+        let val_start = key_start + non_shared as usize;
+        let val_end = offset + entry_len as usize;
+        check_encoded_value(&self.data[val_start..val_end])?;
+        Ok(EntryInfo {
+            shared: shared as usize,
+            key: (key_start, val_start),
+            value: (val_start, val_end),
+        })
+    }
+}
+
+/// The decoded entry header, the ranges are the offsets in the block.
+struct EntryInfo {
+    shared: usize,
+    key: (usize, usize),
+    value: (usize, usize),
+}
+
+#[inline]
+fn read_u32_le(data: &[u8], offset: usize) -> u32 {
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(&data[offset..offset + 4]);
+    u32::from_le_bytes(buf)
+}
+
+/// BlockIterator is an [`Iterator`] over a [`Block`], which yields [`Key`]s and [`Value`]s.
+///
+/// Keys are rebuilt from the prefix compression into a buffer of the iterator, so [`Key`]s
+/// are copied out of it, use [`key_ref`] to borrow them. [`Value`]s share the memory of the
+/// block.
+///
+/// The version of yielded values is set to the timestamp of their keys.
+///
+/// [`Iterator`]: ../iterator/trait.Iterator.html
+/// [`Block`]: struct.Block.html
+/// [`Key`]: ../struct.Key.html
+/// [`Value`]: ../struct.Value.html
+/// [`key_ref`]: ../iterator/trait.Iterator.html#method.key_ref
+#[derive(Debug)]
+pub struct BlockIterator {
+    block: Block,
+    /// the offset of the current entry, equals to restarts_offset if not valid.
+    current: usize,
+    /// the offset of the next entry
+    next: usize,
+    /// the index of the restart point which the current entry belongs to
+    restart_index: usize,
+    key: Vec<u8>,
+    value: (usize, usize),
+    /// the target of advance_to_next_user_key
+    scratch: Vec<u8>,
+    err: Option<Error>,
+}
+
+impl BlockIterator {
+    /// Moves the iterator to another block, keeping its buffers, so iterating over many
+    /// blocks does not allocate per block. Call [`rewind`] or [`seek`] to position it.
+    ///
+    /// [`rewind`]: ../iterator/trait.Iterator.html#tymethod.rewind
+    /// [`seek`]: ../iterator/trait.Iterator.html#tymethod.seek
+    #[inline]
+    pub fn reset(&mut self, block: Block) {
+        self.block = block;
+        self.key.clear();
+        self.invalidate(None);
+    }
+
+    /// Takes the error out, the iterator stays invalid until it is repositioned.
+    #[inline]
+    pub(crate) fn take_error(&mut self) -> Option<Error> {
+        self.err.take()
+    }
+
+    #[inline]
+    pub(crate) fn invalidate(&mut self, err: Option<Error>) {
+        self.current = self.block.restarts_offset;
+        self.next = self.block.restarts_offset;
+        self.restart_index = self.block.num_restarts;
+        self.err = err;
+    }
+
+    fn seek_to_restart(&mut self, idx: usize) {
+        self.key.clear();
+        self.err = None;
+        self.restart_index = idx;
+        match self.block.restart_point(idx) {
+            Ok(offset) => self.next = offset,
+            Err(e) => self.invalidate(Some(e)),
+        }
+    }
+
+    /// Parses the next entry, returns false if the end of block is reached or an error occurs.
+    fn parse_next(&mut self) -> bool {
+        self.current = self.next;
+        if self.err.is_some() || self.current >= self.block.restarts_offset {
+            let err = self.err.take();
+            self.invalidate(err);
+            return false;
+        }
+
+        let EntryInfo { shared, key, value } = match self.block.decode_entry(self.current) {
+            Ok(ent) => ent,
+            Err(e) => {
+                self.invalidate(Some(e));
+                return false;
+            }
+        };
+        if shared > self.key.len() {
+            self.invalidate(Some(Error::corruption("block: invalid shared key length")));
+            return false;
+        }
+
+        self.key.truncate(shared);
+        self.key.extend_from_slice(&self.block.data[key.0..key.1]);
+        self.value = value;
+        self.next = value.1;
+
+        while self.restart_index + 1 < self.block.num_restarts {
+            match self.block.restart_point(self.restart_index + 1) {
+                Ok(offset) if offset <= self.current => self.restart_index += 1,
+                Ok(_) => break,
+                Err(e) => {
+                    self.invalidate(Some(e));
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// Returns the last restart point in `left..num_restarts` whose key is less than the
+    /// target, or `left` if there is none.
+    fn find_restart(&self, mut left: usize, target: &[u8]) -> Result<usize, Error> {
+        let mut right = self.block.num_restarts - 1;
+        while left < right {
+            let mid = (left + right).div_ceil(2);
+            if compare_key_in(self.restart_key(mid)?, target) == Ordering::Less {
+                left = mid;
+            } else {
+                right = mid - 1;
+            }
+        }
+        Ok(left)
+    }
+
+    /// Returns the full key of the restart point.
+    fn restart_key(&self, idx: usize) -> Result<&[u8], Error> {
+        let offset = self.block.restart_point(idx)?;
+        let EntryInfo { shared, key, .. } = self.block.decode_entry(offset)?;
+        if shared != 0 {
+            return Err(Error::corruption("block: restart point shares key"));
+        }
+        Ok(&self.block.data[key.0..key.1])
+    }
+}
+
+impl Iterator<Key, Value> for BlockIterator {
+    #[inline]
+    fn next(&mut self) {
+        if self.valid() {
+            self.parse_next();
+        }
+    }
+
+    #[inline]
+    fn rewind(&mut self) {
+        self.seek_to_restart(0);
+        self.parse_next();
+    }
+
+    fn seek<Q: KeyExt>(&mut self, key: Q) {
+        let target = key.as_bytes();
+        match self.find_restart(0, target) {
+            Ok(idx) => self.seek_to_restart(idx),
+            Err(e) => return self.invalidate(Some(e)),
+        }
+        while self.parse_next() {
+            if compare_key_in(&self.key, target) != Ordering::Less {
+                return;
+            }
+        }
+    }
+
+    fn seek_from<Q: KeyExt>(&mut self, key: Q, from: SeekFrom) {
+        let target = key.as_bytes();
+        match from {
+            SeekFrom::Origin => self.seek(target),
+            SeekFrom::Current => {
+                if !self.valid() || compare_key_in(&self.key, target) != Ordering::Less {
+                    return;
+                }
+                // only jump if the target is beyond the next restart point, otherwise keep
+                // scanning from the current entry.
+                match self.find_restart(self.restart_index, target) {
+                    Ok(idx) if idx > self.restart_index => {
+                        self.seek_to_restart(idx);
+                        self.parse_next();
+                    }
+                    Ok(_) => {}
+                    Err(e) => return self.invalidate(Some(e)),
+                }
+                while self.valid() && compare_key_in(&self.key, target) == Ordering::Less {
+                    self.parse_next();
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn advance_to_next_user_key(&mut self) {
+        let mut scratch = mem::take(&mut self.scratch);
+        seek_to_next_user_key(self, &mut scratch);
+        self.scratch = scratch;
+    }
+
+    #[inline]
+    fn entry(&self) -> Option<(Key, Value)> {
+        self.key().zip(self.val())
+    }
+
+    #[inline]
+    fn key(&self) -> Option<Key> {
+        self.valid().then(|| Key::copy_from_slice(&self.key))
+    }
+
+    #[inline]
+    fn val(&self) -> Option<Value> {
+        self.valid().then(|| {
+            let (start, end) = self.value;
+            Value::decode_bytes(self.block.data.slice(start..end))
+                .set_version(self.key.parse_timestamp())
+        })
+    }
+
+    #[inline]
+    fn key_ref(&self) -> Option<KeyRef<'_>> {
+        self.valid().then(|| KeyRef::new(&self.key))
+    }
+
+    #[inline]
+    fn value_ref(&self) -> Option<ValueRef<'_>> {
+        self.valid().then(|| {
+            let (start, end) = self.value;
+            let mut val = ValueRef::decode_value_ref(&self.block.data[start..end]);
+            val.set_version(self.key.parse_timestamp());
+            val
+        })
+    }
+
+    #[inline]
+    fn valid(&self) -> bool {
+        self.err.is_none() && self.current < self.block.restarts_offset
+    }
+
+    #[inline]
+    fn error(&self) -> Option<&Error> {
+        self.err.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::format;
+
+    fn key(i: usize) -> Key {
+        Key::from(format!("key{:04}", i)).with_timestamp(i as u64)
+    }
+
+    fn build(n: usize, interval: usize) -> Block {
+        let mut builder = BlockBuilder::new().set_restart_interval(interval);
+        for i in 0..n {
+            let val = Value::from(format!("val{}", i)).set_meta(i as u8);
+            builder.add(key(i), &val.to_encoded());
+        }
+        assert_eq!(builder.len(), n);
+        let size = builder.estimated_size();
+        let data = builder.finish();
+        assert_eq!(data.len(), size);
+        assert!(builder.is_empty());
+        Block::new(data).unwrap()
+    }
+
+    #[test]
+    fn test_block() {
+        let block = build(100, 16);
+        assert_eq!(block.num_restarts(), 7);
+
+        let mut iter = block.iter();
+        assert!(!iter.valid());
+        iter.rewind();
+        let mut n = 0;
+        while iter.valid() {
+            let (k, v) = iter.entry().unwrap();
+            assert_eq!(k, key(n));
+            assert_eq!(k.parse_timestamp(), n as u64);
+            assert_eq!(v.parse_value(), format!("val{}", n).as_bytes());
+            assert_eq!(v.get_meta(), n as u8);
+            assert_eq!(v.get_version(), n as u64);
+            assert_eq!(iter.value_ref().unwrap(), v.as_value_ref());
+            iter.next();
+            n += 1;
+        }
+        assert_eq!(n, 100);
+        assert!(iter.status().is_ok());
+
+        for i in [0, 15, 16, 17, 50, 99] {
+            iter.seek(key(i));
+            assert_eq!(iter.key().unwrap(), key(i));
+        }
+
+        // seek to a key between two existing keys
+        iter.seek(Key::from("key0050a").with_timestamp(0));
+        assert_eq!(iter.key().unwrap(), key(51));
+        iter.seek(Key::from("key9999").with_timestamp(0));
+        assert!(!iter.valid());
+        iter.seek(Key::from("a").with_timestamp(0));
+        assert_eq!(iter.key().unwrap(), key(0));
+
+        let empty = Block::new(BlockBuilder::new().finish()).unwrap();
+        let mut iter = empty.iter();
+        iter.rewind();
+        assert!(!iter.valid());
+        iter.seek(key(0));
+        assert!(!iter.valid());
+    }
+
+    #[test]
+    fn test_block_seek_from() {
+        let block = build(100, 16);
+        let mut iter = block.iter();
+        iter.rewind();
+        // forward only, within a restart interval and across several of them.
+        for i in [3, 5, 15, 16, 40, 41, 99] {
+            iter.seek_from(key(i), SeekFrom::Current);
+            assert_eq!(iter.key().unwrap(), key(i));
+        }
+        iter.seek_from(key(10), SeekFrom::Current);
+        assert_eq!(iter.key().unwrap(), key(99));
+        iter.seek_from(key(10), SeekFrom::Origin);
+        assert_eq!(iter.key().unwrap(), key(10));
+        iter.seek_from(Key::from("key0050a").with_timestamp(0), SeekFrom::Current);
+        assert_eq!(iter.key().unwrap(), key(51));
+        iter.seek_from(Key::from("key9999").with_timestamp(0), SeekFrom::Current);
+        assert!(!iter.valid());
+        assert!(iter.status().is_ok());
+
+        // three versions for each user key
+        let mut builder = BlockBuilder::new().set_restart_interval(4);
+        for i in 0..20 {
+            for ts in [3, 2, 1] {
+                let k = Key::from(format!("key{:04}", i)).with_timestamp(ts);
+                builder.add(k, &Value::from(format!("val{}", i)).to_encoded());
+            }
+        }
+        let block = Block::new(builder.finish()).unwrap();
+        let mut iter = block.iter();
+        iter.rewind();
+        for i in 0..20 {
+            let k = iter.key().unwrap();
+            assert_eq!(k.parse_key(), format!("key{:04}", i).as_bytes());
+            assert_eq!(k.parse_timestamp(), 3);
+            iter.advance_to_next_user_key();
+        }
+        assert!(!iter.valid());
+
+        // from the middle of the versions
+        iter.seek(Key::from("key0007").with_timestamp(2));
+        iter.advance_to_next_user_key();
+        assert_eq!(iter.key().unwrap(), Key::from("key0008").with_timestamp(3));
+    }
+
+    #[test]
+    fn test_block_reset() {
+        let mut iter = build(100, 16).iter();
+        iter.rewind();
+        iter.seek(key(50));
+        let capacity = iter.key.capacity();
+
+        let mut builder = BlockBuilder::new();
+        builder.add(
+            Key::from("other").with_timestamp(1),
+            &Value::from("v").to_encoded(),
+        );
+        iter.reset(Block::new(builder.finish()).unwrap());
+        assert!(!iter.valid());
+        assert_eq!(iter.key.capacity(), capacity);
+        iter.rewind();
+        assert_eq!(iter.key().unwrap(), Key::from("other").with_timestamp(1));
+        iter.next();
+        assert!(!iter.valid());
+        assert!(iter.status().is_ok());
+    }
+
+    #[test]
+    fn test_block_corruption() {
+        let data = build(10, 4).as_bytes().to_vec();
+
+        let mut corrupted = data.clone();
+        corrupted[3] ^= 0xff;
+        assert!(matches!(
+            Block::new(Bytes::from(corrupted)),
+            Err(Error::ChecksumMismatch { .. })
+        ));
+
+        assert!(matches!(
+            Block::new(Bytes::from(data[..6].to_vec())),
+            Err(Error::Corruption(_))
+        ));
+
+        // a valid checksum over a broken entry
+        let mut broken = data[..data.len() - 4].to_vec();
+        broken[2] = 0xff;
+        let crc = checksum(&broken);
+        broken.put_u32_le(crc);
+        let block = Block::new(Bytes::from(broken)).unwrap();
+        let mut iter = block.iter();
+        iter.rewind();
+        assert!(!iter.valid());
+        assert!(matches!(iter.status(), Err(Error::Corruption(_))));
+    }
+}
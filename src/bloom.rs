@@ -0,0 +1,303 @@
+use crate::bytes::Bytes;
+use crate::table::FilterPolicy;
+use crate::KeyExt;
+use alloc::vec::Vec;
+
+/// The default number of bits per key, which gives about 1% false positive rate.
+pub const DEFAULT_BITS_PER_KEY: usize = 10;
+
+/// The max number of probes, filters with more probes are treated as matching everything.
+const MAX_PROBES: u8 = 30;
+
+/// BloomFilter is a membership filter of user keys.
+///
+/// Keys are hashed by [`KeyExt::parse_key`], so all the versions of a user key hit the same
+/// bits. The serialized form is the bit array followed by one byte of the number of probes.
+///
+/// [`KeyExt::parse_key`]: ../trait.KeyExt.html#method.parse_key
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct BloomFilter {
+    data: Bytes,
+}
+
+impl BloomFilter {
+    /// Returns a BloomFilter from the serialized form
+    #[inline]
+    pub fn from_bytes(data: Bytes) -> Self {
+        Self { data }
+    }
+
+    /// Returns the serialized form
+    #[inline]
+    pub fn as_bytes(&self) -> &Bytes {
+        &self.data
+    }
+
+    /// Returns the serialized form
+    #[inline]
+    pub fn into_bytes(self) -> Bytes {
+        self.data
+    }
+
+    /// Returns false if no version of the key has been added to the filter.
+    #[inline]
+    pub fn may_contain(&self, key: impl KeyExt) -> bool {
+        self.may_contain_user_key(key.parse_key())
+    }
+
+    /// Returns false if the key without timestamp has not been added to the filter.
+    #[inline]
+    pub(crate) fn may_contain_user_key(&self, user_key: &[u8]) -> bool {
+        may_match(bloom_hash(user_key), &self.data)
+    }
+}
+
+/// BloomFilterBuilder collects the keys and builds a [`BloomFilter`].
+///
+/// [`BloomFilter`]: struct.BloomFilter.html
+#[derive(Debug, Clone)]
+pub struct BloomFilterBuilder {
+    bits_per_key: usize,
+    hashes: Vec<u32>,
+}
+
+impl Default for BloomFilterBuilder {
+    fn default() -> Self {
+        Self::new(DEFAULT_BITS_PER_KEY)
+    }
+}
+
+impl BloomFilterBuilder {
+    /// Returns a BloomFilterBuilder, the more bits per key, the lower false positive rate.
+    #[inline]
+    pub fn new(bits_per_key: usize) -> Self {
+        Self {
+            bits_per_key,
+            hashes: Vec::new(),
+        }
+    }
+
+    /// Get the number of bits per key
+    #[inline]
+    pub fn get_bits_per_key(&self) -> usize {
+        self.bits_per_key
+    }
+
+    /// Adds a key to the filter, the timestamp of the key is ignored.
+    #[inline]
+    pub fn add(&mut self, key: impl KeyExt) {
+        self.add_user_key(key.parse_key())
+    }
+
+    /// Adds a key without timestamp to the filter.
+    #[inline]
+    pub(crate) fn add_user_key(&mut self, user_key: &[u8]) {
+        let h = bloom_hash(user_key);
+        // the versions of a key are usually added together.
+        if self.hashes.last() != Some(&h) {
+            self.hashes.push(h);
+        }
+    }
+
+    /// Returns the number of hashes collected
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    /// Returns true if no key has been added
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    /// Builds the filter, the builder is cleared and can be reused.
+    pub fn finish(&mut self) -> BloomFilter {
+        let mut data = Vec::new();
+        build_filter(&self.hashes, self.bits_per_key, &mut data);
+        self.hashes.clear();
+        BloomFilter::from_bytes(Bytes::from(data))
+    }
+}
+
+/// BloomFilterPolicy is a [`FilterPolicy`] building Bloom filters for tables.
+///
+/// [`FilterPolicy`]: ../table/trait.FilterPolicy.html
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct BloomFilterPolicy {
+    bits_per_key: usize,
+}
+
+impl Default for BloomFilterPolicy {
+    fn default() -> Self {
+        Self::new(DEFAULT_BITS_PER_KEY)
+    }
+}
+
+impl BloomFilterPolicy {
+    /// Returns a BloomFilterPolicy with the number of bits per key.
+    #[inline]
+    pub const fn new(bits_per_key: usize) -> Self {
+        Self { bits_per_key }
+    }
+}
+
+impl FilterPolicy for BloomFilterPolicy {
+    fn name(&self) -> &str {
+        "kvstructs.BloomFilter"
+    }
+
+    fn create_filter(&self, keys: &[&[u8]], dst: &mut Vec<u8>) {
+        let hashes = keys.iter().map(|k| bloom_hash(k)).collect::<Vec<_>>();
+        build_filter(&hashes, self.bits_per_key, dst);
+    }
+
+    #[inline]
+    fn may_match(&self, key: &[u8], filter: &[u8]) -> bool {
+        may_match(bloom_hash(key), filter)
+    }
+}
+
+/// Appends the filter of the hashes to dst.
+fn build_filter(hashes: &[u32], bits_per_key: usize, dst: &mut Vec<u8>) {
+    // k = bits_per_key * ln(2) minimizes the false positive rate.
+    let k = (bits_per_key * 69 / 100).clamp(1, MAX_PROBES as usize) as u8;
+    // too small filter has a high false positive rate.
+    let bits = (hashes.len() * bits_per_key).max(64);
+    let bytes = bits.div_ceil(8);
+    let bits = bytes * 8;
+
+    let start = dst.len();
+    dst.resize(start + bytes, 0);
+    dst.push(k);
+    let array = &mut dst[start..start + bytes];
+    for h in hashes {
+        let mut h = *h;
+        let delta = h.rotate_right(17);
+        for _ in 0..k {
+            let pos = h as usize % bits;
+            array[pos / 8] |= 1 << (pos % 8);
+            h = h.wrapping_add(delta);
+        }
+    }
+}
+
+fn may_match(mut h: u32, filter: &[u8]) -> bool {
+    let (k, array) = match filter.split_last() {
+        Some((k, array)) if !array.is_empty() => (*k, array),
+        _ => return false,
+    };
+    if k > MAX_PROBES {
+        // reserved for other encodings.
+        return true;
+    }
+
+    let bits = array.len() * 8;
+    let delta = h.rotate_right(17);
+    for _ in 0..k {
+        let pos = h as usize % bits;
+        if array[pos / 8] & (1 << (pos % 8)) == 0 {
+            return false;
+        }
+        h = h.wrapping_add(delta);
+    }
+    true
+}
+
+#[inline]
+fn bloom_hash(key: &[u8]) -> u32 {
+    hash(key, 0xbc9f_1d34)
+}
+
+/// The murmur-like hash used by LevelDB.
+fn hash(data: &[u8], seed: u32) -> u32 {
+    const M: u32 = 0xc6a4_a793;
+    const R: u32 = 24;
+    let mut h = seed ^ (data.len() as u32).wrapping_mul(M);
+
+    let mut chunks = data.chunks_exact(4);
+    for c in &mut chunks {
+        let w = u32::from_le_bytes([c[0], c[1], c[2], c[3]]);
+        h = h.wrapping_add(w).wrapping_mul(M);
+        h ^= h >> 16;
+    }
+
+    let rest = chunks.remainder();
+    if rest.len() >= 3 {
+        h = h.wrapping_add((rest[2] as u32) << 16);
+    }
+    if rest.len() >= 2 {
+        h = h.wrapping_add((rest[1] as u32) << 8);
+    }
+    if !rest.is_empty() {
+        h = h.wrapping_add(rest[0] as u32).wrapping_mul(M);
+        h ^= h >> R;
+    }
+    h
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Key;
+
+    fn key(i: u32) -> Key {
+        Key::from(i.to_le_bytes().to_vec()).with_timestamp(1)
+    }
+
+    #[test]
+    fn test_bloom_filter() {
+        let mut builder = BloomFilterBuilder::default();
+        assert!(!builder.finish().may_contain(key(0)));
+
+        for i in 0..10_000 {
+            builder.add(key(i));
+            builder.add(key(i).parse_new_key().with_timestamp(0));
+        }
+        assert_eq!(builder.len(), 10_000);
+        let filter = builder.finish();
+        let filter = BloomFilter::from_bytes(filter.into_bytes());
+
+        for i in 0..10_000 {
+            assert!(filter.may_contain(key(i)));
+            assert!(filter.may_contain(key(i).parse_new_key().with_timestamp(99)));
+        }
+
+        let fp = (10_000..20_000)
+            .filter(|i| filter.may_contain(key(*i)))
+            .count();
+        assert!(fp < 200, "false positive rate is too high: {}/10000", fp);
+    }
+
+    #[test]
+    fn test_false_positive_rate() {
+        // (bits per key, the max false positives of 10000 absent keys), about twice the
+        // theoretical rates of ~9%, ~0.8% and ~0.01%.
+        for (bits_per_key, max) in [(5, 2000), (10, 200), (20, 10)] {
+            let mut builder = BloomFilterBuilder::new(bits_per_key);
+            for i in 0..10_000 {
+                builder.add(key(i));
+            }
+            let filter = builder.finish();
+            let fp = (10_000..20_000)
+                .filter(|i| filter.may_contain(key(*i)))
+                .count();
+            assert!(
+                fp <= max,
+                "false positive rate is too high with {} bits per key: {}/10000",
+                bits_per_key,
+                fp
+            );
+        }
+    }
+
+    #[test]
+    fn test_hash() {
+        // the test vectors of LevelDB
+        assert_eq!(hash(&[], 0xbc9f1d34), 0xbc9f1d34);
+        assert_eq!(hash(&[0x62], 0xbc9f1d34), 0xef1345c4);
+        assert_eq!(hash(&[0xc3, 0x97], 0xbc9f1d34), 0x5b663814);
+        assert_eq!(hash(&[0xe2, 0x99, 0xa5], 0xbc9f1d34), 0x323c078f);
+        assert_eq!(hash(&[0xe1, 0x80, 0xb9, 0x32], 0xbc9f1d34), 0xed21633a);
+    }
+}
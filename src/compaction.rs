@@ -0,0 +1,431 @@
+use crate::iterator::{with_key, Iterator};
+use crate::{Error, Key, KeyExt, Value, ValueExt, OP};
+use alloc::format;
+use alloc::vec::Vec;
+
+/// The decision of a [`CompactionFilter`] on a version of a key.
+///
+/// [`CompactionFilter`]: trait.CompactionFilter.html
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Decision {
+    /// Keeps the version
+    Keep,
+    /// Drops the version
+    Remove,
+    /// Keeps the version with a new value
+    ChangeValue(Value),
+}
+
+/// CompactionFilter decides which versions of a key survive a compaction.
+pub trait CompactionFilter {
+    /// Returns the name of the filter
+    fn name(&self) -> &str;
+
+    /// Decides on all the versions of a user key, which are sorted from the newest to the
+    /// oldest. Exactly one decision per version must be pushed to `decisions`, in the same order.
+    fn filter(&mut self, versions: &[(Key, Value)], decisions: &mut Vec<Decision>);
+}
+
+/// Runs the filter over the iterator from its current position to the end, and calls `emit`
+/// with every kept version, in the order of the iterator.
+///
+/// `removed` is called with every removed version, and with the old version of every
+/// [`Decision::ChangeValue`], so that the dropped values can be recorded, e.g. by
+/// [`DiscardStats::record`]. An error returned by `removed` stops the compaction.
+///
+/// Returns an error if the filter does not decide on every version, or if the iterator fails.
+/// The versions of the user key being read when the iterator fails are neither emitted nor
+/// removed, the output should be dropped then.
+///
+/// [`Decision::ChangeValue`]: enum.Decision.html#variant.ChangeValue
+/// [`DiscardStats::record`]: ../discard/struct.DiscardStats.html#method.record
+pub fn run_compaction_filter<I, K, V, F>(
+    iter: &mut I,
+    filter: &mut F,
+    mut emit: impl FnMut(Key, Value),
+    mut removed: impl FnMut(Key, Value) -> Result<(), Error>,
+) -> Result<(), Error>
+where
+    I: Iterator<K, V>,
+    K: KeyExt,
+    V: ValueExt,
+    F: CompactionFilter + ?Sized,
+{
+    let mut versions: Vec<(Key, Value)> = Vec::new();
+    let mut decisions = Vec::new();
+    loop {
+        // a failed iterator is not the end of the input, the last versions may be missing.
+        if let Some(e) = iter.error() {
+            return Err(Error::corruption(format!(
+                "compaction: iterator failed: {}",
+                e
+            )));
+        }
+
+        let same = match versions.first() {
+            Some((first, _)) => with_key(iter, |k| k.same_key(first)).unwrap_or(false),
+            None => false,
+        };
+
+        if !same && !versions.is_empty() {
+            decisions.clear();
+            filter.filter(&versions, &mut decisions);
+            if decisions.len() != versions.len() {
+                return Err(Error::corruption(format!(
+                    "compaction: filter {} decided on {} of {} versions",
+                    filter.name(),
+                    decisions.len(),
+                    versions.len()
+                )));
+            }
+            for ((key, val), decision) in versions.drain(..).zip(decisions.drain(..)) {
+                match decision {
+                    Decision::Keep => emit(key, val),
+                    Decision::Remove => removed(key, val)?,
+                    Decision::ChangeValue(new) => {
+                        emit(key.clone(), new);
+                        removed(key, val)?;
+                    }
+                }
+            }
+        }
+
+        // copies the version once if the iterator can lend it.
+        let version = match (iter.key_ref(), iter.value_ref()) {
+            (Some(k), Some(v)) => Some((k.to_key(), v.to_value())),
+            _ => iter
+                .entry()
+                .map(|(k, v)| (k.as_key_ref().to_key(), v.as_value_ref().to_value())),
+        };
+        match version {
+            Some(version) => {
+                versions.push(version);
+                iter.next();
+            }
+            None => return Ok(()),
+        }
+    }
+}
+
+/// VersionFilter is a [`CompactionFilter`] following the rules of Badger.
+///
+/// Versions newer than the discard timestamp (the oldest timestamp any reader may still
+/// use) are always kept. For the other versions, from the newest to the oldest:
+///
+/// 1. Merge entries ([`OP::BIT_MERGE_ENTRY`]) are kept and not counted.
+/// 2. The first version which is deleted or expired, has [`OP::BIT_DISCARD_EARLIER_VERSIONS`],
+///    or is the `num_versions_to_keep`-th version, is the last one kept, every older version is removed.
+/// 3. When compacting into the bottommost level, the last kept version is removed as well if it is
+///    deleted or expired, because there is no older version left for it to hide.
+///
+/// [`CompactionFilter`]: trait.CompactionFilter.html
+/// [`OP::BIT_MERGE_ENTRY`]: ../struct.OP.html#associatedconstant.BIT_MERGE_ENTRY
+/// [`OP::BIT_DISCARD_EARLIER_VERSIONS`]: ../struct.OP.html#associatedconstant.BIT_DISCARD_EARLIER_VERSIONS
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct VersionFilter {
+    discard_ts: u64,
+    num_versions_to_keep: usize,
+    now: u64,
+    bottommost: bool,
+}
+
+impl VersionFilter {
+    /// Returns a VersionFilter keeping one version at or below `discard_ts`.
+    ///
+    /// The expiration of values is checked against the current system time when the `std`
+    /// feature is enabled, otherwise values never expire until [`set_now`] is called.
+    ///
+    /// [`set_now`]: #method.set_now
+    #[inline]
+    pub fn new(discard_ts: u64) -> Self {
+        #[cfg(feature = "std")]
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        #[cfg(not(feature = "std"))]
+        let now = 0;
+
+        Self {
+            discard_ts,
+            num_versions_to_keep: 1,
+            now,
+            bottommost: false,
+        }
+    }
+
+    /// Set the number of versions at or below the discard timestamp to keep, at least 1.
+    #[inline]
+    pub fn set_num_versions_to_keep(mut self, num: usize) -> Self {
+        self.num_versions_to_keep = num.max(1);
+        self
+    }
+
+    /// Get the number of versions at or below the discard timestamp to keep
+    #[inline]
+    pub fn get_num_versions_to_keep(&self) -> usize {
+        self.num_versions_to_keep
+    }
+
+    /// Set the time (unix timestamp) used to check the expiration of values
+    #[inline]
+    pub fn set_now(mut self, now: u64) -> Self {
+        self.now = now;
+        self
+    }
+
+    /// Set whether the compaction outputs to the bottommost level
+    #[inline]
+    pub fn set_bottommost(mut self, bottommost: bool) -> Self {
+        self.bottommost = bottommost;
+        self
+    }
+
+    /// Get the discard timestamp
+    #[inline]
+    pub fn get_discard_ts(&self) -> u64 {
+        self.discard_ts
+    }
+}
+
+impl CompactionFilter for VersionFilter {
+    fn name(&self) -> &str {
+        "kvstructs.VersionFilter"
+    }
+
+    fn filter(&mut self, versions: &[(Key, Value)], decisions: &mut Vec<Decision>) {
+        let mut num_versions = 0;
+        let mut skipping = false;
+        for (key, val) in versions {
+            if key.parse_timestamp() > self.discard_ts {
+                decisions.push(Decision::Keep);
+                continue;
+            }
+            if skipping {
+                decisions.push(Decision::Remove);
+                continue;
+            }
+
+            let op = OP::from_bits_truncate(val.get_meta());
+            if op.contains(OP::BIT_MERGE_ENTRY) {
+                decisions.push(Decision::Keep);
+                continue;
+            }
+
+            num_versions += 1;
+            let dead = val.is_deleted_or_expired(self.now);
+            skipping = dead
+                || op.contains(OP::BIT_DISCARD_EARLIER_VERSIONS)
+                || num_versions == self.num_versions_to_keep;
+            if dead && self.bottommost {
+                decisions.push(Decision::Remove);
+            } else {
+                decisions.push(Decision::Keep);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bytes::Bytes;
+    use crate::iterator::test::VecIterator;
+    use crate::table::{Table, TableBuilder, TableOptions};
+
+    type Versions = Vec<(Key, Value)>;
+
+    fn table(entries: &[(&str, u64, Value)]) -> Table {
+        let mut builder = TableBuilder::new(TableOptions::default());
+        for (k, ts, v) in entries {
+            builder.add(Key::from(k.as_bytes()).with_timestamp(*ts), v.clone());
+        }
+        Table::from_bytes(builder.finish(), &TableOptions::default()).unwrap()
+    }
+
+    /// Returns the kept and the removed versions.
+    fn run(
+        filter: &mut impl CompactionFilter,
+        entries: &[(&str, u64, Value)],
+    ) -> Result<(Versions, Versions), Error> {
+        let table = table(entries);
+        let mut iter = table.iter();
+        iter.rewind();
+        let (mut out, mut removed) = (Vec::new(), Vec::new());
+        run_compaction_filter(
+            &mut iter,
+            filter,
+            |k, v| out.push((k, v)),
+            |k, v| {
+                removed.push((k, v));
+                Ok(())
+            },
+        )?;
+        assert!(iter.status().is_ok());
+        Ok((out, removed))
+    }
+
+    fn versions(out: &[(Key, Value)], user_key: &str) -> Vec<u64> {
+        out.iter()
+            .filter(|(k, _)| k.parse_key() == user_key.as_bytes())
+            .map(|(k, _)| k.parse_timestamp())
+            .collect()
+    }
+
+    #[test]
+    fn test_version_filter() {
+        let val = Value::new().set_data(Bytes::from("v"));
+        let del = Value::new().set_meta(OP::BIT_DELETE.bits());
+        let merge = Value::new().set_meta(OP::BIT_MERGE_ENTRY.bits());
+        let discard = val
+            .clone()
+            .set_meta(OP::BIT_DISCARD_EARLIER_VERSIONS.bits());
+        let expired = val.clone().set_expires_at(50);
+        let entries = [
+            ("a", 12, val.clone()),
+            ("a", 9, val.clone()),
+            ("a", 8, val.clone()),
+            ("a", 7, val.clone()),
+            ("b", 9, merge.clone()),
+            ("b", 8, val.clone()),
+            ("b", 7, val.clone()),
+            ("c", 8, discard),
+            ("c", 7, val.clone()),
+            ("d", 9, del),
+            ("d", 8, val.clone()),
+            ("e", 8, expired),
+            ("e", 7, val.clone()),
+            ("f", 11, val.clone()),
+        ];
+
+        let mut filter = VersionFilter::new(10).set_now(100);
+        let (out, removed) = run(&mut filter, &entries).unwrap();
+        assert_eq!(out.len() + removed.len(), entries.len());
+        assert_eq!(versions(&removed, "a"), [8, 7]);
+        assert_eq!(versions(&out, "a"), [12, 9]);
+        assert_eq!(versions(&out, "b"), [9, 8]);
+        assert_eq!(versions(&out, "c"), [8]);
+        assert_eq!(versions(&out, "d"), [9]);
+        assert_eq!(versions(&out, "e"), [8]);
+        assert_eq!(versions(&out, "f"), [11]);
+
+        let mut filter = filter.set_num_versions_to_keep(2).set_bottommost(true);
+        let (out, removed) = run(&mut filter, &entries).unwrap();
+        assert_eq!(versions(&removed, "d"), [9, 8]);
+        assert_eq!(versions(&out, "a"), [12, 9, 8]);
+        assert_eq!(versions(&out, "b"), [9, 8, 7]);
+        assert_eq!(versions(&out, "c"), [8]);
+        assert!(versions(&out, "d").is_empty());
+        assert!(versions(&out, "e").is_empty());
+    }
+
+    /// Replaces the newest version, and decides on `n` versions at most.
+    struct Replace {
+        n: usize,
+    }
+
+    impl CompactionFilter for Replace {
+        fn name(&self) -> &str {
+            "replace"
+        }
+
+        fn filter(&mut self, versions: &[(Key, Value)], decisions: &mut Vec<Decision>) {
+            decisions.push(Decision::ChangeValue(Value::from("new")));
+            decisions.extend(core::iter::repeat_n(
+                Decision::Keep,
+                versions.len().min(self.n) - 1,
+            ));
+        }
+    }
+
+    #[test]
+    fn test_compaction_filter() {
+        let entries = [
+            ("a", 2, Value::from("old")),
+            ("a", 1, Value::from("old")),
+            ("b", 1, Value::from("old")),
+        ];
+
+        let (out, removed) = run(&mut Replace { n: usize::MAX }, &entries).unwrap();
+        assert_eq!(out.len(), 3);
+        assert_eq!(out[0].1.parse_value(), b"new");
+        assert_eq!(out[1].1.parse_value(), b"old");
+        // the replaced values
+        assert_eq!(removed.len(), 2);
+        assert!(removed.iter().all(|(_, v)| v.parse_value() == b"old"));
+
+        // the filter skips a version of "a"
+        assert!(matches!(
+            run(&mut Replace { n: 1 }, &entries),
+            Err(Error::Corruption(_))
+        ));
+
+        // the error of removed stops the compaction
+        let table = table(&entries);
+        let mut iter = table.iter();
+        iter.rewind();
+        let mut out = Vec::new();
+        let res = run_compaction_filter(
+            &mut iter,
+            &mut Replace { n: usize::MAX },
+            |k, v| out.push((k, v)),
+            |_, _| Err(Error::corruption("stop")),
+        );
+        assert!(res.is_err());
+        assert_eq!(out.len(), 1);
+    }
+
+    #[test]
+    fn test_compaction_iterator_error() {
+        let data = [("a", 3), ("a", 2), ("a", 1), ("b", 2), ("b", 1)]
+            .iter()
+            .map(|(k, ts)| (Key::from(*k).with_timestamp(*ts), Value::from("old")))
+            .collect();
+        // fails in the middle of the versions of "b"
+        let mut iter = VecIterator::new(data).fail_at(4);
+        iter.rewind();
+        let mut out = Vec::new();
+        let res = run_compaction_filter(
+            &mut iter,
+            &mut Replace { n: usize::MAX },
+            |k, v| out.push((k, v)),
+            |_, _| Ok(()),
+        );
+        assert!(matches!(res, Err(Error::Corruption(_))));
+        assert_eq!(versions(&out, "a"), [3, 2, 1]);
+        assert!(versions(&out, "b").is_empty());
+    }
+
+    #[test]
+    fn test_discard_stats() {
+        let vp = |fid: u32, len: u32| {
+            Value::new()
+                .set_meta(OP::BIT_VALUE_POINTER.bits())
+                .set_data(Bytes::copy_from_slice(
+                    &crate::value_pointer::ValuePointer::new(fid, len, 0).encode(),
+                ))
+        };
+        let entries = [
+            ("a", 3, vp(1, 100)),
+            ("a", 2, vp(1, 200)),
+            ("a", 1, vp(2, 300)),
+            ("b", 2, Value::from("inline")),
+            ("b", 1, vp(2, 400)),
+        ];
+        let table = table(&entries);
+        let mut iter = table.iter();
+        iter.rewind();
+        let mut stats = crate::discard::DiscardStats::new();
+        let mut out = Vec::new();
+        run_compaction_filter(
+            &mut iter,
+            &mut VersionFilter::new(10),
+            |k, v| out.push((k, v)),
+            |_, v| stats.record(&v).map(|_| ()),
+        )
+        .unwrap();
+        assert_eq!(out.len(), 2);
+        assert_eq!(stats.get(1), 200);
+        assert_eq!(stats.get(2), 700);
+    }
+}
@@ -0,0 +1,203 @@
+use crate::bytes::Bytes;
+use crate::value_pointer::ValuePointer;
+use crate::{binary_put_uvariant_to_bufmut, decode_uvarint, Entry, Error, Key, Value, ValueExt};
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+/// The internal key which [`DiscardStats`] is persisted under.
+///
+/// [`DiscardStats`]: struct.DiscardStats.html
+pub const DISCARD_STATS_KEY: &[u8] = b"!kvstructs!discard";
+
+/// DiscardStats accumulates the number of discarded bytes in each value log file, which is
+/// used to pick the files for garbage collection.
+///
+/// The encoded stats is a uvarint count followed by pairs of uvarint fid and discarded bytes.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct DiscardStats {
+    stats: BTreeMap<u32, u64>,
+}
+
+impl DiscardStats {
+    /// Returns an empty DiscardStats
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            stats: BTreeMap::new(),
+        }
+    }
+
+    /// Adds discarded bytes to the value log file, the sum saturates at `u64::MAX`.
+    #[inline]
+    pub fn add(&mut self, fid: u32, discarded: u64) {
+        if discarded > 0 {
+            let total = self.stats.entry(fid).or_insert(0);
+            *total = total.saturating_add(discarded);
+        }
+    }
+
+    /// Records a value dropped by compaction, values which are not [`ValuePointer`]s are
+    /// ignored. Returns the pointer if the value is one.
+    ///
+    /// It is usually called by the `removed` callback of [`run_compaction_filter`].
+    ///
+    /// [`ValuePointer`]: ../value_pointer/struct.ValuePointer.html
+    /// [`run_compaction_filter`]: ../compaction/fn.run_compaction_filter.html
+    #[inline]
+    pub fn record(&mut self, val: &impl ValueExt) -> Result<Option<ValuePointer>, Error> {
+        let vp = ValuePointer::from_value(val)?;
+        if let Some(vp) = vp {
+            self.add(vp.get_fid(), vp.get_len() as u64);
+        }
+        Ok(vp)
+    }
+
+    /// Merges the other stats into this one
+    #[inline]
+    pub fn merge(&mut self, other: &DiscardStats) {
+        for (fid, discarded) in &other.stats {
+            self.add(*fid, *discarded);
+        }
+    }
+
+    /// Returns the discarded bytes of the value log file
+    #[inline]
+    pub fn get(&self, fid: u32) -> u64 {
+        self.stats.get(&fid).copied().unwrap_or(0)
+    }
+
+    /// Removes the value log file, which is usually called after the file is collected.
+    #[inline]
+    pub fn remove(&mut self, fid: u32) -> Option<u64> {
+        self.stats.remove(&fid)
+    }
+
+    /// Returns the number of value log files which have discarded bytes
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.stats.len()
+    }
+
+    /// Returns true if no file has discarded bytes
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.stats.is_empty()
+    }
+
+    /// Returns an iterator over the file ids and their discarded bytes, ordered by file id.
+    #[inline]
+    pub fn iter(&self) -> impl core::iter::Iterator<Item = (u32, u64)> + '_ {
+        self.stats.iter().map(|(fid, discarded)| (*fid, *discarded))
+    }
+
+    /// Ranks the value log files by their discard ratios, from the highest to the lowest.
+    ///
+    /// `file_size` returns the size of a value log file, files it returns `None` or zero
+    /// for (e.g. deleted files) are skipped.
+    pub fn rank(&self, mut file_size: impl FnMut(u32) -> Option<u64>) -> Vec<(u32, f64)> {
+        let mut ranked = self
+            .stats
+            .iter()
+            .filter_map(|(fid, discarded)| match file_size(*fid) {
+                Some(size) if size > 0 => Some((*fid, (*discarded as f64 / size as f64).min(1.0))),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked
+    }
+
+    /// Encodes the stats
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        binary_put_uvariant_to_bufmut(&mut buf, self.stats.len() as u64);
+        for (fid, discarded) in &self.stats {
+            binary_put_uvariant_to_bufmut(&mut buf, *fid as u64);
+            binary_put_uvariant_to_bufmut(&mut buf, *discarded);
+        }
+        buf
+    }
+
+    /// Decodes the stats from the encoded form
+    pub fn decode(mut src: &[u8]) -> Result<Self, Error> {
+        let mut uvarint = || {
+            decode_uvarint(src).map(|(x, n)| {
+                src = &src[n..];
+                x
+            })
+        };
+
+        let mut stats = BTreeMap::new();
+        for _ in 0..uvarint()? {
+            let fid = u32::try_from(uvarint()?)
+                .map_err(|_| Error::corruption("discard stats: bad file id"))?;
+            stats.insert(fid, uvarint()?);
+        }
+        if !src.is_empty() {
+            return Err(Error::corruption("discard stats: trailing bytes"));
+        }
+        Ok(Self { stats })
+    }
+
+    /// Returns the entry persisting the stats under [`DISCARD_STATS_KEY`] at timestamp `ts`.
+    ///
+    /// [`DISCARD_STATS_KEY`]: constant.DISCARD_STATS_KEY.html
+    #[inline]
+    pub fn to_entry(&self, ts: u64) -> Entry {
+        Entry::new_from_kv(
+            Key::copy_from_slice(DISCARD_STATS_KEY).with_timestamp(ts),
+            Value::new().set_data(Bytes::from(self.encode())),
+        )
+    }
+
+    /// Decodes the stats from the value of a persisted entry
+    #[inline]
+    pub fn from_value(val: &impl ValueExt) -> Result<Self, Error> {
+        Self::decode(val.parse_value())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{KeyExt, OP};
+
+    #[test]
+    fn test_discard_stats() {
+        let mut stats = DiscardStats::new();
+        for (fid, len) in [(1, 100), (2, 300), (1, 50), (3, 10)] {
+            let val = Value::new()
+                .set_meta(OP::BIT_VALUE_POINTER.bits())
+                .set_data(Bytes::copy_from_slice(
+                    &ValuePointer::new(fid, len, 0).encode(),
+                ));
+            assert!(stats.record(&val).unwrap().is_some());
+        }
+        assert!(stats
+            .record(&Value::new().set_data(Bytes::from("inline")))
+            .unwrap()
+            .is_none());
+        assert_eq!(stats.get(1), 150);
+        assert_eq!(stats.len(), 3);
+
+        let sizes = [(1, 1000), (2, 600)];
+        let ranked = stats.rank(|fid| sizes.iter().find(|(f, _)| *f == fid).map(|(_, s)| *s));
+        assert_eq!(ranked, [(2, 0.5), (1, 0.15)]);
+
+        let ent = stats.to_entry(7);
+        assert_eq!(ent.get_key().parse_key(), DISCARD_STATS_KEY);
+        let decoded = DiscardStats::from_value(ent.get_value()).unwrap();
+        assert_eq!(decoded, stats);
+        assert!(DiscardStats::decode(&[2, 1]).is_err());
+
+        let mut merged = DiscardStats::new();
+        merged.add(1, 1);
+        merged.merge(&stats);
+        assert_eq!(merged.get(1), 151);
+        assert_eq!(merged.remove(1), Some(151));
+        assert_eq!(merged.get(1), 0);
+
+        merged.add(2, u64::MAX);
+        assert_eq!(merged.get(2), u64::MAX);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::bytes::BufMut;
+use crate::vlog::VlogRecord;
 use crate::OP;
 use crate::{EncodedValue, Key, Value, ValueExt};
 
@@ -167,4 +169,13 @@ impl Entry {
     pub fn encoded_value(&self) -> EncodedValue {
         self.val.to_encoded()
     }
+
+    /// Encodes the entry as a value log record into the buffer without intermediate
+    /// allocation, returns the length of the record, see [`VlogRecord::encode_to`].
+    ///
+    /// [`VlogRecord::encode_to`]: vlog/struct.VlogRecord.html#method.encode_to
+    #[inline]
+    pub fn encode_to(&self, buf: &mut impl BufMut) -> u32 {
+        VlogRecord::encode_to(&self.key, self.val.as_value_ref(), buf)
+    }
 }
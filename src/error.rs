@@ -0,0 +1,90 @@
+use alloc::borrow::Cow;
+use core::fmt;
+
+/// Errors when reading or decoding the key-value structs.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// I/O error
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+    /// The checksum of the data does not match the expected one
+    ChecksumMismatch {
+        /// The checksum stored along with the data
+        expected: u32,
+        /// The checksum calculated from the data
+        actual: u32,
+    },
+    /// The data is corrupted
+    Corruption(Cow<'static, str>),
+    /// The arena does not have enough space
+    ArenaFull {
+        /// The number of bytes required
+        required: usize,
+        /// The number of bytes remaining in the arena
+        remaining: usize,
+    },
+}
+
+impl Error {
+    /// Returns a corruption error with the message
+    #[inline]
+    pub fn corruption(msg: impl Into<Cow<'static, str>>) -> Self {
+        Self::Corruption(msg.into())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(feature = "std")]
+            Error::Io(e) => write!(f, "kvstructs: {}", e),
+            Error::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "kvstructs: checksum mismatch, expected {:#010x}, actual {:#010x}",
+                expected, actual
+            ),
+            Error::Corruption(msg) => write!(f, "kvstructs: data corruption: {}", msg),
+            Error::ArenaFull {
+                required,
+                remaining,
+            } => write!(
+                f,
+                "kvstructs: arena does not have enough space, required {} bytes, remaining {} bytes",
+                required, remaining
+            ),
+        }
+    }
+}
+
+cfg_std! {
+    impl std::error::Error for Error {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            match self {
+                Error::Io(e) => Some(e),
+                _ => None,
+            }
+        }
+    }
+
+    impl From<std::io::Error> for Error {
+        fn from(e: std::io::Error) -> Self {
+            Error::Io(e)
+        }
+    }
+
+    impl From<Error> for std::io::Error {
+        fn from(e: Error) -> Self {
+            match e {
+                Error::Io(e) => e,
+                e => std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+            }
+        }
+    }
+
+    impl From<crate::Overflow> for Error {
+        fn from(_: crate::Overflow) -> Self {
+            Error::corruption("binary: variant overflows a 64-bit integer")
+        }
+    }
+}
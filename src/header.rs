@@ -1,10 +1,10 @@
-use crate::{binary_put_uvariant_to_bufmut, binary_put_uvariant_to_vec, binary_uvarint};
+use crate::{binary_put_uvariant_to_bufmut, binary_uvarint};
 use alloc::vec::Vec;
 use bytes::{BufMut, Bytes, BytesMut};
 
 /// Maximum possible size of the header. The maximum size of header struct will be 18 but the
-/// maximum size of variant encoded header will be 21.
-pub const MAX_HEADER_SIZE: usize = 21;
+/// maximum size of variant encoded header will be 22.
+pub const MAX_HEADER_SIZE: usize = 22;
 
 /// Header is used in value log as a header before Entry.
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
@@ -18,45 +18,58 @@ pub struct Header {
 }
 
 impl Header {
-    /// Encodes the header into `Vec<u8>`. The provided `Vec<u8>` should be at least 5 bytes. The
-    /// function will panic if out `Vec<u8>` isn't large enough to hold all the values.
+    /// Returns a Header
+    #[inline]
+    pub const fn new(meta: u8, user_meta: u8, k_len: u32, v_len: u32, expires_at: u64) -> Self {
+        Self {
+            meta,
+            user_meta,
+            k_len,
+            v_len,
+            expires_at,
+        }
+    }
+
+    /// Encodes the header into the buffer without intermediate allocation, returns the number
+    /// of bytes written, which is at most [`MAX_HEADER_SIZE`].
     /// The encoded header looks like
+    ///
     /// +------+----------+------------+--------------+-----------+
     ///
     /// | Meta | UserMeta | Key Length | Value Length | ExpiresAt |
     ///
     /// +------+----------+------------+--------------+-----------+
+    ///
+    /// [`MAX_HEADER_SIZE`]: constant.MAX_HEADER_SIZE.html
+    #[inline]
+    pub fn encode_to(&self, buf: &mut impl BufMut) -> usize {
+        buf.put_u8(self.meta);
+        buf.put_u8(self.user_meta);
+        2 + binary_put_uvariant_to_bufmut(buf, self.k_len as u64)
+            + binary_put_uvariant_to_bufmut(buf, self.v_len as u64)
+            + binary_put_uvariant_to_bufmut(buf, self.expires_at)
+    }
+
+    /// Encodes the header into `Vec<u8>`, returns the number of bytes written and the vec,
+    /// see [`encode_to`].
+    ///
+    /// [`encode_to`]: #method.encode_to
+    #[inline]
     pub fn encode(&self) -> (usize, Vec<u8>) {
         let mut buf = Vec::with_capacity(MAX_HEADER_SIZE);
-        buf.push(self.meta);
-        buf.push(self.user_meta);
-        let mut index = 2;
-
-        index += binary_put_uvariant_to_vec(&mut buf, self.k_len as u64);
-        index += binary_put_uvariant_to_vec(&mut buf, self.v_len as u64);
-        index += binary_put_uvariant_to_vec(&mut buf, self.expires_at);
-        (index, buf)
+        let n = self.encode_to(&mut buf);
+        (n, buf)
     }
 
-    /// Encodes the header into `Bytes`. The provided `Bytes` should be at least 5 bytes. The
-    /// function will panic if out `Bytes` isn't large enough to hold all the values.
-    /// The encoded header looks like
-    ///
-    /// +------+----------+------------+--------------+-----------+
-    ///
-    /// | Meta | UserMeta | Key Length | Value Length | ExpiresAt |
+    /// Encodes the header into `Bytes`, returns the number of bytes written and the bytes,
+    /// see [`encode_to`].
     ///
-    /// +------+----------+------------+--------------+-----------+
+    /// [`encode_to`]: #method.encode_to
+    #[inline]
     pub fn encode_to_bytes(&self) -> (usize, Bytes) {
         let mut buf = BytesMut::with_capacity(MAX_HEADER_SIZE);
-        buf.put_u8(self.meta);
-        buf.put_u8(self.user_meta);
-        let mut index = 2;
-
-        index += binary_put_uvariant_to_bufmut(&mut buf, self.k_len as u64);
-        index += binary_put_uvariant_to_bufmut(&mut buf, self.v_len as u64);
-        index += binary_put_uvariant_to_bufmut(&mut buf, self.expires_at);
-        (index, buf.freeze())
+        let n = self.encode_to(&mut buf);
+        (n, buf.freeze())
     }
 
     /// Decode Header from byte slice, returns Header and number of bytes read
@@ -175,7 +188,7 @@ impl Header {
     /// Set the user meta
     #[inline]
     pub fn set_user_meta(&mut self, user_meta: u8) {
-        self.meta = user_meta
+        self.user_meta = user_meta
     }
 
     /// Get the expires_at
@@ -194,7 +207,7 @@ impl Header {
 cfg_std! {
     use std::io::Read;
 
-    ///
+    /// Reads bytes one by one, keeping track of the position.
     pub trait ByteReader {
         /// Read one byte and advance the current position
         fn read_byte(&mut self) -> std::io::Result<u8>;
@@ -227,6 +240,7 @@ cfg_std! {
         &mut [u8],
         Vec<u8>,
         &Vec<u8>,
-        &mut Vec<u8>
+        &mut Vec<u8>,
+        bytes::Bytes
     }
 }
@@ -1,6 +1,17 @@
-use crate::{KeyExt, ValueExt};
+use crate::{Error, KeyExt, KeyRef, ValueExt, ValueRef};
+use alloc::vec::Vec;
 use enum_dispatch::enum_dispatch;
 
+mod bound;
+pub use bound::*;
+mod dyn_iterator;
+pub use dyn_iterator::*;
+mod snapshot;
+pub use snapshot::*;
+
+/// The longest user key [`Iterator::advance_to_next_user_key`] copies on the stack.
+const STACK_KEY_SIZE: usize = 64;
+
 /// Helper struct for iterator
 #[derive(Copy, Clone, Debug)]
 pub enum SeekFrom {
@@ -22,6 +33,75 @@ pub trait Iterator<K: KeyExt, V: ValueExt> {
     /// seek will reset iterator and seek to >= key.
     fn seek<Q: KeyExt>(&mut self, key: Q);
 
+    /// Seeks to >= key from the given position.
+    ///
+    /// [`SeekFrom::Origin`] is the same as [`seek`]. [`SeekFrom::Current`] moves forward from
+    /// the current position without resetting the iterator, and stays if the current key is
+    /// already >= key.
+    ///
+    /// [`SeekFrom::Origin`]: enum.SeekFrom.html#variant.Origin
+    /// [`SeekFrom::Current`]: enum.SeekFrom.html#variant.Current
+    /// [`seek`]: #tymethod.seek
+    #[inline]
+    fn seek_from<Q: KeyExt>(&mut self, key: Q, from: SeekFrom) {
+        match from {
+            SeekFrom::Origin => self.seek(key),
+            SeekFrom::Current => {
+                let key = key.as_key_ref();
+                while self.valid()
+                    && with_key(self, |k| k.compare_key(key).is_lt()).unwrap_or(false)
+                {
+                    self.next();
+                }
+            }
+        }
+    }
+
+    /// Advances the iterator by `n` positions, or until the iterator is not valid.
+    #[inline]
+    fn skip(&mut self, n: usize) {
+        for _ in 0..n {
+            if !self.valid() {
+                return;
+            }
+            self.next();
+        }
+    }
+
+    /// Skips the remaining versions of the current user key, and moves to the
+    /// first version of the next user key.
+    ///
+    /// The default implementation steps over every version and compares it with a copy of the
+    /// user key, which stays on the stack unless the user key is longer than 64 bytes.
+    /// Iterators with a cheaper [`seek_from`] should override it.
+    ///
+    /// [`seek_from`]: #method.seek_from
+    #[inline]
+    fn advance_to_next_user_key(&mut self) {
+        let mut stack = [0u8; STACK_KEY_SIZE];
+        let mut heap = Vec::new();
+        let len = match with_key(self, |k| {
+            let k = k.parse_key();
+            match stack.get_mut(..k.len()) {
+                Some(buf) => buf.copy_from_slice(k),
+                None => heap.extend_from_slice(k),
+            }
+            k.len()
+        }) {
+            Some(len) => len,
+            None => return,
+        };
+        let cur = if len <= STACK_KEY_SIZE {
+            &stack[..len]
+        } else {
+            heap.as_slice()
+        };
+        self.next();
+        while self.valid() && with_key(self, |k| k.parse_key() == cur).unwrap_or(false) {
+            self.next();
+        }
+    }
+
     /// Returns the entry of current position
     fn entry(&self) -> Option<(K, V)>;
 
@@ -31,9 +111,51 @@ pub trait Iterator<K: KeyExt, V: ValueExt> {
     /// Returns the value of current position
     fn val(&self) -> Option<V>;
 
+    /// Returns the key of current position without cloning it.
+    ///
+    /// The default implementation returns `None`, which means the iterator cannot lend its key,
+    /// callers should fall back to [`key`].
+    ///
+    /// [`key`]: #tymethod.key
+    #[inline]
+    fn key_ref(&self) -> Option<KeyRef<'_>> {
+        None
+    }
+
+    /// Returns the value of current position without cloning it.
+    ///
+    /// The default implementation returns `None`, which means the iterator cannot lend its value,
+    /// callers should fall back to [`val`].
+    ///
+    /// [`val`]: #tymethod.val
+    #[inline]
+    fn value_ref(&self) -> Option<ValueRef<'_>> {
+        None
+    }
+
     /// Returns if the current position has a valid value.
     fn valid(&self) -> bool;
 
+    /// Returns the error the iterator has encountered, if any.
+    ///
+    /// Once an error occurs, [`valid`] returns false, use this method to distinguish the error
+    /// from the end of the data. The default implementation never fails.
+    ///
+    /// [`valid`]: #tymethod.valid
+    #[inline]
+    fn error(&self) -> Option<&Error> {
+        None
+    }
+
+    /// Returns `Err` if the iterator has encountered an error.
+    #[inline]
+    fn status(&self) -> Result<(), &Error> {
+        match self.error() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
     /// Size hint for this iterator
     #[inline]
     fn size_hint(&self) -> (usize, Option<usize>) {
@@ -52,3 +174,156 @@ pub trait Iterator<K: KeyExt, V: ValueExt> {
         }
     }
 }
+
+/// Calls `f` with the key of current position, borrowing it by [`Iterator::key_ref`] if possible.
+#[inline]
+pub(crate) fn with_key<K, V, I, R>(iter: &I, f: impl FnOnce(KeyRef<'_>) -> R) -> Option<R>
+where
+    K: KeyExt,
+    V: ValueExt,
+    I: Iterator<K, V> + ?Sized,
+{
+    match iter.key_ref() {
+        Some(key) => Some(f(key)),
+        None => iter.key().map(|key| f(key.as_key_ref())),
+    }
+}
+
+/// Skips the remaining versions of the current user key by seeking from the current position
+/// to its oldest possible version, for the iterators whose [`Iterator::seek_from`] is cheaper
+/// than stepping over every version.
+///
+/// The target key is built in `scratch`, which iterators keep to not allocate per call.
+pub(crate) fn seek_to_next_user_key<K, V, I>(iter: &mut I, scratch: &mut Vec<u8>)
+where
+    K: KeyExt,
+    V: ValueExt,
+    I: Iterator<K, V> + ?Sized,
+{
+    let found = with_key(iter, |k| {
+        scratch.clear();
+        scratch.extend_from_slice(k.parse_key());
+        // timestamp 0, the oldest version
+        scratch.extend_from_slice(&u64::MAX.to_be_bytes());
+    });
+    if found.is_none() {
+        return;
+    }
+    iter.seek_from(scratch.as_slice(), SeekFrom::Current);
+    while iter.valid() && with_key(iter, |k| k.same_key(scratch.as_slice())).unwrap_or(false) {
+        iter.next();
+    }
+}
+
+/// Calls `f` with the value of current position, borrowing it by [`Iterator::value_ref`] if possible.
+#[inline]
+pub(crate) fn with_value<K, V, I, R>(iter: &I, f: impl FnOnce(ValueRef<'_>) -> R) -> Option<R>
+where
+    K: KeyExt,
+    V: ValueExt,
+    I: Iterator<K, V> + ?Sized,
+{
+    match iter.value_ref() {
+        Some(val) => Some(f(val)),
+        None => iter.val().map(|val| f(val.as_value_ref())),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::{Key, Value};
+    use alloc::vec::Vec;
+
+    /// A sorted in-memory iterator, for testing the iterator adapters.
+    pub(crate) struct VecIterator {
+        data: Vec<(Key, Value)>,
+        pos: usize,
+        fail_at: Option<usize>,
+        error: Option<Error>,
+    }
+
+    impl VecIterator {
+        pub(crate) fn new(mut data: Vec<(Key, Value)>) -> Self {
+            data.sort_by(|a, b| a.0.cmp(&b.0));
+            Self {
+                data,
+                pos: 0,
+                fail_at: None,
+                error: None,
+            }
+        }
+
+        /// Fails with a corruption error when the iterator reaches `pos`.
+        pub(crate) fn fail_at(mut self, pos: usize) -> Self {
+            self.fail_at = Some(pos);
+            self.check();
+            self
+        }
+
+        fn check(&mut self) {
+            if self.fail_at == Some(self.pos) {
+                self.error = Some(Error::corruption("injected error"));
+            }
+        }
+    }
+
+    impl Iterator<Key, Value> for VecIterator {
+        fn next(&mut self) {
+            self.pos += 1;
+            self.check();
+        }
+
+        fn rewind(&mut self) {
+            self.pos = 0;
+            self.check();
+        }
+
+        fn seek<Q: KeyExt>(&mut self, key: Q) {
+            self.pos = self
+                .data
+                .partition_point(|(k, _)| k.compare_key(key.as_key_ref()).is_lt());
+            self.check();
+        }
+
+        fn seek_from<Q: KeyExt>(&mut self, key: Q, from: SeekFrom) {
+            if let SeekFrom::Origin = from {
+                return self.seek(key);
+            }
+
+            if self.pos < self.data.len() {
+                self.pos += self.data[self.pos..]
+                    .partition_point(|(k, _)| k.compare_key(key.as_key_ref()).is_lt());
+                self.check();
+            }
+        }
+
+        fn entry(&self) -> Option<(Key, Value)> {
+            self.data.get(self.pos).cloned()
+        }
+
+        fn key(&self) -> Option<Key> {
+            self.data.get(self.pos).map(|(k, _)| k.clone())
+        }
+
+        fn val(&self) -> Option<Value> {
+            self.data.get(self.pos).map(|(_, v)| v.clone())
+        }
+
+        fn key_ref(&self) -> Option<KeyRef<'_>> {
+            self.data.get(self.pos).map(|(k, _)| k.as_key_ref())
+        }
+
+        fn value_ref(&self) -> Option<ValueRef<'_>> {
+            self.data.get(self.pos).map(|(_, v)| v.as_value_ref())
+        }
+
+        fn valid(&self) -> bool {
+            self.error.is_none() && self.pos < self.data.len()
+        }
+
+        fn error(&self) -> Option<&Error> {
+            self.error.as_ref()
+        }
+    }
+}
@@ -0,0 +1,451 @@
+use crate::iterator::{with_key, Iterator, SeekFrom};
+use crate::{Error, Key, KeyExt, KeyRef, ValueExt, ValueRef};
+use core::marker::PhantomData;
+use core::ops::{Bound, RangeBounds};
+
+/// BoundedIterator limits the underlying [`Iterator`] to a range of user keys.
+///
+/// The bounds are user keys (without timestamp), all the versions of a user key inside
+/// the range are yielded. [`seek`] and [`rewind`] are clamped to the lower bound, and
+/// [`valid`] returns false once the underlying iterator moves past the upper bound.
+///
+/// [`Iterator`]: trait.Iterator.html
+/// [`seek`]: trait.Iterator.html#tymethod.seek
+/// [`rewind`]: trait.Iterator.html#tymethod.rewind
+/// [`valid`]: trait.Iterator.html#tymethod.valid
+pub struct BoundedIterator<I, K, V> {
+    iter: I,
+    lower: Bound<Key>,
+    upper: Bound<Key>,
+    /// the lower bound with the max timestamp, which is the first version of the lower bound.
+    lower_seek: Option<Key>,
+    in_range: bool,
+    _marker: PhantomData<fn() -> (K, V)>,
+}
+
+impl<I, K, V> BoundedIterator<I, K, V>
+where
+    I: Iterator<K, V>,
+    K: KeyExt,
+    V: ValueExt,
+{
+    /// Returns a BoundedIterator which only yields the user keys in the range, positioned at
+    /// the first key of the range.
+    #[inline]
+    pub fn new(iter: I, range: impl RangeBounds<Key>) -> Self {
+        let lower = range.start_bound().cloned();
+        let upper = range.end_bound().cloned();
+        let lower_seek = match &lower {
+            Bound::Included(k) | Bound::Excluded(k) => Some(k.clone().with_timestamp(u64::MAX)),
+            Bound::Unbounded => None,
+        };
+        let mut this = Self {
+            iter,
+            lower,
+            upper,
+            lower_seek,
+            in_range: false,
+            _marker: PhantomData,
+        };
+        this.rewind();
+        this
+    }
+
+    /// Returns the lower bound
+    #[inline]
+    pub fn get_lower_bound(&self) -> Bound<&Key> {
+        self.lower.as_ref()
+    }
+
+    /// Returns the upper bound
+    #[inline]
+    pub fn get_upper_bound(&self) -> Bound<&Key> {
+        self.upper.as_ref()
+    }
+
+    /// Returns the underlying iterator
+    #[inline]
+    pub fn into_inner(self) -> I {
+        self.iter
+    }
+
+    #[inline]
+    fn below_lower(&self, user_key: &[u8]) -> bool {
+        match &self.lower {
+            Bound::Included(k) => user_key < k.as_slice(),
+            Bound::Excluded(k) => user_key <= k.as_slice(),
+            Bound::Unbounded => false,
+        }
+    }
+
+    #[inline]
+    fn above_upper(&self, user_key: &[u8]) -> bool {
+        match &self.upper {
+            Bound::Included(k) => user_key > k.as_slice(),
+            Bound::Excluded(k) => user_key >= k.as_slice(),
+            Bound::Unbounded => false,
+        }
+    }
+
+    fn seek_to_lower(&mut self) {
+        match &self.lower_seek {
+            Some(k) => self.iter.seek(k),
+            None => self.iter.rewind(),
+        }
+
+        // skip the versions of the excluded lower bound.
+        while self.iter.valid() {
+            match with_key(&self.iter, |k| self.below_lower(k.parse_key())) {
+                Some(true) => self.iter.next(),
+                _ => break,
+            }
+        }
+    }
+
+    #[inline]
+    fn update(&mut self) {
+        self.in_range = self.iter.valid()
+            && with_key(&self.iter, |k| !self.above_upper(k.parse_key())).unwrap_or(false);
+    }
+}
+
+impl<I, K, V> Iterator<K, V> for BoundedIterator<I, K, V>
+where
+    I: Iterator<K, V>,
+    K: KeyExt,
+    V: ValueExt,
+{
+    fn next(&mut self) {
+        self.iter.next();
+        self.update();
+    }
+
+    fn rewind(&mut self) {
+        self.seek_to_lower();
+        self.update();
+    }
+
+    fn seek<Q: KeyExt>(&mut self, key: Q) {
+        if self.below_lower(key.parse_key()) {
+            self.seek_to_lower();
+        } else {
+            self.iter.seek(key);
+        }
+        self.update();
+    }
+
+    fn seek_from<Q: KeyExt>(&mut self, key: Q, from: SeekFrom) {
+        match from {
+            SeekFrom::Origin => self.seek(key),
+            SeekFrom::Current => {
+                self.iter.seek_from(key, from);
+                self.update();
+            }
+        }
+    }
+
+    fn advance_to_next_user_key(&mut self) {
+        self.iter.advance_to_next_user_key();
+        self.update();
+    }
+
+    #[inline]
+    fn entry(&self) -> Option<(K, V)> {
+        self.in_range.then(|| self.iter.entry()).flatten()
+    }
+
+    #[inline]
+    fn key(&self) -> Option<K> {
+        self.in_range.then(|| self.iter.key()).flatten()
+    }
+
+    #[inline]
+    fn val(&self) -> Option<V> {
+        self.in_range.then(|| self.iter.val()).flatten()
+    }
+
+    #[inline]
+    fn key_ref(&self) -> Option<KeyRef<'_>> {
+        self.in_range.then(|| self.iter.key_ref()).flatten()
+    }
+
+    #[inline]
+    fn value_ref(&self) -> Option<ValueRef<'_>> {
+        self.in_range.then(|| self.iter.value_ref()).flatten()
+    }
+
+    #[inline]
+    fn valid(&self) -> bool {
+        self.in_range
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+
+    #[inline]
+    fn error(&self) -> Option<&Error> {
+        self.iter.error()
+    }
+}
+
+/// PrefixIterator limits the underlying [`Iterator`] to the user keys which have the prefix.
+///
+/// [`seek`] and [`rewind`] are clamped to the prefix, and [`valid`] returns false once the
+/// underlying iterator moves past the keys which have the prefix.
+///
+/// [`Iterator`]: trait.Iterator.html
+/// [`seek`]: trait.Iterator.html#tymethod.seek
+/// [`rewind`]: trait.Iterator.html#tymethod.rewind
+/// [`valid`]: trait.Iterator.html#tymethod.valid
+pub struct PrefixIterator<I, K, V> {
+    iter: I,
+    /// the prefix with the max timestamp, which is the first version of the prefix.
+    prefix: Key,
+    in_range: bool,
+    _marker: PhantomData<fn() -> (K, V)>,
+}
+
+impl<I, K, V> PrefixIterator<I, K, V>
+where
+    I: Iterator<K, V>,
+    K: KeyExt,
+    V: ValueExt,
+{
+    /// Returns a PrefixIterator which only yields the user keys have the prefix, positioned at
+    /// the first key which has the prefix.
+    #[inline]
+    pub fn new(iter: I, prefix: impl KeyExt) -> Self {
+        let mut this = Self {
+            iter,
+            prefix: Key::copy_from_slice(prefix.as_bytes()).with_timestamp(u64::MAX),
+            in_range: false,
+            _marker: PhantomData,
+        };
+        this.rewind();
+        this
+    }
+
+    /// Returns the prefix
+    #[inline]
+    pub fn get_prefix(&self) -> &[u8] {
+        self.prefix.parse_key()
+    }
+
+    /// Returns the underlying iterator
+    #[inline]
+    pub fn into_inner(self) -> I {
+        self.iter
+    }
+
+    #[inline]
+    fn update(&mut self) {
+        self.in_range = self.iter.valid()
+            && with_key(&self.iter, |k| k.has_prefix(&self.prefix)).unwrap_or(false);
+    }
+}
+
+impl<I, K, V> Iterator<K, V> for PrefixIterator<I, K, V>
+where
+    I: Iterator<K, V>,
+    K: KeyExt,
+    V: ValueExt,
+{
+    fn next(&mut self) {
+        self.iter.next();
+        self.update();
+    }
+
+    fn rewind(&mut self) {
+        self.iter.seek(&self.prefix);
+        self.update();
+    }
+
+    fn seek<Q: KeyExt>(&mut self, key: Q) {
+        if key.parse_key() < self.prefix.parse_key() {
+            self.iter.seek(&self.prefix);
+        } else {
+            self.iter.seek(key);
+        }
+        self.update();
+    }
+
+    fn seek_from<Q: KeyExt>(&mut self, key: Q, from: SeekFrom) {
+        match from {
+            SeekFrom::Origin => self.seek(key),
+            SeekFrom::Current => {
+                self.iter.seek_from(key, from);
+                self.update();
+            }
+        }
+    }
+
+    fn advance_to_next_user_key(&mut self) {
+        self.iter.advance_to_next_user_key();
+        self.update();
+    }
+
+    #[inline]
+    fn entry(&self) -> Option<(K, V)> {
+        self.in_range.then(|| self.iter.entry()).flatten()
+    }
+
+    #[inline]
+    fn key(&self) -> Option<K> {
+        self.in_range.then(|| self.iter.key()).flatten()
+    }
+
+    #[inline]
+    fn val(&self) -> Option<V> {
+        self.in_range.then(|| self.iter.val()).flatten()
+    }
+
+    #[inline]
+    fn key_ref(&self) -> Option<KeyRef<'_>> {
+        self.in_range.then(|| self.iter.key_ref()).flatten()
+    }
+
+    #[inline]
+    fn value_ref(&self) -> Option<ValueRef<'_>> {
+        self.in_range.then(|| self.iter.value_ref()).flatten()
+    }
+
+    #[inline]
+    fn valid(&self) -> bool {
+        self.in_range
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+
+    #[inline]
+    fn error(&self) -> Option<&Error> {
+        self.iter.error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::iterator::test::VecIterator;
+    use crate::Value;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    fn collect<I: Iterator<Key, Value>>(iter: &mut I) -> Vec<Vec<u8>> {
+        let mut rst = Vec::new();
+        while iter.valid() {
+            rst.push(iter.key().unwrap().parse_key().to_vec());
+            iter.next();
+        }
+        rst
+    }
+
+    fn data() -> VecIterator {
+        VecIterator::new(
+            ["a", "ab", "abc", "b", "ba", "bb", "c"]
+                .iter()
+                .flat_map(|k| {
+                    (1..=2).map(move |ts| (Key::from(*k).with_timestamp(ts), Value::from(*k)))
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_bounded_iterator() {
+        let mut iter = BoundedIterator::new(data(), Key::from("ab")..Key::from("ba"));
+        iter.rewind();
+        assert_eq!(
+            collect(&mut iter),
+            vec![
+                b"ab".to_vec(),
+                b"ab".to_vec(),
+                b"abc".to_vec(),
+                b"abc".to_vec(),
+                b"b".to_vec(),
+                b"b".to_vec()
+            ]
+        );
+
+        iter.seek(Key::from("a").with_timestamp(1));
+        assert_eq!(iter.key().unwrap().parse_key(), b"ab");
+        iter.seek(Key::from("ba").with_timestamp(1));
+        assert!(!iter.valid());
+
+        let mut iter = BoundedIterator::new(
+            data(),
+            (
+                Bound::Excluded(Key::from("b")),
+                Bound::Included(Key::from("bb")),
+            ),
+        );
+        iter.rewind();
+        assert_eq!(
+            collect(&mut iter),
+            vec![
+                b"ba".to_vec(),
+                b"ba".to_vec(),
+                b"bb".to_vec(),
+                b"bb".to_vec()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_prefix_iterator() {
+        let mut iter = PrefixIterator::new(data(), "ab");
+        iter.rewind();
+        assert_eq!(
+            collect(&mut iter),
+            vec![
+                b"ab".to_vec(),
+                b"ab".to_vec(),
+                b"abc".to_vec(),
+                b"abc".to_vec()
+            ]
+        );
+
+        iter.seek(Key::from("abc").with_timestamp(2));
+        assert_eq!(iter.key().unwrap().parse_key(), b"abc");
+        iter.seek(Key::from("b").with_timestamp(2));
+        assert!(!iter.valid());
+    }
+
+    #[test]
+    fn test_new_iterator() {
+        // the underlying iterator is positioned before the range.
+        let mut iter = BoundedIterator::new(data(), Key::from("b")..Key::from("bb"));
+        assert_eq!(
+            collect(&mut iter),
+            vec![b"b".to_vec(), b"b".to_vec(), b"ba".to_vec(), b"ba".to_vec()]
+        );
+
+        let mut iter = PrefixIterator::new(data(), "b");
+        assert_eq!(iter.key().unwrap().parse_key(), b"b");
+        assert_eq!(collect(&mut iter).len(), 6);
+    }
+
+    #[test]
+    fn test_relative_seek() {
+        let mut iter = BoundedIterator::new(data(), Key::from("ab")..);
+        iter.rewind();
+        iter.advance_to_next_user_key();
+        assert_eq!(iter.key().unwrap().parse_key(), b"abc");
+
+        iter.skip(3);
+        assert_eq!(iter.key().unwrap().parse_key(), b"b");
+        assert_eq!(iter.key().unwrap().parse_timestamp(), 1);
+
+        // the current key is already >= "a", so it stays.
+        iter.seek_from(Key::from("a").with_timestamp(2), SeekFrom::Current);
+        assert_eq!(iter.key().unwrap().parse_key(), b"b");
+        iter.seek_from(Key::from("bb").with_timestamp(2), SeekFrom::Current);
+        assert_eq!(iter.key().unwrap().parse_key(), b"bb");
+        iter.seek_from(Key::from("a").with_timestamp(2), SeekFrom::Origin);
+        assert_eq!(iter.key().unwrap().parse_key(), b"ab");
+    }
+}
@@ -0,0 +1,277 @@
+use crate::iterator::{Iterator, SeekFrom};
+use crate::{Error, KeyExt, KeyRef, ValueExt, ValueRef};
+use alloc::boxed::Box;
+
+/// Object safe version of [`Iterator`], which can be used as `dyn DynIterator<K, V>`.
+///
+/// `DynIterator` is implemented for all the [`Iterator`]s, and `Box<dyn DynIterator<K, V>>`
+/// implements [`Iterator`], so boxed iterators can be used everywhere an [`Iterator`] is required.
+///
+/// The methods are prefixed with `dyn_`, so that they do not conflict with the methods
+/// of [`Iterator`] when both traits are in scope.
+///
+/// [`Iterator`]: trait.Iterator.html
+pub trait DynIterator<K: KeyExt, V: ValueExt> {
+    /// advance to next
+    fn dyn_next(&mut self);
+
+    /// reset to 0
+    fn dyn_rewind(&mut self);
+
+    /// seek will reset iterator and seek to >= key.
+    fn dyn_seek(&mut self, key: KeyRef<'_>);
+
+    /// Seeks to >= key from the given position.
+    fn dyn_seek_from(&mut self, key: KeyRef<'_>, from: SeekFrom);
+
+    /// Advances the iterator by `n` positions, or until the iterator is not valid.
+    fn dyn_skip(&mut self, n: usize);
+
+    /// Skips the remaining versions of the current user key.
+    fn dyn_advance_to_next_user_key(&mut self);
+
+    /// Returns the entry of current position
+    fn dyn_entry(&self) -> Option<(K, V)>;
+
+    /// Returns the key of current position
+    fn dyn_key(&self) -> Option<K>;
+
+    /// Returns the value of current position
+    fn dyn_val(&self) -> Option<V>;
+
+    /// Returns the key of current position without cloning it.
+    fn dyn_key_ref(&self) -> Option<KeyRef<'_>>;
+
+    /// Returns the value of current position without cloning it.
+    fn dyn_value_ref(&self) -> Option<ValueRef<'_>>;
+
+    /// Returns if the current position has a valid value.
+    fn dyn_valid(&self) -> bool;
+
+    /// Size hint for this iterator
+    fn dyn_size_hint(&self) -> (usize, Option<usize>);
+
+    /// Returns the error the iterator has encountered, if any.
+    fn dyn_error(&self) -> Option<&Error>;
+}
+
+impl<K, V, I> DynIterator<K, V> for I
+where
+    K: KeyExt,
+    V: ValueExt,
+    I: Iterator<K, V>,
+{
+    #[inline]
+    fn dyn_next(&mut self) {
+        Iterator::next(self)
+    }
+
+    #[inline]
+    fn dyn_rewind(&mut self) {
+        Iterator::rewind(self)
+    }
+
+    #[inline]
+    fn dyn_seek(&mut self, key: KeyRef<'_>) {
+        Iterator::seek(self, key)
+    }
+
+    #[inline]
+    fn dyn_seek_from(&mut self, key: KeyRef<'_>, from: SeekFrom) {
+        Iterator::seek_from(self, key, from)
+    }
+
+    #[inline]
+    fn dyn_skip(&mut self, n: usize) {
+        Iterator::skip(self, n)
+    }
+
+    #[inline]
+    fn dyn_advance_to_next_user_key(&mut self) {
+        Iterator::advance_to_next_user_key(self)
+    }
+
+    #[inline]
+    fn dyn_entry(&self) -> Option<(K, V)> {
+        Iterator::entry(self)
+    }
+
+    #[inline]
+    fn dyn_key(&self) -> Option<K> {
+        Iterator::key(self)
+    }
+
+    #[inline]
+    fn dyn_val(&self) -> Option<V> {
+        Iterator::val(self)
+    }
+
+    #[inline]
+    fn dyn_key_ref(&self) -> Option<KeyRef<'_>> {
+        Iterator::key_ref(self)
+    }
+
+    #[inline]
+    fn dyn_value_ref(&self) -> Option<ValueRef<'_>> {
+        Iterator::value_ref(self)
+    }
+
+    #[inline]
+    fn dyn_valid(&self) -> bool {
+        Iterator::valid(self)
+    }
+
+    #[inline]
+    fn dyn_size_hint(&self) -> (usize, Option<usize>) {
+        Iterator::size_hint(self)
+    }
+
+    #[inline]
+    fn dyn_error(&self) -> Option<&Error> {
+        Iterator::error(self)
+    }
+}
+
+macro_rules! impl_iterator_for_boxed_dyn {
+    ($($ty:ty), +$(,)?) => {
+        $(
+        impl<'a, K: KeyExt, V: ValueExt> Iterator<K, V> for $ty {
+            #[inline]
+            fn next(&mut self) {
+                (**self).dyn_next()
+            }
+
+            #[inline]
+            fn rewind(&mut self) {
+                (**self).dyn_rewind()
+            }
+
+            #[inline]
+            fn seek<Q: KeyExt>(&mut self, key: Q) {
+                (**self).dyn_seek(key.as_key_ref())
+            }
+
+            #[inline]
+            fn seek_from<Q: KeyExt>(&mut self, key: Q, from: SeekFrom) {
+                (**self).dyn_seek_from(key.as_key_ref(), from)
+            }
+
+            #[inline]
+            fn skip(&mut self, n: usize) {
+                (**self).dyn_skip(n)
+            }
+
+            #[inline]
+            fn advance_to_next_user_key(&mut self) {
+                (**self).dyn_advance_to_next_user_key()
+            }
+
+            #[inline]
+            fn entry(&self) -> Option<(K, V)> {
+                (**self).dyn_entry()
+            }
+
+            #[inline]
+            fn key(&self) -> Option<K> {
+                (**self).dyn_key()
+            }
+
+            #[inline]
+            fn val(&self) -> Option<V> {
+                (**self).dyn_val()
+            }
+
+            #[inline]
+            fn key_ref(&self) -> Option<KeyRef<'_>> {
+                (**self).dyn_key_ref()
+            }
+
+            #[inline]
+            fn value_ref(&self) -> Option<ValueRef<'_>> {
+                (**self).dyn_value_ref()
+            }
+
+            #[inline]
+            fn valid(&self) -> bool {
+                (**self).dyn_valid()
+            }
+
+            #[inline]
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                (**self).dyn_size_hint()
+            }
+
+            #[inline]
+            fn error(&self) -> Option<&Error> {
+                (**self).dyn_error()
+            }
+        }
+        )*
+    };
+}
+
+impl_iterator_for_boxed_dyn! {
+    Box<dyn DynIterator<K, V> + 'a>,
+    Box<dyn DynIterator<K, V> + Send + 'a>,
+    Box<dyn DynIterator<K, V> + Send + Sync + 'a>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::iterator::test::VecIterator;
+    use crate::iterator::PrefixIterator;
+    use crate::{Key, Value};
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_dyn_iterator() {
+        let data = |keys: &[&'static str]| {
+            VecIterator::new(
+                keys.iter()
+                    .map(|k| (Key::from(*k).with_timestamp(1), Value::from(*k)))
+                    .collect(),
+            )
+        };
+
+        let children: Vec<Box<dyn DynIterator<Key, Value>>> = vec![
+            Box::new(data(&["a", "b"])),
+            Box::new(PrefixIterator::new(data(&["a", "ab", "b"]), "a")),
+        ];
+
+        let mut keys = Vec::new();
+        for child in children {
+            let mut iter = PrefixIterator::new(child, "a");
+            iter.rewind();
+            while iter.valid() {
+                keys.push(iter.key().unwrap().parse_key().to_vec());
+                iter.next();
+            }
+        }
+        assert_eq!(keys, vec![b"a".to_vec(), b"a".to_vec(), b"ab".to_vec()]);
+    }
+
+    #[test]
+    fn test_error_propagation() {
+        let data = (0..4)
+            .map(|i| {
+                (
+                    Key::from(alloc::vec![b'a', i]).with_timestamp(1),
+                    Value::from("v"),
+                )
+            })
+            .collect();
+        let child: Box<dyn DynIterator<Key, Value>> = Box::new(VecIterator::new(data).fail_at(2));
+        let mut iter = crate::iterator::SnapshotIterator::new(PrefixIterator::new(child, "a"), 1);
+
+        let mut n = 0;
+        iter.rewind();
+        while iter.valid() {
+            n += 1;
+            iter.next();
+        }
+        assert_eq!(n, 2);
+        assert!(matches!(iter.status(), Err(Error::Corruption(_))));
+    }
+}
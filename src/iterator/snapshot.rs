@@ -0,0 +1,289 @@
+use crate::iterator::{with_key, with_value, Iterator, SeekFrom};
+use crate::{Error, KeyExt, KeyRef, ValueExt, ValueRef};
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+#[cfg(feature = "std")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// SnapshotIterator reads the underlying [`Iterator`] "as of" a read timestamp.
+///
+/// Versions newer than the read timestamp are skipped, and only the newest visible
+/// version of each user key is yielded. Deleted and expired values hide the whole user key.
+///
+/// With [`set_all_versions`], every version not newer than the read timestamp is yielded,
+/// including the deleted and expired ones, which is useful for history views.
+///
+/// **Note:** [`seek`] takes a key with timestamp, use [`Key::with_timestamp`] with the read timestamp
+/// to seek to the newest visible version of a user key.
+///
+/// [`Iterator`]: trait.Iterator.html
+/// [`set_all_versions`]: #method.set_all_versions
+/// [`seek`]: trait.Iterator.html#tymethod.seek
+/// [`Key::with_timestamp`]: ../struct.Key.html#method.with_timestamp
+pub struct SnapshotIterator<I, K, V> {
+    iter: I,
+    read_ts: u64,
+    now: u64,
+    all_versions: bool,
+    /// the key of the current position, used to skip the older versions.
+    last_key: Vec<u8>,
+    _marker: PhantomData<fn() -> (K, V)>,
+}
+
+impl<I, K, V> SnapshotIterator<I, K, V>
+where
+    I: Iterator<K, V>,
+    K: KeyExt,
+    V: ValueExt,
+{
+    /// Returns a SnapshotIterator which reads the underlying iterator as of `read_ts`.
+    ///
+    /// The expiration of values is checked against the current system time when the `std`
+    /// feature is enabled, otherwise values never expire until [`set_now`] is called.
+    ///
+    /// [`set_now`]: #method.set_now
+    #[inline]
+    pub fn new(iter: I, read_ts: u64) -> Self {
+        #[cfg(feature = "std")]
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        #[cfg(not(feature = "std"))]
+        let now = 0;
+
+        let mut this = Self {
+            iter,
+            read_ts,
+            now,
+            all_versions: false,
+            last_key: Vec::new(),
+            _marker: PhantomData,
+        };
+        this.settle();
+        this
+    }
+
+    /// Set the time (unix timestamp) used to check the expiration of values
+    #[inline]
+    pub fn set_now(mut self, now: u64) -> Self {
+        self.now = now;
+        self.settle();
+        self
+    }
+
+    /// Set whether to yield all the versions of a key which are not newer than the read timestamp.
+    #[inline]
+    pub fn set_all_versions(mut self, all_versions: bool) -> Self {
+        self.all_versions = all_versions;
+        self.settle();
+        self
+    }
+
+    /// Returns the read timestamp
+    #[inline]
+    pub fn get_read_ts(&self) -> u64 {
+        self.read_ts
+    }
+
+    /// Returns whether all versions will be yielded
+    #[inline]
+    pub fn get_all_versions(&self) -> bool {
+        self.all_versions
+    }
+
+    /// Returns the underlying iterator
+    #[inline]
+    pub fn into_inner(self) -> I {
+        self.iter
+    }
+
+    /// Moves the underlying iterator to the next visible position (including the current one).
+    fn settle(&mut self) {
+        while self.iter.valid() {
+            let read_ts = self.read_ts;
+            let last_key = &mut self.last_key;
+            let newer = with_key(&self.iter, |key| {
+                let newer = key.parse_timestamp() > read_ts;
+                if !newer {
+                    last_key.clear();
+                    last_key.extend_from_slice(key.as_bytes());
+                }
+                newer
+            });
+
+            match newer {
+                Some(true) => {
+                    self.iter.next();
+                    continue;
+                }
+                Some(false) if self.all_versions => return,
+                Some(false) => {}
+                None => return,
+            }
+
+            let now = self.now;
+            match with_value(&self.iter, |val| val.is_deleted_or_expired(now)) {
+                Some(false) => return,
+                _ => self.skip_versions(),
+            }
+        }
+    }
+
+    /// Skips the current position and the remaining versions of the last key.
+    fn skip_versions(&mut self) {
+        self.iter.next();
+        while self.iter.valid() {
+            let last_key = self.last_key.as_slice();
+            match with_key(&self.iter, |key| key.same_key(last_key)) {
+                Some(true) => self.iter.next(),
+                _ => return,
+            }
+        }
+    }
+}
+
+impl<I, K, V> Iterator<K, V> for SnapshotIterator<I, K, V>
+where
+    I: Iterator<K, V>,
+    K: KeyExt,
+    V: ValueExt,
+{
+    fn next(&mut self) {
+        if self.all_versions {
+            self.iter.next();
+        } else {
+            self.skip_versions();
+        }
+        self.settle();
+    }
+
+    fn rewind(&mut self) {
+        self.iter.rewind();
+        self.settle();
+    }
+
+    fn seek<Q: KeyExt>(&mut self, key: Q) {
+        self.iter.seek(key);
+        self.settle();
+    }
+
+    fn seek_from<Q: KeyExt>(&mut self, key: Q, from: SeekFrom) {
+        self.iter.seek_from(key, from);
+        self.settle();
+    }
+
+    fn advance_to_next_user_key(&mut self) {
+        // settle always records the current key, so skip its versions directly.
+        if self.iter.valid() {
+            self.skip_versions();
+            self.settle();
+        }
+    }
+
+    #[inline]
+    fn entry(&self) -> Option<(K, V)> {
+        self.iter.entry()
+    }
+
+    #[inline]
+    fn key(&self) -> Option<K> {
+        self.iter.key()
+    }
+
+    #[inline]
+    fn val(&self) -> Option<V> {
+        self.iter.val()
+    }
+
+    #[inline]
+    fn key_ref(&self) -> Option<KeyRef<'_>> {
+        self.iter.key_ref()
+    }
+
+    #[inline]
+    fn value_ref(&self) -> Option<ValueRef<'_>> {
+        self.iter.value_ref()
+    }
+
+    #[inline]
+    fn valid(&self) -> bool {
+        self.iter.valid()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+
+    #[inline]
+    fn error(&self) -> Option<&Error> {
+        self.iter.error()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::iterator::test::VecIterator;
+    use crate::{Key, Value, OP};
+    use alloc::vec;
+
+    fn kv(key: &'static str, ts: u64, meta: u8, expires_at: u64) -> (Key, Value) {
+        let val = Value::from(key)
+            .set_meta(meta)
+            .set_expires_at(expires_at)
+            .set_version(ts);
+        (Key::from(key).with_timestamp(ts), val)
+    }
+
+    fn collect<I: Iterator<Key, Value>>(iter: &mut I) -> Vec<(Vec<u8>, u64)> {
+        let mut rst = Vec::new();
+        iter.rewind();
+        while iter.valid() {
+            let key = iter.key().unwrap();
+            rst.push((key.parse_key().to_vec(), key.parse_timestamp()));
+            iter.next();
+        }
+        rst
+    }
+
+    #[test]
+    fn test_snapshot_iterator() {
+        let del = OP::BIT_DELETE.bits();
+        let data = vec![
+            kv("a", 3, 0, 0),
+            kv("a", 2, 0, 0),
+            kv("a", 1, 0, 0),
+            kv("b", 2, del, 0),
+            kv("b", 1, 0, 0),
+            kv("c", 1, 0, 10),
+            kv("d", 3, 0, 0),
+            kv("e", 1, 0, 0),
+        ];
+
+        let mut iter = SnapshotIterator::new(VecIterator::new(data.clone()), 2).set_now(100);
+        assert_eq!(
+            collect(&mut iter),
+            vec![(b"a".to_vec(), 2), (b"e".to_vec(), 1)]
+        );
+
+        iter.seek(Key::from("b").with_timestamp(2));
+        assert_eq!(iter.key().unwrap().parse_key(), b"e");
+
+        let mut iter = SnapshotIterator::new(VecIterator::new(data), 2)
+            .set_now(5)
+            .set_all_versions(true);
+        assert_eq!(
+            collect(&mut iter),
+            vec![
+                (b"a".to_vec(), 2),
+                (b"a".to_vec(), 1),
+                (b"b".to_vec(), 2),
+                (b"b".to_vec(), 1),
+                (b"c".to_vec(), 1),
+                (b"e".to_vec(), 1),
+            ]
+        );
+    }
+}
@@ -5,7 +5,7 @@ use alloc::borrow::Cow;
 use alloc::boxed::Box;
 use alloc::string::String;
 use alloc::vec::Vec;
-use bytes::{Buf, Bytes, BytesMut};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 use core::cmp::Ordering;
 use core::hash::{Hash, Hasher};
 use core::ops::{Deref, DerefMut};
@@ -71,29 +71,14 @@ impl Key {
     #[inline]
     #[cfg(feature = "std")]
     pub fn with_system_time(self, st: SystemTime) -> Self {
-        let len = self.data.len() + TIMESTAMP_SIZE;
-        let ts = Bytes::from(Box::from(
-            st.duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs()
-                .to_be_bytes(),
-        ));
-        self.data.chain(ts).copy_to_bytes(len).into()
+        self.with_timestamp(st.duration_since(UNIX_EPOCH).unwrap().as_secs())
     }
 
     /// Generates a new key by appending the current UNIX system time to key.
     #[inline]
     #[cfg(feature = "std")]
     pub fn with_now(self) -> Self {
-        let len = self.data.len() + TIMESTAMP_SIZE;
-        let ts = Bytes::from(Box::from(
-            SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs()
-                .to_be_bytes(),
-        ));
-        self.data.chain(ts).copy_to_bytes(len).into()
+        self.with_system_time(SystemTime::now())
     }
 
     /// Returns a new Key without timestamp.
@@ -215,7 +200,7 @@ pub(crate) fn same_key_in(me: &[u8], other: &[u8]) -> bool {
             Some(sz) => me[..sz].as_ref(),
         };
         let o = match ol.checked_sub(TIMESTAMP_SIZE) {
-            None => me,
+            None => other,
             Some(sz) => other[..sz].as_ref(),
         };
         s.eq(o)
@@ -417,7 +402,7 @@ pub trait KeyExt {
 
     /// Returns a KeyRef.
     #[inline]
-    fn as_key_ref(&self) -> KeyRef {
+    fn as_key_ref(&self) -> KeyRef<'_> {
         KeyRef {
             data: self.as_bytes(),
         }
@@ -426,6 +411,12 @@ pub trait KeyExt {
     /// Returns the underlying slice of key (with timestamp data).
     fn as_bytes(&self) -> &[u8];
 
+    /// Encodes the key (with timestamp data) into the buffer without intermediate allocation.
+    #[inline]
+    fn encode_to(&self, buf: &mut impl BufMut) {
+        buf.put_slice(self.as_bytes())
+    }
+
     /// Parses the actual key from the key bytes.
     #[inline]
     fn parse_key(&self) -> &[u8] {
@@ -582,14 +573,14 @@ impl<const N: usize> KeyExt for [u8; N] {
     }
 }
 
-impl<'a, const N: usize> KeyExt for &'a [u8; N] {
+impl<const N: usize> KeyExt for &[u8; N] {
     #[inline]
     fn as_bytes(&self) -> &[u8] {
         self.as_slice()
     }
 }
 
-impl<'a, const N: usize> KeyExt for &'a mut [u8; N] {
+impl<const N: usize> KeyExt for &mut [u8; N] {
     #[inline]
     fn as_bytes(&self) -> &[u8] {
         self.as_slice()
@@ -624,4 +615,16 @@ mod test {
         // test same key
         assert_eq!(nk, nk2);
     }
+
+    #[test]
+    fn test_longest_prefix() {
+        let a = Key::from("abcd").with_timestamp(1);
+        assert_eq!(
+            a.longest_prefix(Key::from("abce").with_timestamp(2)),
+            b"abc"
+        );
+        assert_eq!(a.longest_prefix(Key::from("ab").with_timestamp(1)), b"ab");
+        assert_eq!(a.longest_prefix(a.clone()), b"abcd");
+        assert_eq!(a.longest_prefix(Key::new()), b"");
+    }
 }
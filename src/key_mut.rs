@@ -101,7 +101,7 @@ impl KeyMut {
     /// Generates a new key by appending timestamp to key.
     #[inline]
     pub fn with_timestamp(mut self, ts: u64) -> Self {
-        self.data.put_u64(ts);
+        self.data.put_u64(u64::MAX - ts);
         self
     }
 
@@ -114,13 +114,13 @@ impl KeyMut {
     }
 }
 
-impl<'a> KeyExt for &'a KeyMut {
+impl KeyExt for &KeyMut {
     fn as_bytes(&self) -> &[u8] {
         self.data.as_ref()
     }
 }
 
-impl<'a> KeyExt for &'a mut KeyMut {
+impl KeyExt for &mut KeyMut {
     fn as_bytes(&self) -> &[u8] {
         self.data.as_ref()
     }
@@ -165,13 +165,13 @@ impl KeyMutExt for KeyMut {
     fn set_timestamp(&mut self, ts: u64) {
         let sz = self.len();
         match sz.checked_sub(TIMESTAMP_SIZE) {
-            None => self.data.put_u64(ts),
-            Some(sz) => self.data[sz..].copy_from_slice(ts.to_be_bytes().as_slice()),
+            None => self.data.put_u64(u64::MAX - ts),
+            Some(sz) => self.data[sz..].copy_from_slice((u64::MAX - ts).to_be_bytes().as_slice()),
         }
     }
 }
 
-impl<'a> KeyMutExt for &'a mut KeyMut {
+impl KeyMutExt for &mut KeyMut {
     #[inline]
     fn as_bytes_mut(&mut self) -> &mut [u8] {
         self.data.as_mut()
@@ -186,8 +186,8 @@ impl<'a> KeyMutExt for &'a mut KeyMut {
     fn set_timestamp(&mut self, ts: u64) {
         let sz = self.len();
         match sz.checked_sub(TIMESTAMP_SIZE) {
-            None => self.data.put_u64(ts),
-            Some(sz) => self.data[sz..].copy_from_slice(ts.to_be_bytes().as_slice()),
+            None => self.data.put_u64(u64::MAX - ts),
+            Some(sz) => self.data[sz..].copy_from_slice((u64::MAX - ts).to_be_bytes().as_slice()),
         }
     }
 }
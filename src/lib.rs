@@ -48,7 +48,7 @@ macro_rules! longest_prefix {
             let k2 = $trait::$fn(&other);
             let max = k1.len().min(k2.len());
 
-            let mut n = max - 1;
+            let mut n = max;
             for i in 0..max {
                 if k1[i].ne(&k2[i]) {
                     n = i;
@@ -141,18 +141,61 @@ macro_rules! cfg_std {
 
 extern crate alloc;
 
+#[cfg(feature = "arbitrary")]
+mod arbitrary_impl;
+/// Arena backed by a fixed-size buffer, and the handles of the keys and values in it
+pub mod arena;
+/// Checksummed backup files with incremental backups and batched restores
+pub mod backup;
+/// Write batches
+pub mod batch;
+/// Data blocks with restart points
+pub mod block;
+/// Bloom filters on user keys
+pub mod bloom;
+/// Compaction filters
+pub mod compaction;
+/// Discard stats of the value log files
+pub mod discard;
 mod entry;
+mod error;
 mod header;
 /// Iterator trait
 pub mod iterator;
 mod key;
 mod key_mut;
+/// Version edits, the manifest log and the version set
+pub mod manifest;
+/// Memory-mapped files
+#[cfg(feature = "memmap2")]
+pub mod mmap;
+/// Codec for Badger's `pb.KV` and `pb.KVList` protobuf messages
+pub mod pb;
+/// Prefix extractors and prefix bloom filters
+pub mod prefix;
 mod raw_entry_pointer;
 mod raw_key_pointer;
 mod raw_value_pointer;
+#[cfg(feature = "rkyv")]
+mod rkyv_impl;
+#[cfg(feature = "serde")]
+mod serde_impl;
+/// Lock-free skiplist memtable
+pub mod skl;
+/// Proptest strategies for the core types
+#[cfg(feature = "proptest")]
+pub mod strategy;
+/// SST tables
+pub mod table;
 mod value;
 mod value_enc;
 mod value_mut;
+/// Value pointers into the value log
+pub mod value_pointer;
+/// Value log records and garbage collection
+pub mod vlog;
+/// Fragmented write-ahead log
+pub mod wal;
 
 /// Unsafe raw pointer for [`Key`], [`Value`], [`Entry`]
 ///
@@ -171,21 +214,28 @@ pub mod bytes {
     pub use bytes::*;
 }
 pub use entry::*;
+pub use error::*;
 pub use header::*;
 pub use key::*;
 pub use key_mut::*;
+#[cfg(feature = "rkyv")]
+pub use rkyv_impl::*;
 pub use value::*;
 pub use value_enc::*;
 pub use value_mut::*;
 
-use crate::bytes::{BufMut, BytesMut};
-use alloc::vec::Vec;
+use crate::bytes::BufMut;
 use bitflags::bitflags;
-#[cfg(feature = "std")]
-use header::ByteReader;
 
 const TIMESTAMP_SIZE: usize = core::mem::size_of::<u64>();
 
+/// The prefixes of the keys generated by the `arbitrary` and `proptest` features, which is
+/// public as [`strategy::SHARED_KEY_PREFIXES`].
+///
+/// [`strategy::SHARED_KEY_PREFIXES`]: strategy/constant.SHARED_KEY_PREFIXES.html
+#[cfg(any(feature = "arbitrary", feature = "proptest"))]
+pub(crate) const SHARED_KEY_PREFIXES: &[&[u8]] = &[b"", b"a", b"key", b"user/", b"user/0001/"];
+
 bitflags! {
     /// Values have their first byte being byteData or byteDelete. This helps us distinguish between
     /// a key that has never been seen and a key that has been explicitly deleted.
@@ -246,17 +296,40 @@ fn binary_uvarint(buf: &[u8]) -> (u64, usize) {
     (0, 0)
 }
 
+/// Decodes a uvarint from buf, returns the value and the number of bytes read.
 #[inline]
-fn put_binary_uvariant_to_vec(vec: &mut Vec<u8>, mut x: u64) {
-    while x >= 0x80 {
-        vec.push((x as u8) | 0x80);
-        x >>= 7;
+fn decode_uvarint(buf: &[u8]) -> Result<(u64, usize), Error> {
+    match binary_uvarint(buf) {
+        (_, 0) => Err(Error::corruption(
+            "binary: buffer too small to decode a variant",
+        )),
+        (_, n) if n > MAX_VARINT_LEN64 => Err(Error::corruption(
+            "binary: variant overflows a 64-bit integer",
+        )),
+        (x, n) => Ok((x, n)),
+    }
+}
+
+/// Checks whether the bytes can be decoded as [`EncodedValue`].
+#[inline]
+fn check_encoded_value(val: &[u8]) -> Result<(), Error> {
+    if val.len() <= EXPIRATION_OFFSET {
+        return Err(Error::corruption("value: encoded value too short"));
     }
-    vec.push(x as u8)
+    decode_uvarint(&val[EXPIRATION_OFFSET..]).map(|_| ())
 }
 
+/// The CRC-32 (Castagnoli) used to checksum the encoded blocks.
+const CASTAGNOLI: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_ISCSI);
+
+/// Returns the CRC-32 (Castagnoli) checksum of data.
 #[inline]
-fn binary_put_uvariant_to_bufmut(buf: &mut BytesMut, mut x: u64) -> usize {
+fn checksum(data: &[u8]) -> u32 {
+    CASTAGNOLI.checksum(data)
+}
+
+#[inline]
+fn binary_put_uvariant_to_bufmut(buf: &mut impl BufMut, mut x: u64) -> usize {
     let mut i = 0;
     while x >= 0x80 {
         buf.put_u8((x as u8) | 0x80);
@@ -268,6 +341,8 @@ fn binary_put_uvariant_to_bufmut(buf: &mut BytesMut, mut x: u64) -> usize {
 }
 
 cfg_std! {
+    use crate::bytes::BytesMut;
+
     /// Uvariant overflows a 64-bit integer
     #[derive(Copy, Clone, Debug)]
     pub struct Overflow;
@@ -282,12 +357,12 @@ cfg_std! {
 
     impl From<Overflow> for std::io::Error {
         fn from(of: Overflow) -> Self {
-            std::io::Error::new(std::io::ErrorKind::Other, of)
+            std::io::Error::other(of)
         }
     }
 
     /// read_uvarint reads an encoded unsigned integer from r and returns it as a u64.
-    fn binary_read_and_put_uvarint(r: &mut impl ByteReader, dst: &mut BytesMut) -> std::io::Result<u64> {
+    fn binary_read_and_put_uvarint(r: &mut impl crate::ByteReader, dst: &mut BytesMut) -> std::io::Result<u64> {
         let mut x = 0u64;
         let mut s = 0usize;
         for idx in 0..MAX_VARINT_LEN64 {
@@ -305,26 +380,3 @@ cfg_std! {
         Ok(x)
     }
 }
-
-#[inline]
-fn binary_put_uvariant_to_vec(buf: &mut Vec<u8>, mut x: u64) -> usize {
-    let mut i = 0;
-    while x >= 0x80 {
-        buf.push((x as u8) | 0x80);
-        x >>= 7;
-        i += 1;
-    }
-    buf.push(x as u8);
-    i + 1
-}
-
-#[inline]
-fn binary_uvarint_allocate(mut x: u64) -> Vec<u8> {
-    let mut vec = Vec::with_capacity(MAX_VARINT_LEN64);
-    while x >= 0x80 {
-        vec.push((x as u8) | 0x80);
-        x >>= 7;
-    }
-    vec.push(x as u8);
-    vec
-}
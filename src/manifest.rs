@@ -0,0 +1,599 @@
+use crate::bytes::Bytes;
+use crate::wal::{LogReader, LogSink, LogWriter, RecoveryMode};
+use crate::{binary_put_uvariant_to_bufmut, compare_key, decode_uvarint, Error, Key};
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::vec::Vec;
+
+const TAG_NEXT_FILE_NUMBER: u64 = 1;
+const TAG_LAST_SEQUENCE: u64 = 2;
+const TAG_DELETED_TABLE: u64 = 3;
+const TAG_NEW_TABLE: u64 = 4;
+
+/// The max number of levels of a [`VersionSet`], edits with higher levels are corruptions.
+///
+/// [`VersionSet`]: struct.VersionSet.html
+pub const MAX_LEVELS: usize = 64;
+
+/// TableMeta describes a table tracked by the [`VersionSet`].
+///
+/// [`VersionSet`]: struct.VersionSet.html
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct TableMeta {
+    id: u64,
+    size: u64,
+    smallest: Key,
+    largest: Key,
+}
+
+impl TableMeta {
+    /// Returns a TableMeta, the keys are with timestamps.
+    #[inline]
+    pub fn new(id: u64, size: u64, smallest: Key, largest: Key) -> Self {
+        Self {
+            id,
+            size,
+            smallest,
+            largest,
+        }
+    }
+
+    /// Get the file number of the table
+    #[inline]
+    pub fn get_id(&self) -> u64 {
+        self.id
+    }
+
+    /// Get the size of the table in bytes
+    #[inline]
+    pub fn get_size(&self) -> u64 {
+        self.size
+    }
+
+    /// Get the smallest key in the table
+    #[inline]
+    pub fn get_smallest(&self) -> &Key {
+        &self.smallest
+    }
+
+    /// Get the largest key in the table
+    #[inline]
+    pub fn get_largest(&self) -> &Key {
+        &self.largest
+    }
+}
+
+/// VersionEdit is a change to the [`VersionSet`], which is recorded in the manifest.
+///
+/// An edit is encoded as a sequence of tagged fields, every integer is a uvarint.
+///
+/// ```text
+/// next file number: 1 | number
+/// last sequence:    2 | sequence
+/// deleted table:    3 | level | id
+/// new table:        4 | level | id | size | smallest length | smallest | largest length | largest
+/// ```
+///
+/// [`VersionSet`]: struct.VersionSet.html
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct VersionEdit {
+    next_file_number: Option<u64>,
+    last_sequence: Option<u64>,
+    deleted: Vec<(u32, u64)>,
+    added: Vec<(u32, TableMeta)>,
+}
+
+impl VersionEdit {
+    /// Returns an empty VersionEdit
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            next_file_number: None,
+            last_sequence: None,
+            deleted: Vec::new(),
+            added: Vec::new(),
+        }
+    }
+
+    /// Set the next file number
+    #[inline]
+    pub fn set_next_file_number(&mut self, num: u64) {
+        self.next_file_number = Some(num);
+    }
+
+    /// Get the next file number, if it is set by the edit
+    #[inline]
+    pub fn get_next_file_number(&self) -> Option<u64> {
+        self.next_file_number
+    }
+
+    /// Set the last sequence (timestamp)
+    #[inline]
+    pub fn set_last_sequence(&mut self, seq: u64) {
+        self.last_sequence = Some(seq);
+    }
+
+    /// Get the last sequence (timestamp), if it is set by the edit
+    #[inline]
+    pub fn get_last_sequence(&self) -> Option<u64> {
+        self.last_sequence
+    }
+
+    /// Adds a table to the level
+    #[inline]
+    pub fn add_table(&mut self, level: u32, meta: TableMeta) {
+        self.added.push((level, meta));
+    }
+
+    /// Deletes the table from the level
+    #[inline]
+    pub fn delete_table(&mut self, level: u32, id: u64) {
+        self.deleted.push((level, id));
+    }
+
+    /// Returns the added tables and their levels
+    #[inline]
+    pub fn added_tables(&self) -> &[(u32, TableMeta)] {
+        self.added.as_slice()
+    }
+
+    /// Returns the levels and the file numbers of deleted tables
+    #[inline]
+    pub fn deleted_tables(&self) -> &[(u32, u64)] {
+        self.deleted.as_slice()
+    }
+
+    /// Returns true if the edit changes nothing
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.next_file_number.is_none()
+            && self.last_sequence.is_none()
+            && self.deleted.is_empty()
+            && self.added.is_empty()
+    }
+
+    /// Encodes the edit and appends it to dst
+    pub fn encode_to(&self, dst: &mut Vec<u8>) {
+        if let Some(num) = self.next_file_number {
+            binary_put_uvariant_to_bufmut(dst, TAG_NEXT_FILE_NUMBER);
+            binary_put_uvariant_to_bufmut(dst, num);
+        }
+        if let Some(seq) = self.last_sequence {
+            binary_put_uvariant_to_bufmut(dst, TAG_LAST_SEQUENCE);
+            binary_put_uvariant_to_bufmut(dst, seq);
+        }
+        for (level, id) in &self.deleted {
+            binary_put_uvariant_to_bufmut(dst, TAG_DELETED_TABLE);
+            binary_put_uvariant_to_bufmut(dst, *level as u64);
+            binary_put_uvariant_to_bufmut(dst, *id);
+        }
+        for (level, meta) in &self.added {
+            binary_put_uvariant_to_bufmut(dst, TAG_NEW_TABLE);
+            binary_put_uvariant_to_bufmut(dst, *level as u64);
+            binary_put_uvariant_to_bufmut(dst, meta.id);
+            binary_put_uvariant_to_bufmut(dst, meta.size);
+            binary_put_uvariant_to_bufmut(dst, meta.smallest.len() as u64);
+            dst.extend_from_slice(meta.smallest.as_slice());
+            binary_put_uvariant_to_bufmut(dst, meta.largest.len() as u64);
+            dst.extend_from_slice(meta.largest.as_slice());
+        }
+    }
+
+    /// Decodes an edit from the encoded form
+    pub fn decode(mut src: &[u8]) -> Result<Self, Error> {
+        fn uvarint(src: &mut &[u8]) -> Result<u64, Error> {
+            let (x, n) = decode_uvarint(src)?;
+            *src = &src[n..];
+            Ok(x)
+        }
+
+        fn level(src: &mut &[u8]) -> Result<u32, Error> {
+            match uvarint(src)? {
+                level if level < MAX_LEVELS as u64 => Ok(level as u32),
+                _ => Err(Error::corruption("manifest: bad level")),
+            }
+        }
+
+        fn key(src: &mut &[u8]) -> Result<Key, Error> {
+            let len = uvarint(src)? as usize;
+            if src.len() < len {
+                return Err(Error::corruption("manifest: bad key length"));
+            }
+            let (key, rest) = src.split_at(len);
+            *src = rest;
+            Ok(Key::copy_from_slice(key))
+        }
+
+        let mut edit = Self::new();
+        while !src.is_empty() {
+            match uvarint(&mut src)? {
+                TAG_NEXT_FILE_NUMBER => edit.next_file_number = Some(uvarint(&mut src)?),
+                TAG_LAST_SEQUENCE => edit.last_sequence = Some(uvarint(&mut src)?),
+                TAG_DELETED_TABLE => {
+                    let level = level(&mut src)?;
+                    let id = uvarint(&mut src)?;
+                    edit.deleted.push((level, id));
+                }
+                TAG_NEW_TABLE => {
+                    let level = level(&mut src)?;
+                    let id = uvarint(&mut src)?;
+                    let size = uvarint(&mut src)?;
+                    let smallest = key(&mut src)?;
+                    let largest = key(&mut src)?;
+                    edit.added
+                        .push((level, TableMeta::new(id, size, smallest, largest)));
+                }
+                _ => return Err(Error::corruption("manifest: unknown tag")),
+            }
+        }
+        Ok(edit)
+    }
+}
+
+/// ManifestWriter appends [`VersionEdit`]s to a manifest, which is a write-ahead log
+/// with one edit per record.
+///
+/// [`VersionEdit`]: struct.VersionEdit.html
+#[derive(Debug)]
+pub struct ManifestWriter<W> {
+    log: LogWriter<W>,
+    buf: Vec<u8>,
+}
+
+impl<W: LogSink> ManifestWriter<W> {
+    /// Returns a ManifestWriter writing a new manifest to dst
+    #[inline]
+    pub fn new(dst: W) -> Self {
+        Self {
+            log: LogWriter::new(dst),
+            buf: Vec::new(),
+        }
+    }
+
+    /// Returns a ManifestWriter appending to an existing manifest, which has `len` bytes.
+    #[inline]
+    pub fn with_len(dst: W, len: u64) -> Self {
+        Self {
+            log: LogWriter::with_len(dst, len),
+            buf: Vec::new(),
+        }
+    }
+
+    /// Appends the edit
+    pub fn add_edit(&mut self, edit: &VersionEdit) -> Result<(), Error> {
+        self.buf.clear();
+        edit.encode_to(&mut self.buf);
+        self.log.add_record(&self.buf)
+    }
+
+    /// Makes the appended edits durable
+    #[inline]
+    pub fn sync(&mut self) -> Result<(), Error> {
+        self.log.sync()
+    }
+
+    /// Returns the destination
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.log.into_inner()
+    }
+}
+
+/// ManifestReader reads the [`VersionEdit`]s written by [`ManifestWriter`].
+///
+/// [`VersionEdit`]: struct.VersionEdit.html
+/// [`ManifestWriter`]: struct.ManifestWriter.html
+#[derive(Debug)]
+pub struct ManifestReader {
+    log: LogReader,
+}
+
+impl ManifestReader {
+    /// Returns a ManifestReader over the whole manifest
+    #[inline]
+    pub fn new(data: Bytes, mode: RecoveryMode) -> Self {
+        Self {
+            log: LogReader::new(data, mode),
+        }
+    }
+
+    cfg_std! {
+        /// Reads the whole manifest file
+        pub fn open(path: impl AsRef<std::path::Path>, mode: RecoveryMode) -> Result<Self, Error> {
+            LogReader::open(path, mode).map(|log| Self { log })
+        }
+    }
+
+    /// Reads the next edit, returns `None` at the end of the manifest.
+    #[inline]
+    pub fn read_edit(&mut self) -> Result<Option<VersionEdit>, Error> {
+        match self.log.read_record()? {
+            Some(record) => VersionEdit::decode(&record).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// VersionSet is the current set of tables in each level, built by applying [`VersionEdit`]s.
+///
+/// Tables in level 0 may overlap and are sorted by file number, tables in the other levels
+/// are sorted by their smallest keys.
+///
+/// [`VersionEdit`]: struct.VersionEdit.html
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct VersionSet {
+    levels: Vec<Vec<TableMeta>>,
+    /// the level of each table, by file number
+    tables: BTreeMap<u64, u32>,
+    next_file_number: u64,
+    last_sequence: u64,
+}
+
+impl VersionSet {
+    /// Returns an empty VersionSet
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            levels: Vec::new(),
+            tables: BTreeMap::new(),
+            next_file_number: 0,
+            last_sequence: 0,
+        }
+    }
+
+    /// Replays all the edits in the manifest
+    pub fn recover(reader: &mut ManifestReader) -> Result<Self, Error> {
+        let mut vs = Self::new();
+        while let Some(edit) = reader.read_edit()? {
+            vs.apply(&edit)?;
+        }
+        Ok(vs)
+    }
+
+    /// Applies the edit. Deleting a missing table or adding an existing table is a corruption,
+    /// and the set is left unchanged.
+    pub fn apply(&mut self, edit: &VersionEdit) -> Result<(), Error> {
+        let next_file_number = self.check(edit)?;
+
+        for (level, id) in &edit.deleted {
+            self.tables.remove(id);
+            let tables = &mut self.levels[*level as usize];
+            if let Some(idx) = tables.iter().position(|t| t.id == *id) {
+                tables.remove(idx);
+            }
+        }
+
+        for (level, meta) in &edit.added {
+            self.tables.insert(meta.id, *level);
+            let level = *level as usize;
+            if self.levels.len() <= level {
+                self.levels.resize_with(level + 1, Vec::new);
+            }
+            let tables = &mut self.levels[level];
+            let idx = if level == 0 {
+                tables.partition_point(|t| t.id < meta.id)
+            } else {
+                tables.partition_point(|t| compare_key(&t.smallest, &meta.smallest).is_lt())
+            };
+            tables.insert(idx, meta.clone());
+        }
+
+        self.next_file_number = next_file_number;
+        if let Some(seq) = edit.last_sequence {
+            self.last_sequence = self.last_sequence.max(seq);
+        }
+        Ok(())
+    }
+
+    /// Validates the edit against the current tables without changing them, returns the next
+    /// file number after the edit.
+    fn check(&self, edit: &VersionEdit) -> Result<u64, Error> {
+        let mut deleted = BTreeSet::new();
+        for (level, id) in &edit.deleted {
+            if self.tables.get(id) != Some(level) || !deleted.insert(*id) {
+                return Err(Error::corruption("manifest: delete a missing table"));
+            }
+        }
+
+        let mut added = BTreeSet::new();
+        let mut next_file_number = self.next_file_number;
+        for (level, meta) in &edit.added {
+            let exists = self.tables.contains_key(&meta.id) && !deleted.contains(&meta.id);
+            if exists || !added.insert(meta.id) {
+                return Err(Error::corruption("manifest: add an existing table"));
+            }
+            if *level as usize >= MAX_LEVELS {
+                return Err(Error::corruption("manifest: bad level"));
+            }
+            let next = meta
+                .id
+                .checked_add(1)
+                .ok_or_else(|| Error::corruption("manifest: table id overflows"))?;
+            next_file_number = next_file_number.max(next);
+        }
+
+        if let Some(num) = edit.next_file_number {
+            next_file_number = next_file_number.max(num);
+        }
+        Ok(next_file_number)
+    }
+
+    /// Returns an edit which creates the current set from an empty one, which is used as
+    /// the first record of a new manifest.
+    pub fn snapshot(&self) -> VersionEdit {
+        let mut edit = VersionEdit::new();
+        edit.set_next_file_number(self.next_file_number);
+        edit.set_last_sequence(self.last_sequence);
+        for (level, tables) in self.levels.iter().enumerate() {
+            for t in tables {
+                edit.add_table(level as u32, t.clone());
+            }
+        }
+        edit
+    }
+
+    /// Returns the number of levels which have ever had tables
+    #[inline]
+    pub fn num_levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Returns the tables in the level
+    #[inline]
+    pub fn level(&self, level: usize) -> &[TableMeta] {
+        self.levels.get(level).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Returns the number of tables in all levels
+    #[inline]
+    pub fn num_tables(&self) -> usize {
+        self.tables.len()
+    }
+
+    /// Returns the total size of the tables in the level
+    #[inline]
+    pub fn level_size(&self, level: usize) -> u64 {
+        self.level(level).iter().map(|t| t.size).sum()
+    }
+
+    /// Returns the next file number
+    #[inline]
+    pub fn get_next_file_number(&self) -> u64 {
+        self.next_file_number
+    }
+
+    /// Returns the next file number and increases it. The new number should be recorded by
+    /// a [`VersionEdit`] before the file is used.
+    ///
+    /// [`VersionEdit`]: struct.VersionEdit.html
+    #[inline]
+    pub fn new_file_number(&mut self) -> u64 {
+        let num = self.next_file_number;
+        self.next_file_number += 1;
+        num
+    }
+
+    /// Returns the last sequence (timestamp)
+    #[inline]
+    pub fn get_last_sequence(&self) -> u64 {
+        self.last_sequence
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn meta(id: u64, smallest: &str, largest: &str) -> TableMeta {
+        TableMeta::new(
+            id,
+            id * 100,
+            Key::copy_from_slice(smallest.as_bytes()).with_timestamp(1),
+            Key::copy_from_slice(largest.as_bytes()).with_timestamp(1),
+        )
+    }
+
+    #[test]
+    fn test_version_edit() {
+        let mut edit = VersionEdit::new();
+        assert!(edit.is_empty());
+        edit.set_next_file_number(300);
+        edit.set_last_sequence(1 << 40);
+        edit.delete_table(1, 7);
+        edit.add_table(2, meta(8, "a", "z"));
+
+        let mut buf = Vec::new();
+        edit.encode_to(&mut buf);
+        assert_eq!(VersionEdit::decode(&buf).unwrap(), edit);
+        assert!(VersionEdit::decode(&buf[..buf.len() - 1]).is_err());
+        assert!(VersionEdit::decode(&[9, 1]).is_err());
+
+        let mut edit = VersionEdit::new();
+        edit.add_table(MAX_LEVELS as u32, meta(1, "a", "z"));
+        let mut buf = Vec::new();
+        edit.encode_to(&mut buf);
+        assert!(VersionEdit::decode(&buf).is_err());
+    }
+
+    #[test]
+    fn test_version_set() {
+        let mut writer = ManifestWriter::new(Vec::new());
+        let mut vs = VersionSet::new();
+
+        let mut edit = VersionEdit::new();
+        edit.add_table(0, meta(2, "k", "p"));
+        edit.add_table(0, meta(1, "a", "z"));
+        edit.add_table(1, meta(4, "m", "z"));
+        edit.add_table(1, meta(3, "a", "l"));
+        edit.set_last_sequence(10);
+        vs.apply(&edit).unwrap();
+        writer.add_edit(&edit).unwrap();
+
+        // compact level 0 to level 1
+        let mut edit = VersionEdit::new();
+        let id = vs.new_file_number();
+        assert_eq!(id, 5);
+        edit.delete_table(0, 1);
+        edit.delete_table(0, 2);
+        edit.add_table(2, meta(id, "a", "z"));
+        edit.set_next_file_number(vs.get_next_file_number());
+        edit.set_last_sequence(20);
+        vs.apply(&edit).unwrap();
+        writer.add_edit(&edit).unwrap();
+
+        let mut bad = VersionEdit::new();
+        bad.delete_table(0, 1);
+        bad.add_table(3, meta(9, "a", "b"));
+        assert!(vs.apply(&bad).is_err());
+        let mut bad = VersionEdit::new();
+        bad.add_table(3, meta(3, "a", "b"));
+        assert!(vs.apply(&bad).is_err());
+        let mut bad = VersionEdit::new();
+        bad.add_table(3, meta(9, "a", "b"));
+        bad.add_table(MAX_LEVELS as u32, meta(10, "a", "b"));
+        assert!(vs.apply(&bad).is_err());
+        let mut bad = VersionEdit::new();
+        bad.add_table(3, meta(9, "a", "b"));
+        let last = TableMeta::new(u64::MAX, 0, Key::from("c"), Key::from("d"));
+        bad.add_table(3, last);
+        assert!(vs.apply(&bad).is_err());
+        let mut bad = VersionEdit::new();
+        bad.delete_table(2, 3);
+        assert!(vs.apply(&bad).is_err());
+        let mut bad = VersionEdit::new();
+        bad.delete_table(1, 3);
+        bad.delete_table(1, 3);
+        assert!(vs.apply(&bad).is_err());
+        let mut bad = VersionEdit::new();
+        bad.add_table(3, meta(9, "a", "b"));
+        bad.add_table(4, meta(9, "a", "b"));
+        assert!(vs.apply(&bad).is_err());
+        // the failed edits change nothing
+        assert_eq!(vs.num_tables(), 3);
+        assert_eq!(vs.num_levels(), 3);
+
+        assert!(vs.level(0).is_empty());
+        let ids = vs.level(1).iter().map(|t| t.get_id()).collect::<Vec<_>>();
+        assert_eq!(ids, [3, 4]);
+        assert_eq!(vs.level_size(2), 500);
+        assert_eq!(vs.get_last_sequence(), 20);
+        assert_eq!(vs.get_next_file_number(), 6);
+
+        let data = Bytes::from(writer.into_inner());
+        let mut reader = ManifestReader::new(data, RecoveryMode::Strict);
+        let recovered = VersionSet::recover(&mut reader).unwrap();
+        assert_eq!(recovered, vs);
+
+        let mut from_snapshot = VersionSet::new();
+        from_snapshot.apply(&vs.snapshot()).unwrap();
+        assert_eq!(from_snapshot, vs);
+
+        // a table moves to another level in one edit
+        let mut edit = VersionEdit::new();
+        edit.delete_table(1, 4);
+        edit.add_table(3, meta(4, "m", "z"));
+        vs.apply(&edit).unwrap();
+        assert_eq!(vs.num_tables(), 3);
+        assert_eq!(vs.level(1).len(), 1);
+        assert_eq!(vs.level(3)[0].get_id(), 4);
+    }
+}
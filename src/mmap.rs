@@ -0,0 +1,164 @@
+use crate::bytes::Bytes;
+use crate::value_pointer::ValuePointer;
+use crate::vlog::{VlogRecord, VlogRecordRef};
+use crate::Error;
+use alloc::sync::Arc;
+use memmap2::Mmap;
+use std::fs::File;
+use std::path::Path;
+
+/// MmapFile is a read-only memory-mapped file, e.g. a value log or a table file.
+///
+/// The refs read from the file borrow the mapping, so they cannot outlive it. To keep keys
+/// and values after the file is dropped without copying them, use [`to_bytes`], the returned
+/// [`Bytes`] keeps the mapping alive.
+///
+/// Cloning a MmapFile is cheap, the mapping is shared.
+///
+/// [`to_bytes`]: #method.to_bytes
+/// [`Bytes`]: ../bytes/struct.Bytes.html
+#[derive(Debug, Clone)]
+pub struct MmapFile {
+    map: Arc<Mmap>,
+}
+
+impl MmapFile {
+    /// Maps the whole file into memory.
+    ///
+    /// # Safety
+    /// The file must not be modified or truncated while it is mapped (including by other
+    /// processes), otherwise the refs and bytes read from it may change or be invalid.
+    pub unsafe fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let file = File::open(path)?;
+        let map = Mmap::map(&file)?;
+        Ok(Self { map: Arc::new(map) })
+    }
+
+    /// Returns the mapped memory
+    #[inline]
+    pub fn as_slice(&self) -> &[u8] {
+        &self.map
+    }
+
+    /// Returns the size of the file in bytes
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns true if the file is empty
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns the mapped memory as [`Bytes`] without copying, which keeps the mapping
+    /// alive until all the bytes sliced from it are dropped.
+    ///
+    /// [`Bytes`]: ../bytes/struct.Bytes.html
+    #[inline]
+    pub fn to_bytes(&self) -> Bytes {
+        Bytes::from_owner(Owner(self.map.clone()))
+    }
+
+    /// Reads the value log record pointed by the value pointer, the key and value refs
+    /// borrow the mapping.
+    ///
+    /// The id of the value log file is not checked.
+    #[inline]
+    pub fn read(&self, vp: ValuePointer) -> Result<VlogRecordRef<'_>, Error> {
+        VlogRecordRef::read(self.as_slice(), vp)
+    }
+
+    /// Reads the value log record pointed by the value pointer, the key and value share the
+    /// mapping (see [`to_bytes`]).
+    ///
+    /// The id of the value log file is not checked.
+    ///
+    /// [`to_bytes`]: #method.to_bytes
+    #[inline]
+    pub fn read_bytes(&self, vp: ValuePointer) -> Result<VlogRecord, Error> {
+        VlogRecord::read_bytes(&self.to_bytes(), vp)
+    }
+}
+
+impl AsRef<[u8]> for MmapFile {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+/// The owner of the [`Bytes`] returned by [`MmapFile::to_bytes`].
+struct Owner(Arc<Mmap>);
+
+impl AsRef<[u8]> for Owner {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::iterator::Iterator;
+    use crate::table::{Table, TableBuilder, TableOptions};
+    use crate::{Key, KeyExt, Value, ValueExt};
+    use std::vec::Vec;
+
+    #[test]
+    fn test_mmap_vlog() {
+        let dir = std::env::temp_dir().join(format!("kvstructs-mmap-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut log = Vec::new();
+        let mut vps = Vec::new();
+        for i in 0..10u64 {
+            let key = Key::from(format!("key{:02}", i).into_bytes()).with_timestamp(i + 1);
+            let val = Value::new()
+                .set_user_meta(i as u8)
+                .set_data(Bytes::from(vec![i as u8; 10]));
+            let offset = log.len() as u32;
+            let len = VlogRecord::encode_to(&key, val, &mut log);
+            vps.push(ValuePointer::new(1, len, offset));
+        }
+        let path = dir.join("000001.vlog");
+        std::fs::write(&path, &log).unwrap();
+
+        let file = unsafe { MmapFile::open(&path) }.unwrap();
+        assert_eq!(file.as_slice(), log.as_slice());
+        let record = file.read(vps[3]).unwrap();
+        assert_eq!(record.get_key().parse_key(), b"key03");
+        assert_eq!(record.get_value().get_version(), 4);
+        assert_eq!(record.get_value().parse_value(), &[3; 10]);
+
+        // the keys and values outlive the file.
+        let records = vps
+            .iter()
+            .map(|vp| file.read_bytes(*vp).unwrap())
+            .collect::<Vec<_>>();
+        drop(file);
+        for (i, record) in records.iter().enumerate() {
+            assert_eq!(record.get_key().parse_timestamp(), i as u64 + 1);
+            assert_eq!(record.get_value().get_user_meta(), i as u8);
+        }
+
+        let table_path = dir.join("000001.sst");
+        let mut builder = TableBuilder::new(TableOptions::new());
+        for record in &records {
+            builder.add(record.get_key(), record.get_value().clone());
+        }
+        std::fs::write(&table_path, builder.finish()).unwrap();
+        let table = unsafe { Table::open_mmap(&table_path, &TableOptions::new()) }.unwrap();
+        let mut iter = table.iter();
+        iter.rewind();
+        let (key, val) = iter.entry().unwrap();
+        drop(iter);
+        drop(table);
+        assert_eq!(key.parse_key(), b"key00");
+        assert_eq!(val.parse_value(), &[0; 10]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
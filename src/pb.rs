@@ -0,0 +1,612 @@
+//! The wire format is the protobuf encoding of
+//!
+//! ```text
+//! message KV {
+//!   bytes key = 1;
+//!   bytes value = 2;
+//!   bytes user_meta = 3;
+//!   uint64 version = 4;
+//!   uint64 expires_at = 5;
+//!   bytes meta = 6;
+//!   uint32 stream_id = 10;
+//!   bool stream_done = 11;
+//!   Kind kind = 12;
+//! }
+//!
+//! message KVList {
+//!   repeated KV kv = 1;
+//!   uint64 alloc_ref = 10;
+//! }
+//! ```
+//!
+//! Fields with default values are not written, and unknown fields are skipped when decoding.
+use crate::bytes::Bytes;
+use crate::{
+    binary_put_uvariant_to_bufmut, decode_uvarint, Entry, Error, Key, KeyExt, Value, ValueExt,
+};
+use alloc::vec::Vec;
+
+const WIRE_VARINT: u64 = 0;
+const WIRE_FIXED64: u64 = 1;
+const WIRE_LEN: u64 = 2;
+const WIRE_FIXED32: u64 = 5;
+
+#[inline]
+fn put_tag(dst: &mut Vec<u8>, field: u64, wire: u64) {
+    binary_put_uvariant_to_bufmut(dst, field << 3 | wire);
+}
+
+#[inline]
+fn put_varint_field(dst: &mut Vec<u8>, field: u64, val: u64) {
+    if val != 0 {
+        put_tag(dst, field, WIRE_VARINT);
+        binary_put_uvariant_to_bufmut(dst, val);
+    }
+}
+
+#[inline]
+fn put_bytes_field(dst: &mut Vec<u8>, field: u64, val: &[u8]) {
+    if !val.is_empty() {
+        put_tag(dst, field, WIRE_LEN);
+        binary_put_uvariant_to_bufmut(dst, val.len() as u64);
+        dst.extend_from_slice(val);
+    }
+}
+
+/// Reads the fields of a message, calls `f` with the field number, the wire type and the
+/// reader positioned at the field value, `f` returns false for unknown fields to skip them.
+fn read_fields<'a>(
+    src: &'a [u8],
+    mut f: impl FnMut(u64, u64, &mut FieldReader<'a>) -> Result<bool, Error>,
+) -> Result<(), Error> {
+    let mut r = FieldReader { src };
+    while !r.src.is_empty() {
+        let tag = r.varint()?;
+        let (field, wire) = (tag >> 3, tag & 7);
+        if field == 0 {
+            return Err(Error::corruption("pb: bad field number"));
+        }
+        if !f(field, wire, &mut r)? {
+            r.skip(wire)?;
+        }
+    }
+    Ok(())
+}
+
+struct FieldReader<'a> {
+    src: &'a [u8],
+}
+
+impl<'a> FieldReader<'a> {
+    #[inline]
+    fn varint(&mut self) -> Result<u64, Error> {
+        let (x, n) = decode_uvarint(self.src)?;
+        self.src = &self.src[n..];
+        Ok(x)
+    }
+
+    #[inline]
+    fn take(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        if self.src.len() < n {
+            return Err(Error::corruption("pb: truncated field"));
+        }
+        let (data, rest) = self.src.split_at(n);
+        self.src = rest;
+        Ok(data)
+    }
+
+    #[inline]
+    fn bytes(&mut self) -> Result<&'a [u8], Error> {
+        let len = self.varint()?;
+        let len = usize::try_from(len).map_err(|_| Error::corruption("pb: truncated field"))?;
+        self.take(len)
+    }
+
+    fn skip(&mut self, wire: u64) -> Result<(), Error> {
+        match wire {
+            WIRE_VARINT => self.varint().map(|_| ()),
+            WIRE_FIXED64 => self.take(8).map(|_| ()),
+            WIRE_LEN => self.bytes().map(|_| ()),
+            WIRE_FIXED32 => self.take(4).map(|_| ()),
+            _ => Err(Error::corruption("pb: unsupported wire type")),
+        }
+    }
+}
+
+#[inline]
+fn expect_wire(wire: u64, expected: u64) -> Result<(), Error> {
+    if wire == expected {
+        Ok(())
+    } else {
+        Err(Error::corruption("pb: unexpected wire type"))
+    }
+}
+
+/// The kind of a [`KV`] in a stream
+///
+/// [`KV`]: struct.KV.html
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+#[repr(u8)]
+pub enum KVKind {
+    /// A key-value pair
+    #[default]
+    Key = 0,
+    /// A key-value pair of the internal data keys
+    DataKey = 1,
+    /// A file
+    File = 2,
+}
+
+impl TryFrom<u64> for KVKind {
+    type Error = Error;
+
+    #[inline]
+    fn try_from(kind: u64) -> Result<Self, Self::Error> {
+        match kind {
+            0 => Ok(Self::Key),
+            1 => Ok(Self::DataKey),
+            2 => Ok(Self::File),
+            _ => Err(Error::corruption("pb: unknown kv kind")),
+        }
+    }
+}
+
+/// KV is Badger's `pb.KV`, a key-value pair in stream and backup outputs.
+///
+/// The key is the user key without timestamp, which is carried by the version,
+/// and the meta and user meta are single bytes (empty means 0).
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+pub struct KV {
+    key: Bytes,
+    value: Bytes,
+    user_meta: Bytes,
+    version: u64,
+    expires_at: u64,
+    meta: Bytes,
+    stream_id: u32,
+    stream_done: bool,
+    kind: KVKind,
+}
+
+impl KV {
+    /// Returns an empty KV
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            key: Bytes::new(),
+            value: Bytes::new(),
+            user_meta: Bytes::new(),
+            version: 0,
+            expires_at: 0,
+            meta: Bytes::new(),
+            stream_id: 0,
+            stream_done: false,
+            kind: KVKind::Key,
+        }
+    }
+
+    /// Returns a KV from a key with timestamp and its value
+    pub fn from_kv(key: impl KeyExt, val: impl ValueExt) -> Self {
+        Self {
+            key: Bytes::copy_from_slice(key.parse_key()),
+            value: val.parse_value_to_bytes(),
+            user_meta: Bytes::copy_from_slice(&[val.get_user_meta()]),
+            version: key.parse_timestamp(),
+            expires_at: val.get_expires_at(),
+            meta: Bytes::copy_from_slice(&[val.get_meta()]),
+            ..Self::new()
+        }
+    }
+
+    /// Returns a KV from the entry
+    #[inline]
+    pub fn from_entry(ent: &Entry) -> Self {
+        Self::from_kv(ent.get_key(), ent.get_value().as_value_ref())
+    }
+
+    /// Returns the key with timestamp (the version)
+    #[inline]
+    pub fn to_key(&self) -> Key {
+        Key::from(self.key.clone()).with_timestamp(self.version)
+    }
+
+    /// Returns the value, with the version set
+    #[inline]
+    pub fn to_value(&self) -> Value {
+        Value::with_all_fields(
+            self.get_meta(),
+            self.get_user_meta(),
+            self.expires_at,
+            self.version,
+            self.value.clone(),
+        )
+    }
+
+    /// Returns the entry
+    #[inline]
+    pub fn to_entry(&self) -> Entry {
+        Entry::new_from_kv(self.to_key(), self.to_value())
+    }
+
+    /// Set the user key (without timestamp)
+    #[inline]
+    pub fn set_key(mut self, key: Bytes) -> Self {
+        self.key = key;
+        self
+    }
+
+    /// Get the user key (without timestamp)
+    #[inline]
+    pub fn get_key(&self) -> &Bytes {
+        &self.key
+    }
+
+    /// Set the value data
+    #[inline]
+    pub fn set_value(mut self, value: Bytes) -> Self {
+        self.value = value;
+        self
+    }
+
+    /// Get the value data
+    #[inline]
+    pub fn get_value(&self) -> &Bytes {
+        &self.value
+    }
+
+    /// Set the user meta
+    #[inline]
+    pub fn set_user_meta(mut self, user_meta: u8) -> Self {
+        self.user_meta = Bytes::copy_from_slice(&[user_meta]);
+        self
+    }
+
+    /// Get the user meta, which is the first byte of the user meta field
+    #[inline]
+    pub fn get_user_meta(&self) -> u8 {
+        self.user_meta.first().copied().unwrap_or(0)
+    }
+
+    /// Set the version
+    #[inline]
+    pub fn set_version(mut self, version: u64) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Get the version
+    #[inline]
+    pub fn get_version(&self) -> u64 {
+        self.version
+    }
+
+    /// Set the expiration time (unix timestamp)
+    #[inline]
+    pub fn set_expires_at(mut self, expires_at: u64) -> Self {
+        self.expires_at = expires_at;
+        self
+    }
+
+    /// Get the expiration time (unix timestamp)
+    #[inline]
+    pub fn get_expires_at(&self) -> u64 {
+        self.expires_at
+    }
+
+    /// Set the meta
+    #[inline]
+    pub fn set_meta(mut self, meta: u8) -> Self {
+        self.meta = Bytes::copy_from_slice(&[meta]);
+        self
+    }
+
+    /// Get the meta, which is the first byte of the meta field
+    #[inline]
+    pub fn get_meta(&self) -> u8 {
+        self.meta.first().copied().unwrap_or(0)
+    }
+
+    /// Set the id of the stream which the KV comes from
+    #[inline]
+    pub fn set_stream_id(mut self, stream_id: u32) -> Self {
+        self.stream_id = stream_id;
+        self
+    }
+
+    /// Get the id of the stream which the KV comes from
+    #[inline]
+    pub fn get_stream_id(&self) -> u32 {
+        self.stream_id
+    }
+
+    /// Set whether the KV marks the end of its stream
+    #[inline]
+    pub fn set_stream_done(mut self, stream_done: bool) -> Self {
+        self.stream_done = stream_done;
+        self
+    }
+
+    /// Returns whether the KV marks the end of its stream
+    #[inline]
+    pub fn is_stream_done(&self) -> bool {
+        self.stream_done
+    }
+
+    /// Set the kind
+    #[inline]
+    pub fn set_kind(mut self, kind: KVKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Get the kind
+    #[inline]
+    pub fn get_kind(&self) -> KVKind {
+        self.kind
+    }
+
+    /// Encodes the KV to the buffer
+    pub fn encode_to(&self, dst: &mut Vec<u8>) {
+        put_bytes_field(dst, 1, &self.key);
+        put_bytes_field(dst, 2, &self.value);
+        put_bytes_field(dst, 3, &self.user_meta);
+        put_varint_field(dst, 4, self.version);
+        put_varint_field(dst, 5, self.expires_at);
+        put_bytes_field(dst, 6, &self.meta);
+        put_varint_field(dst, 10, self.stream_id as u64);
+        put_varint_field(dst, 11, self.stream_done as u64);
+        put_varint_field(dst, 12, self.kind as u64);
+    }
+
+    /// Encodes the KV
+    #[inline]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.encode_to(&mut buf);
+        buf
+    }
+
+    /// Decodes a KV from the encoded form
+    pub fn decode(src: &[u8]) -> Result<Self, Error> {
+        let mut kv = Self::new();
+        read_fields(src, |field, wire, r| {
+            match field {
+                1 | 2 | 3 | 6 => {
+                    expect_wire(wire, WIRE_LEN)?;
+                    let data = Bytes::copy_from_slice(r.bytes()?);
+                    match field {
+                        1 => kv.key = data,
+                        2 => kv.value = data,
+                        3 => kv.user_meta = data,
+                        _ => kv.meta = data,
+                    }
+                }
+                4 | 5 | 10 | 11 | 12 => {
+                    expect_wire(wire, WIRE_VARINT)?;
+                    let x = r.varint()?;
+                    match field {
+                        4 => kv.version = x,
+                        5 => kv.expires_at = x,
+                        // uint32 fields take the low 32 bits, as protobuf does
+                        10 => kv.stream_id = x as u32,
+                        11 => kv.stream_done = x != 0,
+                        _ => kv.kind = KVKind::try_from(x)?,
+                    }
+                }
+                _ => return Ok(false),
+            }
+            Ok(true)
+        })?;
+        Ok(kv)
+    }
+}
+
+impl From<&Entry> for KV {
+    #[inline]
+    fn from(ent: &Entry) -> Self {
+        Self::from_entry(ent)
+    }
+}
+
+impl From<&KV> for Entry {
+    #[inline]
+    fn from(kv: &KV) -> Self {
+        kv.to_entry()
+    }
+}
+
+/// KVList is Badger's `pb.KVList`, a batch of [`KV`]s sent by streams.
+///
+/// [`KV`]: struct.KV.html
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash)]
+pub struct KVList {
+    kvs: Vec<KV>,
+    alloc_ref: u64,
+}
+
+impl KVList {
+    /// Returns an empty KVList
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            kvs: Vec::new(),
+            alloc_ref: 0,
+        }
+    }
+
+    /// Returns a KVList of the entries
+    #[inline]
+    pub fn from_entries<'a>(entries: impl IntoIterator<Item = &'a Entry>) -> Self {
+        Self {
+            kvs: entries.into_iter().map(KV::from_entry).collect(),
+            alloc_ref: 0,
+        }
+    }
+
+    /// Returns an iterator over the entries of the list
+    #[inline]
+    pub fn entries(&self) -> impl core::iter::Iterator<Item = Entry> + '_ {
+        self.kvs.iter().map(KV::to_entry)
+    }
+
+    /// Appends a KV to the list
+    #[inline]
+    pub fn push(&mut self, kv: KV) {
+        self.kvs.push(kv);
+    }
+
+    /// Returns the KVs
+    #[inline]
+    pub fn as_slice(&self) -> &[KV] {
+        self.kvs.as_slice()
+    }
+
+    /// Returns the KVs
+    #[inline]
+    pub fn into_vec(self) -> Vec<KV> {
+        self.kvs
+    }
+
+    /// Returns the number of KVs
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.kvs.len()
+    }
+
+    /// Returns true if the list has no KV
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.kvs.is_empty()
+    }
+
+    /// Set the alloc ref, which is used by Badger internally
+    #[inline]
+    pub fn set_alloc_ref(&mut self, alloc_ref: u64) {
+        self.alloc_ref = alloc_ref;
+    }
+
+    /// Get the alloc ref
+    #[inline]
+    pub fn get_alloc_ref(&self) -> u64 {
+        self.alloc_ref
+    }
+
+    /// Encodes the list to the buffer
+    pub fn encode_to(&self, dst: &mut Vec<u8>) {
+        let mut buf = Vec::new();
+        for kv in &self.kvs {
+            buf.clear();
+            kv.encode_to(&mut buf);
+            // empty messages are still written, as the field is repeated
+            put_tag(dst, 1, WIRE_LEN);
+            binary_put_uvariant_to_bufmut(dst, buf.len() as u64);
+            dst.extend_from_slice(&buf);
+        }
+        put_varint_field(dst, 10, self.alloc_ref);
+    }
+
+    /// Encodes the list
+    #[inline]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.encode_to(&mut buf);
+        buf
+    }
+
+    /// Decodes a list from the encoded form
+    pub fn decode(src: &[u8]) -> Result<Self, Error> {
+        let mut list = Self::new();
+        read_fields(src, |field, wire, r| {
+            match field {
+                1 => {
+                    expect_wire(wire, WIRE_LEN)?;
+                    list.kvs.push(KV::decode(r.bytes()?)?);
+                }
+                10 => {
+                    expect_wire(wire, WIRE_VARINT)?;
+                    list.alloc_ref = r.varint()?;
+                }
+                _ => return Ok(false),
+            }
+            Ok(true)
+        })?;
+        Ok(list)
+    }
+}
+
+impl From<Vec<KV>> for KVList {
+    #[inline]
+    fn from(kvs: Vec<KV>) -> Self {
+        Self { kvs, alloc_ref: 0 }
+    }
+}
+
+impl<'a> IntoIterator for &'a KVList {
+    type Item = &'a KV;
+    type IntoIter = core::slice::Iter<'a, KV>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.kvs.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::OP;
+
+    #[test]
+    fn test_kv_list() {
+        let kv = KV::new()
+            .set_key(Bytes::from("a"))
+            .set_value(Bytes::from("b"))
+            .set_user_meta(1)
+            .set_version(5)
+            .set_meta(OP::BIT_TXN.bits())
+            .set_stream_id(3)
+            .set_stream_done(true)
+            .set_kind(KVKind::DataKey);
+        // the encoding of the same message by the Go protobuf runtime
+        let expected = [
+            0x0a, 0x01, b'a', 0x12, 0x01, b'b', 0x1a, 0x01, 0x01, 0x20, 0x05, 0x32, 0x01, 0x40,
+            0x50, 0x03, 0x58, 0x01, 0x60, 0x01,
+        ];
+        assert_eq!(kv.encode(), expected);
+        assert_eq!(KV::decode(&expected).unwrap(), kv);
+
+        // unknown fields of all the wire types are skipped
+        let mut unknown = expected.to_vec();
+        unknown.extend_from_slice(&[0x38, 0x96, 0x01, 0x41, 0, 0, 0, 0, 0, 0, 0, 0]);
+        unknown.extend_from_slice(&[0x4a, 0x02, 0, 0, 0x4d, 0, 0, 0, 0]);
+        assert_eq!(KV::decode(&unknown).unwrap(), kv);
+        assert!(KV::decode(&expected[..expected.len() - 1]).is_err());
+        assert!(KV::decode(&[0x08, 0x01]).is_err());
+        assert!(KV::decode(&[0x60, 0x09]).is_err());
+
+        let ent = kv.to_entry();
+        assert_eq!(ent.get_key().parse_key(), b"a");
+        assert_eq!(ent.get_key().parse_timestamp(), 5);
+        assert_eq!(ent.get_value().get_version(), 5);
+        assert_eq!(ent.get_value().get_meta(), OP::BIT_TXN.bits());
+        assert_eq!(ent.get_value().get_user_meta(), 1);
+        let back = KV::from_entry(&ent);
+        assert_eq!(
+            back,
+            kv.clone()
+                .set_stream_id(0)
+                .set_stream_done(false)
+                .set_kind(KVKind::Key)
+        );
+
+        let mut list = KVList::from_entries([&ent, &Entry::new()]);
+        list.set_alloc_ref(7);
+        let buf = list.encode();
+        assert_eq!(buf[0], 0x0a);
+        assert_eq!(&buf[buf.len() - 2..], [0x50, 0x07]);
+        let decoded = KVList::decode(&buf).unwrap();
+        assert_eq!(decoded, list);
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded.entries().next().unwrap(), ent);
+    }
+}
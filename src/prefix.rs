@@ -0,0 +1,306 @@
+use crate::bloom::{BloomFilter, BloomFilterBuilder, DEFAULT_BITS_PER_KEY};
+use crate::bytes::Bytes;
+use crate::KeyExt;
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// PrefixExtractor extracts the prefix of user keys (without timestamp) for prefix filters.
+///
+/// For a scan prefix `p` whose extracted prefix is `e`, every key beginning with `p` must
+/// have the extracted prefix `e` as well, so that a prefix filter can answer whether any
+/// key begins with `p`.
+pub trait PrefixExtractor: Send + Sync {
+    /// Returns the name of the extractor, which is stored in the table properties.
+    /// A prefix filter is only used by the extractor with the same name.
+    fn name(&self) -> &str;
+
+    /// Returns the prefix of the user key, or `None` if the key is not in the domain
+    /// of the extractor.
+    fn extract<'a>(&self, user_key: &'a [u8]) -> Option<&'a [u8]>;
+
+    /// Returns whether the user key is in the domain of the extractor.
+    #[inline]
+    fn in_domain(&self, user_key: &[u8]) -> bool {
+        self.extract(user_key).is_some()
+    }
+}
+
+/// FixedPrefix extracts the first `n` bytes, keys shorter than `n` are not in the domain.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct FixedPrefix {
+    len: usize,
+    name: String,
+}
+
+impl FixedPrefix {
+    /// Returns a FixedPrefix extracting the first `len` bytes
+    #[inline]
+    pub fn new(len: usize) -> Self {
+        Self {
+            len,
+            name: format!("kvstructs.FixedPrefix:{}", len),
+        }
+    }
+}
+
+impl PrefixExtractor for FixedPrefix {
+    #[inline]
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    #[inline]
+    fn extract<'a>(&self, user_key: &'a [u8]) -> Option<&'a [u8]> {
+        user_key.get(..self.len)
+    }
+}
+
+/// DelimiterPrefix extracts the bytes up to and including the first delimiter,
+/// keys without the delimiter are not in the domain.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DelimiterPrefix {
+    delimiter: u8,
+    name: String,
+}
+
+impl DelimiterPrefix {
+    /// Returns a DelimiterPrefix with the delimiter
+    #[inline]
+    pub fn new(delimiter: u8) -> Self {
+        Self {
+            delimiter,
+            name: format!("kvstructs.DelimiterPrefix:{}", delimiter),
+        }
+    }
+}
+
+impl PrefixExtractor for DelimiterPrefix {
+    #[inline]
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    #[inline]
+    fn extract<'a>(&self, user_key: &'a [u8]) -> Option<&'a [u8]> {
+        user_key
+            .iter()
+            .position(|b| *b == self.delimiter)
+            .map(|pos| &user_key[..=pos])
+    }
+}
+
+/// KeyspacePrefix extracts the shortest of the configured keyspaces which the key begins with,
+/// keys not in any keyspace are not in the domain.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct KeyspacePrefix {
+    /// sorted by length
+    keyspaces: Vec<Vec<u8>>,
+    name: String,
+}
+
+impl KeyspacePrefix {
+    /// Returns a KeyspacePrefix with the keyspaces
+    pub fn new<I, P>(keyspaces: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<[u8]>,
+    {
+        let mut keyspaces = keyspaces
+            .into_iter()
+            .map(|p| p.as_ref().to_vec())
+            .collect::<Vec<_>>();
+        keyspaces.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+        keyspaces.dedup();
+
+        let mut name = String::from("kvstructs.KeyspacePrefix");
+        for ks in &keyspaces {
+            name.push(':');
+            for b in ks {
+                name.push_str(&format!("{:02x}", b));
+            }
+        }
+        Self { keyspaces, name }
+    }
+}
+
+impl PrefixExtractor for KeyspacePrefix {
+    #[inline]
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    #[inline]
+    fn extract<'a>(&self, user_key: &'a [u8]) -> Option<&'a [u8]> {
+        self.keyspaces
+            .iter()
+            .find(|ks| user_key.starts_with(ks))
+            .map(|ks| &user_key[..ks.len()])
+    }
+}
+
+/// PrefixFilterBuilder records the extracted prefixes of keys, and builds a [`PrefixFilter`].
+///
+/// [`PrefixFilter`]: struct.PrefixFilter.html
+#[derive(Clone)]
+pub struct PrefixFilterBuilder {
+    extractor: Arc<dyn PrefixExtractor>,
+    bloom: BloomFilterBuilder,
+}
+
+impl fmt::Debug for PrefixFilterBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PrefixFilterBuilder")
+            .field("extractor", &self.extractor.name())
+            .field("bloom", &self.bloom)
+            .finish()
+    }
+}
+
+impl PrefixFilterBuilder {
+    /// Returns a PrefixFilterBuilder with [`DEFAULT_BITS_PER_KEY`].
+    ///
+    /// [`DEFAULT_BITS_PER_KEY`]: ../bloom/constant.DEFAULT_BITS_PER_KEY.html
+    #[inline]
+    pub fn new(extractor: Arc<dyn PrefixExtractor>) -> Self {
+        Self {
+            extractor,
+            bloom: BloomFilterBuilder::new(DEFAULT_BITS_PER_KEY),
+        }
+    }
+
+    /// Set the number of bits per prefix
+    #[inline]
+    pub fn set_bits_per_key(mut self, bits_per_key: usize) -> Self {
+        self.bloom = BloomFilterBuilder::new(bits_per_key);
+        self
+    }
+
+    /// Records the prefix of the key, the timestamp of the key is ignored.
+    #[inline]
+    pub fn add(&mut self, key: impl KeyExt) {
+        if let Some(prefix) = self.extractor.extract(key.parse_key()) {
+            self.bloom.add_user_key(prefix);
+        }
+    }
+
+    /// Returns true if no prefix has been recorded
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.bloom.is_empty()
+    }
+
+    /// Builds the filter, the builder is cleared and can be reused.
+    #[inline]
+    pub fn finish(&mut self) -> PrefixFilter {
+        PrefixFilter {
+            extractor: self.extractor.clone(),
+            bloom: self.bloom.finish(),
+        }
+    }
+}
+
+/// PrefixFilter answers whether any key with a prefix may have been added.
+#[derive(Clone)]
+pub struct PrefixFilter {
+    extractor: Arc<dyn PrefixExtractor>,
+    bloom: BloomFilter,
+}
+
+impl fmt::Debug for PrefixFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PrefixFilter")
+            .field("extractor", &self.extractor.name())
+            .field("bloom", &self.bloom)
+            .finish()
+    }
+}
+
+impl PrefixFilter {
+    /// Returns a PrefixFilter from the serialized form, which must be built with an
+    /// extractor of the same name.
+    #[inline]
+    pub fn from_bytes(extractor: Arc<dyn PrefixExtractor>, data: Bytes) -> Self {
+        Self {
+            extractor,
+            bloom: BloomFilter::from_bytes(data),
+        }
+    }
+
+    /// Returns the serialized form
+    #[inline]
+    pub fn as_bytes(&self) -> &Bytes {
+        self.bloom.as_bytes()
+    }
+
+    /// Returns the prefix extractor
+    #[inline]
+    pub fn extractor(&self) -> &Arc<dyn PrefixExtractor> {
+        &self.extractor
+    }
+
+    /// Returns false if no key beginning with the prefix (without timestamp) has been added.
+    ///
+    /// Always returns true if the prefix is not in the domain of the extractor, because the
+    /// keys beginning with it may have different extracted prefixes.
+    #[inline]
+    pub fn may_contain_prefix(&self, prefix: &[u8]) -> bool {
+        match self.extractor.extract(prefix) {
+            Some(p) => self.bloom.may_contain_user_key(p),
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Key;
+
+    #[test]
+    fn test_prefix_extractor() {
+        let fixed = FixedPrefix::new(3);
+        assert_eq!(fixed.extract(b"abcd"), Some(&b"abc"[..]));
+        assert_eq!(fixed.extract(b"ab"), None);
+
+        let delim = DelimiterPrefix::new(b'/');
+        assert_eq!(delim.extract(b"users/1/name"), Some(&b"users/"[..]));
+        assert!(!delim.in_domain(b"users"));
+
+        let ks = KeyspacePrefix::new(["user", "us", "order"]);
+        assert_eq!(ks.extract(b"user1"), Some(&b"us"[..]));
+        assert_eq!(ks.extract(b"order1"), Some(&b"order"[..]));
+        assert_eq!(ks.extract(b"x"), None);
+        assert_eq!(
+            ks.name(),
+            KeyspacePrefix::new(["us", "order", "user"]).name()
+        );
+    }
+
+    #[test]
+    fn test_prefix_filter() {
+        let mut builder = PrefixFilterBuilder::new(Arc::new(DelimiterPrefix::new(b'/')));
+        for (k, ts) in [
+            ("users/1", 2),
+            ("users/1", 1),
+            ("users/2", 1),
+            ("tags/a", 1),
+        ] {
+            builder.add(Key::from(k).with_timestamp(ts));
+        }
+        builder.add(Key::from("nodelimiter").with_timestamp(1));
+        let filter = builder.finish();
+        let filter =
+            PrefixFilter::from_bytes(filter.extractor().clone(), filter.as_bytes().clone());
+
+        assert!(filter.may_contain_prefix(b"users/"));
+        assert!(filter.may_contain_prefix(b"users/1"));
+        assert!(filter.may_contain_prefix(b"tags/"));
+        assert!(!filter.may_contain_prefix(b"orders/"));
+        assert!(!filter.may_contain_prefix(b"orders/1"));
+        // not in the domain
+        assert!(filter.may_contain_prefix(b"orders"));
+    }
+}
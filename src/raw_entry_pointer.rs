@@ -47,7 +47,7 @@ impl RawEntryPointer {
     /// # Safety
     /// The inner raw key pointer must be valid.
     #[inline]
-    pub unsafe fn key(&self) -> KeyRef {
+    pub unsafe fn key(&self) -> KeyRef<'_> {
         self.key.as_key_ref()
     }
 
@@ -56,7 +56,7 @@ impl RawEntryPointer {
     /// # Safety
     /// The inner raw value pointer must be valid.
     #[inline]
-    pub unsafe fn value(&self) -> ValueRef {
+    pub unsafe fn value(&self) -> ValueRef<'_> {
         self.val.as_value_ref()
     }
 
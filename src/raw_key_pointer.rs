@@ -14,6 +14,10 @@ pub struct RawKeyPointer {
     l: u32,
 }
 
+/// The pointer borrows the bytes of the key without owning them, so it dangles once the key
+/// and all its clones are dropped. Prefer [`OwnedKeyPointer`], which keeps the key alive.
+///
+/// [`OwnedKeyPointer`]: struct.OwnedKeyPointer.html
 impl From<Key> for RawKeyPointer {
     fn from(k: Key) -> Self {
         RawKeyPointer {
@@ -83,7 +87,7 @@ impl Eq for RawKeyPointer {}
 
 impl PartialOrd<RawKeyPointer> for RawKeyPointer {
     fn partial_cmp(&self, other: &RawKeyPointer) -> Option<Ordering> {
-        unsafe { self.as_key_ref().partial_cmp(&other.as_key_ref()) }
+        Some(self.cmp(other))
     }
 }
 
@@ -92,3 +96,55 @@ impl Ord for RawKeyPointer {
         unsafe { self.as_key_ref().cmp(&other.as_key_ref()) }
     }
 }
+
+/// OwnedKeyPointer owns the [`Key`], so that the [`RawKeyPointer`]s returned by
+/// [`as_raw_key_pointer`] are valid as long as the OwnedKeyPointer is alive.
+///
+/// [`Key`]: ../struct.Key.html
+/// [`RawKeyPointer`]: struct.RawKeyPointer.html
+/// [`as_raw_key_pointer`]: #method.as_raw_key_pointer
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[repr(transparent)]
+pub struct OwnedKeyPointer {
+    key: Key,
+}
+
+impl From<Key> for OwnedKeyPointer {
+    fn from(key: Key) -> Self {
+        Self { key }
+    }
+}
+
+impl OwnedKeyPointer {
+    /// Returns a [`RawKeyPointer`] to the owned key.
+    ///
+    /// The pointer is valid as long as this OwnedKeyPointer (or any clone of it) is alive,
+    /// because moving the OwnedKeyPointer does not move the underlying bytes.
+    ///
+    /// [`RawKeyPointer`]: struct.RawKeyPointer.html
+    #[inline]
+    pub fn as_raw_key_pointer(&self) -> RawKeyPointer {
+        RawKeyPointer::from(self.key.as_key_ref())
+    }
+
+    /// Returns the owned key
+    #[inline]
+    pub fn into_key(self) -> Key {
+        self.key
+    }
+}
+
+impl Deref for OwnedKeyPointer {
+    type Target = Key;
+
+    fn deref(&self) -> &Self::Target {
+        &self.key
+    }
+}
+
+impl KeyExt for OwnedKeyPointer {
+    #[inline]
+    fn as_bytes(&self) -> &[u8] {
+        self.key.as_slice()
+    }
+}
@@ -7,7 +7,12 @@ use core::slice::from_raw_parts;
 /// RawValuePointer contains a raw pointer of the data of [`Value`]
 /// This struct is unsafe, because it does not promise the raw pointer always valid.
 ///
+/// To read values from memory-mapped files, prefer [`MmapFile`] (`memmap2` feature), whose
+/// [`ValueRef`]s are bound to the lifetime of the mapping.
+///
 /// [`Value`]: struct.Value.html
+/// [`MmapFile`]: ../mmap/struct.MmapFile.html
+/// [`ValueRef`]: ../struct.ValueRef.html
 #[derive(Debug, Copy, Clone)]
 pub struct RawValuePointer {
     pub(crate) meta: u8,
@@ -112,7 +117,7 @@ impl ValueExt for RawValuePointer {
 
 impl PartialOrd<RawValuePointer> for RawValuePointer {
     fn partial_cmp(&self, other: &RawValuePointer) -> Option<Ordering> {
-        unsafe { self.as_value_ref().partial_cmp(&other.as_value_ref()) }
+        Some(self.cmp(other))
     }
 }
 
@@ -0,0 +1,265 @@
+//! rkyv support.
+//!
+//! [`Key`], [`Value`] and [`Entry`] are archived as [`ArchivedKey`], [`ArchivedValue`] and
+//! [`ArchivedEntry`], which can be read in place (e.g. from a mmaped snapshot file) through
+//! [`KeyExt`] and [`ValueExt`] without deserializing.
+//!
+//! The version of values and the internal fields of [`Entry`] are not archived.
+use crate::bytes::Bytes;
+use crate::{Entry, Key, KeyExt, Value, ValueExt};
+use rkyv::bytecheck::CheckBytes;
+use rkyv::munge::munge;
+use rkyv::rancor::Fallible;
+use rkyv::ser::{Allocator, Writer};
+use rkyv::vec::{ArchivedVec, VecResolver};
+use rkyv::{Archive, Archived, Deserialize, Place, Portable, Serialize};
+
+/// The archived form of [`Key`], which is the same as the bytes of the key.
+///
+/// [`Key`]: struct.Key.html
+#[derive(Debug, Eq, PartialEq, Hash, Portable, CheckBytes)]
+#[bytecheck(crate = rkyv::bytecheck)]
+#[repr(transparent)]
+pub struct ArchivedKey {
+    data: ArchivedVec<u8>,
+}
+
+impl KeyExt for ArchivedKey {
+    #[inline]
+    fn as_bytes(&self) -> &[u8] {
+        self.data.as_slice()
+    }
+}
+
+impl PartialEq<Key> for ArchivedKey {
+    #[inline]
+    fn eq(&self, other: &Key) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+impl Archive for Key {
+    type Archived = ArchivedKey;
+    type Resolver = VecResolver;
+
+    #[inline]
+    fn resolve(&self, resolver: Self::Resolver, out: Place<Self::Archived>) {
+        munge!(let ArchivedKey { data } = out);
+        ArchivedVec::resolve_from_slice(self.as_bytes(), resolver, data);
+    }
+}
+
+impl<S: Fallible + Allocator + Writer + ?Sized> Serialize<S> for Key {
+    #[inline]
+    fn serialize(&self, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        ArchivedVec::serialize_from_slice(self.as_bytes(), serializer)
+    }
+}
+
+impl<D: Fallible + ?Sized> Deserialize<Key, D> for ArchivedKey {
+    #[inline]
+    fn deserialize(&self, _deserializer: &mut D) -> Result<Key, D::Error> {
+        Ok(Key::copy_from_slice(self.as_bytes()))
+    }
+}
+
+/// The archived form of [`Value`].
+///
+/// [`Value`]: struct.Value.html
+#[derive(Debug, Eq, PartialEq, Hash, Portable, CheckBytes)]
+#[bytecheck(crate = rkyv::bytecheck)]
+#[repr(C)]
+pub struct ArchivedValue {
+    meta: u8,
+    user_meta: u8,
+    expires_at: Archived<u64>,
+    value: ArchivedVec<u8>,
+}
+
+impl ValueExt for ArchivedValue {
+    #[inline]
+    fn parse_value(&self) -> &[u8] {
+        self.value.as_slice()
+    }
+
+    /// Returns the value data, the data is copied as it lives in the archive.
+    #[inline]
+    fn parse_value_to_bytes(&self) -> Bytes {
+        Bytes::copy_from_slice(self.parse_value())
+    }
+
+    #[inline]
+    fn get_meta(&self) -> u8 {
+        self.meta
+    }
+
+    #[inline]
+    fn get_user_meta(&self) -> u8 {
+        self.user_meta
+    }
+
+    #[inline]
+    fn get_expires_at(&self) -> u64 {
+        self.expires_at.to_native()
+    }
+}
+
+impl PartialEq<Value> for ArchivedValue {
+    #[inline]
+    fn eq(&self, other: &Value) -> bool {
+        self.meta == other.meta
+            && self.user_meta == other.user_meta
+            && self.get_expires_at() == other.expires_at
+            && self.parse_value() == other.parse_value()
+    }
+}
+
+impl Archive for Value {
+    type Archived = ArchivedValue;
+    type Resolver = VecResolver;
+
+    #[inline]
+    fn resolve(&self, resolver: Self::Resolver, out: Place<Self::Archived>) {
+        munge!(let ArchivedValue { meta, user_meta, expires_at, value } = out);
+        self.meta.resolve((), meta);
+        self.user_meta.resolve((), user_meta);
+        self.expires_at.resolve((), expires_at);
+        ArchivedVec::resolve_from_slice(self.parse_value(), resolver, value);
+    }
+}
+
+impl<S: Fallible + Allocator + Writer + ?Sized> Serialize<S> for Value {
+    #[inline]
+    fn serialize(&self, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        ArchivedVec::serialize_from_slice(self.parse_value(), serializer)
+    }
+}
+
+impl<D: Fallible + ?Sized> Deserialize<Value, D> for ArchivedValue {
+    #[inline]
+    fn deserialize(&self, _deserializer: &mut D) -> Result<Value, D::Error> {
+        Ok(Value::with_all_fields(
+            self.meta,
+            self.user_meta,
+            self.get_expires_at(),
+            0,
+            self.parse_value_to_bytes(),
+        ))
+    }
+}
+
+/// The archived form of [`Entry`].
+///
+/// [`Entry`]: struct.Entry.html
+#[derive(Debug, Eq, PartialEq, Hash, Portable, CheckBytes)]
+#[bytecheck(crate = rkyv::bytecheck)]
+#[repr(C)]
+pub struct ArchivedEntry {
+    key: ArchivedKey,
+    value: ArchivedValue,
+}
+
+impl ArchivedEntry {
+    /// Get the archived key
+    #[inline]
+    pub fn get_key(&self) -> &ArchivedKey {
+        &self.key
+    }
+
+    /// Get the archived value
+    #[inline]
+    pub fn get_value(&self) -> &ArchivedValue {
+        &self.value
+    }
+}
+
+impl PartialEq<Entry> for ArchivedEntry {
+    #[inline]
+    fn eq(&self, other: &Entry) -> bool {
+        self.key == *other.get_key() && self.value == *other.get_value()
+    }
+}
+
+impl Archive for Entry {
+    type Archived = ArchivedEntry;
+    type Resolver = (VecResolver, VecResolver);
+
+    #[inline]
+    fn resolve(&self, resolver: Self::Resolver, out: Place<Self::Archived>) {
+        munge!(let ArchivedEntry { key, value } = out);
+        self.get_key().resolve(resolver.0, key);
+        self.get_value().resolve(resolver.1, value);
+    }
+}
+
+impl<S: Fallible + Allocator + Writer + ?Sized> Serialize<S> for Entry {
+    #[inline]
+    fn serialize(&self, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        Ok((
+            self.get_key().serialize(serializer)?,
+            self.get_value().serialize(serializer)?,
+        ))
+    }
+}
+
+impl<D: Fallible + ?Sized> Deserialize<Entry, D> for ArchivedEntry {
+    #[inline]
+    fn deserialize(&self, deserializer: &mut D) -> Result<Entry, D::Error> {
+        Ok(Entry::new_from_kv(
+            self.key.deserialize(deserializer)?,
+            self.value.deserialize(deserializer)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::OP;
+    use alloc::vec::Vec;
+    use rkyv::rancor::Error;
+
+    #[test]
+    fn test_rkyv() {
+        let entries = (0..3u64)
+            .map(|i| {
+                Entry::new_from_kv(
+                    Key::copy_from_slice(b"key").with_timestamp(i + 1),
+                    Value::new()
+                        .set_meta(OP::BIT_DELETE.bits() * (i == 2) as u8)
+                        .set_user_meta(i as u8)
+                        .set_expires_at(i * 1000)
+                        .set_data(Bytes::from(alloc::format!("val{}", i))),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let buf = rkyv::to_bytes::<Error>(&entries).unwrap();
+        let archived = rkyv::access::<Archived<Vec<Entry>>, Error>(&buf).unwrap();
+        assert_eq!(archived.len(), entries.len());
+        for (a, ent) in archived.iter().zip(&entries) {
+            assert_eq!(a, ent);
+            assert!(a.get_key().same_key(ent.get_key()));
+            assert_eq!(
+                a.get_key().parse_timestamp(),
+                ent.get_key().parse_timestamp()
+            );
+            assert_eq!(a.get_value().as_value_ref(), ent.get_value().as_value_ref());
+            assert_eq!(a.get_value().encoded_size(), ent.get_value().encoded_size());
+        }
+        assert!(archived[2].get_value().is_deleted_or_expired(0));
+        assert!(archived[1].get_value().is_deleted_or_expired(1000));
+        assert!(!archived[0].get_value().is_deleted_or_expired(u64::MAX));
+
+        let deserialized = rkyv::deserialize::<Vec<Entry>, Error>(archived).unwrap();
+        for (a, b) in deserialized.iter().zip(&entries) {
+            assert_eq!(a.get_key(), b.get_key());
+            assert_eq!(a.get_value(), b.get_value());
+        }
+
+        let mut bad = buf.to_vec();
+        let len = bad.len();
+        bad.truncate(len - 4);
+        assert!(rkyv::access::<Archived<Vec<Entry>>, Error>(&bad).is_err());
+    }
+}
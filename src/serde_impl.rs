@@ -0,0 +1,449 @@
+//! Serde support.
+//!
+//! Binary formats get the compact forms: keys as bytes, values as encoded values (see
+//! [`ValueExt::encode`]) and [`OP`] as a byte.
+//!
+//! Human-readable formats get the readable forms: keys as the hex user key and the
+//! timestamp, values as the meta flags, the user meta, the expiration and the hex data,
+//! and [`OP`] as a list of flag names.
+//!
+//! The version of values and the internal fields of [`Entry`] are not serialized.
+use crate::bytes::{Bytes, BytesMut};
+use crate::{
+    binary_uvarint, check_encoded_value, EncodedValue, Entry, Header, Key, KeyExt, KeyMut, Value,
+    ValueExt, ValueMut, EXPIRATION_OFFSET, OP, TIMESTAMP_SIZE,
+};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::ser::{SerializeSeq, Serializer};
+use serde::{Deserialize, Serialize};
+
+const HEX: &[u8; 16] = b"0123456789abcdef";
+
+fn to_hex(data: &[u8]) -> String {
+    let mut s = String::with_capacity(data.len() * 2);
+    for b in data {
+        s.push(HEX[(b >> 4) as usize] as char);
+        s.push(HEX[(b & 0xf) as usize] as char);
+    }
+    s
+}
+
+fn from_hex<E: de::Error>(s: &str) -> Result<Vec<u8>, E> {
+    fn nibble<E: de::Error>(c: u8) -> Result<u8, E> {
+        match c {
+            b'0'..=b'9' => Ok(c - b'0'),
+            b'a'..=b'f' => Ok(c - b'a' + 10),
+            b'A'..=b'F' => Ok(c - b'A' + 10),
+            _ => Err(E::custom(format!("invalid hex character {:?}", c as char))),
+        }
+    }
+
+    let s = s.as_bytes();
+    if !s.len().is_multiple_of(2) {
+        return Err(E::custom("odd number of hex digits"));
+    }
+    s.chunks_exact(2)
+        .map(|c| Ok((nibble(c[0])? << 4) | nibble(c[1])?))
+        .collect()
+}
+
+/// Deserializes owned bytes from bytes or a sequence of u8.
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("bytes")
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(v.to_vec())
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(v)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut v = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(b) = seq.next_element()? {
+            v.push(b);
+        }
+        Ok(v)
+    }
+}
+
+/// The meta byte, which is readable as the flag names of [`OP`] and hex unknown bits.
+struct Meta(u8);
+
+impl Serialize for Meta {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if !serializer.is_human_readable() {
+            return serializer.serialize_u8(self.0);
+        }
+
+        let op = OP::from_bits_truncate(self.0);
+        let unknown = self.0 & !OP::all().bits();
+        let mut seq = serializer.serialize_seq(None)?;
+        for (name, flag) in OP_NAMES {
+            if op.contains(*flag) {
+                seq.serialize_element(name)?;
+            }
+        }
+        if unknown != 0 {
+            seq.serialize_element(&format!("{:#04x}", unknown))?;
+        }
+        seq.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Meta {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if !deserializer.is_human_readable() {
+            return u8::deserialize(deserializer).map(Meta);
+        }
+
+        let mut meta = 0;
+        for name in Vec::<String>::deserialize(deserializer)? {
+            meta |= match OP_NAMES.iter().find(|(n, _)| *n == name) {
+                Some((_, flag)) => flag.bits(),
+                None => name
+                    .strip_prefix("0x")
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or_else(|| de::Error::custom(format!("unknown meta flag {:?}", name)))?,
+            };
+        }
+        Ok(Meta(meta))
+    }
+}
+
+const OP_NAMES: &[(&str, OP)] = &[
+    ("BIT_DELETE", OP::BIT_DELETE),
+    ("BIT_VALUE_POINTER", OP::BIT_VALUE_POINTER),
+    (
+        "BIT_DISCARD_EARLIER_VERSIONS",
+        OP::BIT_DISCARD_EARLIER_VERSIONS,
+    ),
+    ("BIT_MERGE_ENTRY", OP::BIT_MERGE_ENTRY),
+    ("BIT_TXN", OP::BIT_TXN),
+    ("BIT_FIN_TXN", OP::BIT_FIN_TXN),
+];
+
+impl Serialize for OP {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Meta(self.bits()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for OP {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let Meta(bits) = Meta::deserialize(deserializer)?;
+        OP::from_bits(bits).ok_or_else(|| de::Error::custom("unknown bits in OP"))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Key")]
+struct ReadableKey {
+    key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timestamp: Option<u64>,
+}
+
+fn serialize_key<S: Serializer>(key: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    if !serializer.is_human_readable() {
+        return serializer.serialize_bytes(key);
+    }
+
+    // keys not longer than the timestamp do not have user keys.
+    let readable = if key.len() > TIMESTAMP_SIZE {
+        ReadableKey {
+            key: to_hex(key.parse_key()),
+            timestamp: Some(key.parse_timestamp()),
+        }
+    } else {
+        ReadableKey {
+            key: to_hex(key),
+            timestamp: None,
+        }
+    };
+    readable.serialize(serializer)
+}
+
+fn deserialize_key<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    if !deserializer.is_human_readable() {
+        return deserializer.deserialize_byte_buf(BytesVisitor);
+    }
+
+    let readable = ReadableKey::deserialize(deserializer)?;
+    let mut key = from_hex(&readable.key)?;
+    if let Some(ts) = readable.timestamp {
+        key.extend_from_slice(&(u64::MAX - ts).to_be_bytes());
+    }
+    Ok(key)
+}
+
+impl Serialize for Key {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_key(self.as_bytes(), serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_key(deserializer).map(Key::from)
+    }
+}
+
+impl Serialize for KeyMut {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_key(self.as_bytes(), serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyMut {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let key = deserialize_key(deserializer)?;
+        let mut k = KeyMut::with_capacity(key.len());
+        k.extend_from_slice(&key);
+        Ok(k)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Value")]
+struct ReadableValue {
+    meta: Meta,
+    user_meta: u8,
+    expires_at: u64,
+    value: String,
+}
+
+fn serialize_value<S: Serializer>(val: impl ValueExt, serializer: S) -> Result<S::Ok, S::Error> {
+    if !serializer.is_human_readable() {
+        let mut buf = alloc::vec![0; val.encoded_size() as usize];
+        val.encode(&mut buf);
+        return serializer.serialize_bytes(&buf);
+    }
+
+    ReadableValue {
+        meta: Meta(val.get_meta()),
+        user_meta: val.get_user_meta(),
+        expires_at: val.get_expires_at(),
+        value: to_hex(val.parse_value()),
+    }
+    .serialize(serializer)
+}
+
+/// Returns the encoded value and the size of the encoded expiration.
+fn deserialize_value<'de, D: Deserializer<'de>>(deserializer: D) -> Result<(Bytes, u8), D::Error> {
+    let encoded = if !deserializer.is_human_readable() {
+        let encoded = deserializer.deserialize_byte_buf(BytesVisitor)?;
+        check_encoded_value(&encoded).map_err(de::Error::custom)?;
+        encoded
+    } else {
+        let readable = ReadableValue::deserialize(deserializer)?;
+        let data = from_hex::<D::Error>(&readable.value)?;
+        let val = Value::new()
+            .set_meta(readable.meta.0)
+            .set_user_meta(readable.user_meta)
+            .set_expires_at(readable.expires_at);
+        let mut buf = alloc::vec![0; val.encoded_size() as usize + data.len()];
+        val.set_data(Bytes::from(data)).encode(&mut buf);
+        buf
+    };
+    let (_, sz) = binary_uvarint(&encoded[EXPIRATION_OFFSET..]);
+    Ok((Bytes::from(encoded), sz as u8))
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_value(self.as_value_ref(), serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_value(deserializer).map(|(encoded, _)| Value::decode_bytes(encoded))
+    }
+}
+
+impl Serialize for ValueMut {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_value(self.as_value_ref(), serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ValueMut {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let val = Value::deserialize(deserializer)?;
+        Ok(ValueMut {
+            meta: val.meta,
+            user_meta: val.user_meta,
+            expires_at: val.expires_at,
+            version: 0,
+            value: BytesMut::from(val.value.as_ref()),
+        })
+    }
+}
+
+impl Serialize for EncodedValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if !serializer.is_human_readable() {
+            return serializer.serialize_bytes(&self.data);
+        }
+        serialize_value(self.as_value_ref(), serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for EncodedValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_value(deserializer).map(|(data, expires_sz)| EncodedValue { data, expires_sz })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Entry")]
+struct EntryRepr {
+    key: Key,
+    value: Value,
+}
+
+impl Serialize for Entry {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        EntryRepr {
+            key: self.get_key().clone(),
+            value: self.get_value().clone(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Entry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        EntryRepr::deserialize(deserializer).map(|e| Entry::new_from_kv(e.key, e.value))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Header")]
+struct HeaderRepr {
+    meta: Meta,
+    user_meta: u8,
+    key_len: u32,
+    value_len: u32,
+    expires_at: u64,
+}
+
+impl Serialize for Header {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        HeaderRepr {
+            meta: Meta(self.get_meta()),
+            user_meta: self.get_user_meta(),
+            key_len: self.get_key_len(),
+            value_len: self.get_value_len(),
+            expires_at: self.get_expires_at(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Header {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let h = HeaderRepr::deserialize(deserializer)?;
+        Ok(Header::new(
+            h.meta.0,
+            h.user_meta,
+            h.key_len,
+            h.value_len,
+            h.expires_at,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ValueMutExt;
+
+    fn round_trip<T>(val: &T) -> (String, T, T)
+    where
+        T: Serialize + for<'de> Deserialize<'de>,
+    {
+        let json = serde_json::to_string(val).unwrap();
+        let from_json = serde_json::from_str(&json).unwrap();
+        let bin = postcard::to_allocvec(val).unwrap();
+        let from_bin = postcard::from_bytes(&bin).unwrap();
+        (json, from_json, from_bin)
+    }
+
+    #[test]
+    fn test_serde() {
+        let key = Key::from("ab").with_timestamp(7);
+        let (json, a, b) = round_trip(&key);
+        assert_eq!(json, r#"{"key":"6162","timestamp":7}"#);
+        assert_eq!((a, b), (key.clone(), key.clone()));
+        let short = Key::from("ab");
+        let (json, a, _) = round_trip(&short);
+        assert_eq!(json, r#"{"key":"6162"}"#);
+        assert_eq!(a, short);
+
+        let mut key_mut = KeyMut::new();
+        key_mut.extend_from_slice(key.as_slice());
+        let (_, a, b) = round_trip(&key_mut);
+        assert_eq!((a.freeze(), b.freeze()), (key.clone(), key.clone()));
+
+        let val = Value::new()
+            .set_meta(OP::BIT_DELETE.bits() | OP::BIT_TXN.bits() | 0x10)
+            .set_user_meta(3)
+            .set_expires_at(1000)
+            .set_data(Bytes::from("v"));
+        let (json, a, b) = round_trip(&val);
+        assert_eq!(
+            json,
+            r#"{"meta":["BIT_DELETE","BIT_TXN","0x10"],"user_meta":3,"expires_at":1000,"value":"76"}"#
+        );
+        assert_eq!((a, b), (val.clone(), val.clone()));
+        assert_eq!(
+            postcard::to_allocvec(&val).unwrap()[1..],
+            val.to_encoded().leak_data()[..]
+        );
+
+        let mut val_mut = ValueMut::default();
+        val_mut.set_user_meta(9);
+        let (_, a, b) = round_trip(&val_mut);
+        assert_eq!((a, b), (val_mut.clone(), val_mut));
+
+        let enc = val.to_encoded();
+        let (_, a, b) = round_trip(&enc);
+        assert_eq!((a, b), (enc.clone(), enc));
+
+        let ent = Entry::new_from_kv(key, val);
+        let (_, a, b) = round_trip(&ent);
+        assert_eq!((a, b), (ent.clone(), ent));
+
+        let h = Header::new(1, 2, 3, 4, 5);
+        let (json, a, b) = round_trip(&h);
+        assert_eq!(
+            json,
+            r#"{"meta":["BIT_DELETE"],"user_meta":2,"key_len":3,"value_len":4,"expires_at":5}"#
+        );
+        assert_eq!((a, b), (h, h));
+
+        let op = OP::BIT_MERGE_ENTRY | OP::BIT_FIN_TXN;
+        let (json, a, b) = round_trip(&op);
+        assert_eq!(json, r#"["BIT_MERGE_ENTRY","BIT_FIN_TXN"]"#);
+        assert_eq!((a, b), (op, op));
+        assert!(serde_json::from_str::<OP>(r#"["0x10"]"#).is_err());
+        assert!(serde_json::from_str::<Value>(
+            r#"{"meta":[],"user_meta":0,"expires_at":0,"value":"7"}"#
+        )
+        .is_err());
+        assert!(postcard::from_bytes::<Value>(&[1, 0]).is_err());
+    }
+}
@@ -0,0 +1,641 @@
+use crate::arena::Arena;
+use crate::iterator::{Iterator, SeekFrom};
+use crate::{compare_key_in, Error, KeyExt, KeyRef, Value, ValueExt, ValueRef};
+use core::cmp::Ordering as CmpOrdering;
+use core::mem;
+use core::ptr;
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+/// The max height of the skiplist
+pub const MAX_HEIGHT: usize = 20;
+
+/// Each level has 1/4 probability to grow.
+const HEIGHT_INCREASE: u32 = u32::MAX / 4;
+
+const NODE_SIZE: u32 = mem::size_of::<Node>() as u32;
+const NODE_ALIGN: u32 = mem::align_of::<Node>() as u32;
+const TOWER_SLOT_SIZE: u32 = mem::size_of::<AtomicU32>() as u32;
+
+/// Returns the size of a node with the height, including its tower.
+#[inline]
+const fn node_size(height: usize) -> u32 {
+    NODE_SIZE + height as u32 * TOWER_SLOT_SIZE
+}
+
+/// The node is followed by its tower in the arena, which has `height` slots of `AtomicU32`,
+/// the offsets of the next nodes at each level, 0 means null. Only the levels the node
+/// belongs to are allocated, as most of the nodes are short.
+#[repr(C)]
+struct Node {
+    /// the offset and the size of the encoded value, offset is the lower 32 bits.
+    value: AtomicU64,
+    key_offset: u32,
+    key_size: u32,
+    height: u16,
+}
+
+impl Node {
+    #[inline]
+    fn value_offset(&self) -> (u32, u32) {
+        let v = self.value.load(Ordering::Acquire);
+        (v as u32, (v >> 32) as u32)
+    }
+}
+
+#[inline]
+const fn encode_value_offset(offset: u32, size: u32) -> u64 {
+    ((size as u64) << 32) | offset as u64
+}
+
+/// SkipList is a lock-free skiplist, which stores the keys and the encoded values in an arena.
+///
+/// The keys are ordered by [`compare_key`], so the versions of a key are ordered from the
+/// newest to the oldest. Inserting an existing key overwrites its value.
+///
+/// The arena has a fixed capacity, [`insert`] returns an error once the arena is full,
+/// use [`mem_size`] and [`remaining`] to decide when to flush the skiplist.
+///
+/// [`compare_key`]: ../fn.compare_key.html
+/// [`insert`]: #method.insert
+/// [`mem_size`]: #method.mem_size
+/// [`remaining`]: #method.remaining
+pub struct SkipList {
+    height: AtomicU32,
+    head: u32,
+    seed: AtomicU64,
+    arena: Arena,
+}
+
+impl SkipList {
+    /// Returns a SkipList, the arena of the skiplist can hold `cap` bytes.
+    ///
+    /// # Panics
+    /// If the capacity is too small to hold the head node.
+    pub fn new(cap: usize) -> Self {
+        let arena = Arena::new(cap);
+        let head = Self::new_node(
+            &arena,
+            KeyRef::new(&[]),
+            Value::default(),
+            MAX_HEIGHT as u16,
+        )
+        .expect("kvstructs: the capacity of skiplist is too small");
+        Self {
+            height: AtomicU32::new(1),
+            head,
+            seed: AtomicU64::new(cap as u64),
+            arena,
+        }
+    }
+
+    /// Returns the number of bytes used by the skiplist
+    #[inline]
+    pub fn mem_size(&self) -> usize {
+        self.arena.len()
+    }
+
+    /// Returns the capacity of the skiplist in bytes
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.arena.capacity()
+    }
+
+    /// Returns the number of bytes can be used by the skiplist
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.arena.remaining()
+    }
+
+    /// Returns true if the skiplist does not contain any key.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.next_offset(self.head, 0) == 0
+    }
+
+    /// Returns the current height of the skiplist
+    #[inline]
+    pub fn height(&self) -> usize {
+        self.height.load(Ordering::Acquire) as usize
+    }
+
+    /// Inserts the key and value, the value is overwritten if the key (with the same timestamp)
+    /// already exists.
+    ///
+    /// Returns an error if the skiplist does not have enough space.
+    pub fn insert(&self, key: impl KeyExt, val: impl ValueExt) -> Result<(), Error> {
+        let key = key.as_bytes();
+        let mut list_height = self.height();
+        let mut prev = [0u32; MAX_HEIGHT + 1];
+        let mut next = [0u32; MAX_HEIGHT + 1];
+        prev[list_height] = self.head;
+
+        for i in (0..list_height).rev() {
+            // use higher level to speed up for current level.
+            let (p, n) = self.find_splice_for_level(key, prev[i + 1], i);
+            if p == n {
+                return self.set_value(p, &val);
+            }
+            prev[i] = p;
+            next[i] = n;
+        }
+
+        let height = self.random_height();
+        let x = Self::new_node(
+            &self.arena,
+            KeyRef::new(key),
+            val.as_value_ref(),
+            height as u16,
+        )
+        .map_err(|_| self.arena_full(node_size(height) as usize + key.len(), &val))?;
+
+        // try to increase the height of the skiplist.
+        while height > list_height {
+            match self.height.compare_exchange_weak(
+                list_height as u32,
+                height as u32,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => break,
+                Err(h) => list_height = h as usize,
+            }
+        }
+
+        // insert from the base level and up. After the node is added in base level,
+        // we cannot create a node in the level above because it would have discovered the
+        // node in the base level.
+        for i in 0..height {
+            loop {
+                if prev[i] == 0 {
+                    // the height exceeds the old list height, the levels are sparse,
+                    // so we can just search from head.
+                    debug_assert!(i > 0);
+                    let (p, n) = self.find_splice_for_level(key, self.head, i);
+                    debug_assert_ne!(p, n);
+                    prev[i] = p;
+                    next[i] = n;
+                }
+
+                self.tower(x, i).store(next[i], Ordering::Release);
+                if self
+                    .tower(prev[i], i)
+                    .compare_exchange(next[i], x, Ordering::AcqRel, Ordering::Acquire)
+                    .is_ok()
+                {
+                    break;
+                }
+
+                // CAS failed, recompute prev and next.
+                let (p, n) = self.find_splice_for_level(key, prev[i], i);
+                if p == n {
+                    debug_assert_eq!(i, 0, "equality can happen only on base level");
+                    return self.set_value(p, &val);
+                }
+                prev[i] = p;
+                next[i] = n;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the newest version of the key whose timestamp is not newer than the timestamp
+    /// of the given key.
+    pub fn get(&self, key: impl KeyExt) -> Option<(KeyRef<'_>, ValueRef<'_>)> {
+        let key = key.as_bytes();
+        let n = self.find_greater_or_equal(self.head, key);
+        if n == 0 {
+            return None;
+        }
+
+        let (k, v) = self.entry_of(n);
+        if crate::same_key_in(k.as_slice(), key) {
+            Some((k, v))
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if the skiplist contains a version of the key whose timestamp is not newer
+    /// than the timestamp of the given key.
+    #[inline]
+    pub fn contains_key(&self, key: impl KeyExt) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Returns an iterator over the skiplist
+    #[inline]
+    pub fn iter(&self) -> SkipListIterator<'_> {
+        SkipListIterator {
+            list: self,
+            node: 0,
+        }
+    }
+
+    fn new_node(
+        arena: &Arena,
+        key: impl KeyExt,
+        val: impl ValueExt,
+        height: u16,
+    ) -> Result<u32, Error> {
+        let offset = arena.allocate_or_err(node_size(height as usize) as usize, NODE_ALIGN)?;
+        let key = arena.put_key(key)?;
+        let val = arena.put_value(val)?;
+        // Safety: the node is allocated just now, and is not shared with others.
+        unsafe {
+            ptr::write(
+                arena.get_pointer(offset) as *mut Node,
+                Node {
+                    value: AtomicU64::new(encode_value_offset(
+                        val.offset(),
+                        val.encoded_bytes().len() as u32,
+                    )),
+                    key_offset: key.offset(),
+                    key_size: key.len() as u32,
+                    height,
+                },
+            );
+            for level in 0..height as u32 {
+                ptr::write(
+                    arena.get_pointer(offset + node_size(level as usize)) as *mut AtomicU32,
+                    AtomicU32::new(0),
+                );
+            }
+        }
+        Ok(offset)
+    }
+
+    #[inline]
+    fn node(&self, offset: u32) -> &Node {
+        debug_assert_ne!(offset, 0);
+        // Safety: the offset is allocated and initialized by new_node.
+        unsafe { &*(self.arena.get_pointer(offset) as *const Node) }
+    }
+
+    /// Returns the slot of the tower of the node at the level.
+    #[inline]
+    fn tower(&self, offset: u32, level: usize) -> &AtomicU32 {
+        debug_assert!(level < self.node(offset).height as usize);
+        // Safety: the tower is allocated and initialized by new_node, and the level is less
+        // than the height of the node.
+        unsafe { &*(self.arena.get_pointer(offset + node_size(level)) as *const AtomicU32) }
+    }
+
+    #[inline]
+    fn next_offset(&self, offset: u32, level: usize) -> u32 {
+        self.tower(offset, level).load(Ordering::Acquire)
+    }
+
+    #[inline]
+    fn key_of(&self, offset: u32) -> &[u8] {
+        let n = self.node(offset);
+        // Safety: the key is written before the node is published, and is never changed.
+        unsafe { self.arena.get_bytes(n.key_offset, n.key_size) }
+    }
+
+    #[inline]
+    fn entry_of(&self, offset: u32) -> (KeyRef<'_>, ValueRef<'_>) {
+        let key = KeyRef::new(self.key_of(offset));
+        let (vo, vs) = self.node(offset).value_offset();
+        // Safety: the value is written before the offset is published, and is never changed.
+        let mut val = ValueRef::decode_value_ref(unsafe { self.arena.get_bytes(vo, vs) });
+        val.set_version(key.parse_timestamp());
+        (key, val)
+    }
+
+    fn set_value(&self, node: u32, val: &impl ValueExt) -> Result<(), Error> {
+        let enc = self
+            .arena
+            .put_value(val.as_value_ref())
+            .map_err(|_| self.arena_full(0, val))?;
+        self.node(node).value.store(
+            encode_value_offset(enc.offset(), enc.encoded_bytes().len() as u32),
+            Ordering::Release,
+        );
+        Ok(())
+    }
+
+    #[inline]
+    fn arena_full(&self, node_and_key: usize, val: &impl ValueExt) -> Error {
+        Error::ArenaFull {
+            required: node_and_key + val.encoded_size() as usize,
+            remaining: self.remaining(),
+        }
+    }
+
+    fn random_height(&self) -> usize {
+        // splitmix64
+        let mut z = self
+            .seed
+            .fetch_add(0x9E37_79B9_7F4A_7C15, Ordering::Relaxed)
+            .wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        let mut rnd = z ^ (z >> 31);
+
+        let mut h = 1;
+        while h < MAX_HEIGHT && (rnd as u32) <= HEIGHT_INCREASE {
+            h += 1;
+            rnd >>= 2;
+        }
+        h
+    }
+
+    /// Returns (before, next) such that before.key < key <= next.key at the level.
+    /// If the key is found, returns (node, node).
+    fn find_splice_for_level(&self, key: &[u8], mut before: u32, level: usize) -> (u32, u32) {
+        loop {
+            let next = self.next_offset(before, level);
+            if next == 0 {
+                return (before, next);
+            }
+
+            match compare_key_in(key, self.key_of(next)) {
+                CmpOrdering::Equal => return (next, next),
+                CmpOrdering::Less => return (before, next),
+                CmpOrdering::Greater => before = next,
+            }
+        }
+    }
+
+    /// Returns the first node >= key, searching from `start` (exclusive), which must be < key.
+    #[inline]
+    fn find_greater_or_equal(&self, start: u32, key: &[u8]) -> u32 {
+        self.find_first(start, |k| compare_key_in(k, key).is_lt())
+    }
+
+    /// Returns the first node for which `before` is false, searching from `start` (exclusive).
+    /// `before` must hold for `start` and all the nodes before the returned one.
+    fn find_first(&self, start: u32, before: impl Fn(&[u8]) -> bool) -> u32 {
+        let mut x = start;
+        let mut level = if start == self.head {
+            self.height()
+        } else {
+            self.node(start).height as usize
+        };
+        let mut next = 0;
+        while level > 0 {
+            next = self.next_offset(x, level - 1);
+            if next != 0 && before(self.key_of(next)) {
+                x = next;
+            } else {
+                level -= 1;
+            }
+        }
+        next
+    }
+}
+
+/// SkipListIterator is an [`Iterator`] over [`SkipList`], which yields the [`KeyRef`] and
+/// [`ValueRef`] borrowed from the arena of the skiplist.
+///
+/// [`Iterator`]: ../iterator/trait.Iterator.html
+/// [`SkipList`]: struct.SkipList.html
+/// [`KeyRef`]: ../struct.KeyRef.html
+/// [`ValueRef`]: ../struct.ValueRef.html
+#[derive(Copy, Clone)]
+pub struct SkipListIterator<'a> {
+    list: &'a SkipList,
+    node: u32,
+}
+
+impl SkipListIterator<'_> {
+    /// Returns the first node after the current one for which `before` is false, see
+    /// `SkipList::find_first`.
+    ///
+    /// The search starts from the tower of the current node if its top level reaches past the
+    /// target, and from the head otherwise, as most towers are too short to skip far.
+    fn find_from_current(&self, before: impl Fn(&[u8]) -> bool) -> u32 {
+        let list = self.list;
+        let top = list.node(self.node).height as usize - 1;
+        let next = list.next_offset(self.node, top);
+        if next == 0 || !before(list.key_of(next)) {
+            list.find_first(self.node, before)
+        } else {
+            list.find_first(list.head, before)
+        }
+    }
+}
+
+impl<'a> Iterator<KeyRef<'a>, ValueRef<'a>> for SkipListIterator<'a> {
+    #[inline]
+    fn next(&mut self) {
+        if self.node != 0 {
+            self.node = self.list.next_offset(self.node, 0);
+        }
+    }
+
+    #[inline]
+    fn rewind(&mut self) {
+        self.node = self.list.next_offset(self.list.head, 0);
+    }
+
+    #[inline]
+    fn seek<Q: KeyExt>(&mut self, key: Q) {
+        self.node = self
+            .list
+            .find_greater_or_equal(self.list.head, key.as_bytes());
+    }
+
+    fn seek_from<Q: KeyExt>(&mut self, key: Q, from: SeekFrom) {
+        let key = key.as_bytes();
+        match from {
+            SeekFrom::Origin => self.seek(key),
+            SeekFrom::Current => {
+                if self.node != 0 && compare_key_in(self.list.key_of(self.node), key).is_lt() {
+                    self.node = self.find_from_current(|k| compare_key_in(k, key).is_lt());
+                }
+            }
+        }
+    }
+
+    fn advance_to_next_user_key(&mut self) {
+        if self.node == 0 {
+            return;
+        }
+        // the key lives in the arena, so it is borrowed from the list instead of copied.
+        let key = self.list.key_of(self.node);
+        let cur = key.parse_key();
+        self.node = self.find_from_current(|k| k.parse_key() <= cur);
+    }
+
+    #[inline]
+    fn entry(&self) -> Option<(KeyRef<'a>, ValueRef<'a>)> {
+        (self.node != 0).then(|| self.list.entry_of(self.node))
+    }
+
+    #[inline]
+    fn key(&self) -> Option<KeyRef<'a>> {
+        (self.node != 0).then(|| KeyRef::new(self.list.key_of(self.node)))
+    }
+
+    #[inline]
+    fn val(&self) -> Option<ValueRef<'a>> {
+        self.entry().map(|(_, v)| v)
+    }
+
+    #[inline]
+    fn key_ref(&self) -> Option<KeyRef<'_>> {
+        self.key()
+    }
+
+    #[inline]
+    fn value_ref(&self) -> Option<ValueRef<'_>> {
+        self.val()
+    }
+
+    #[inline]
+    fn valid(&self) -> bool {
+        self.node != 0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Key, Value};
+    use alloc::format;
+    use alloc::sync::Arc;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_skiplist() {
+        let list = SkipList::new(1 << 16);
+        assert!(list.is_empty());
+        for i in (0..100u64).rev() {
+            let key = Key::from(format!("key{:03}", i / 2)).with_timestamp(i % 2 + 1);
+            list.insert(&key, Value::from(format!("val{}", i))).unwrap();
+        }
+        list.insert(Key::from("key000").with_timestamp(2), Value::from("new"))
+            .unwrap();
+
+        let (k, v) = list.get(Key::from("key000").with_timestamp(3)).unwrap();
+        assert_eq!(k.parse_timestamp(), 2);
+        assert_eq!(v.parse_value(), b"new");
+        assert_eq!(v.get_version(), 2);
+        assert!(list.get(Key::from("key050").with_timestamp(3)).is_none());
+
+        let mut iter = list.iter();
+        iter.rewind();
+        let mut n = 0;
+        while iter.valid() {
+            n += 1;
+            iter.next();
+        }
+        assert_eq!(n, 100);
+
+        iter.seek(Key::from("key010").with_timestamp(u64::MAX));
+        assert_eq!(iter.key().unwrap().parse_key(), b"key010");
+        iter.seek_from(Key::from("key040").with_timestamp(1), SeekFrom::Current);
+        assert_eq!(iter.key().unwrap().parse_timestamp(), 1);
+        assert_eq!(iter.val().unwrap().parse_value(), b"val80");
+
+        let small = SkipList::new(node_size(MAX_HEIGHT) as usize + 16);
+        assert!(matches!(
+            small.insert(Key::from("k").with_timestamp(1), Value::from("v")),
+            Err(Error::ArenaFull { .. })
+        ));
+    }
+
+    #[test]
+    fn test_skiplist_seek_from() {
+        let list = SkipList::new(1 << 20);
+        for i in 0..500 {
+            for ts in 1..=3 {
+                let key = Key::from(format!("key{:04}", i)).with_timestamp(ts);
+                list.insert(key, Value::from("v")).unwrap();
+            }
+        }
+
+        // near and far targets, from short and tall towers.
+        let mut iter = list.iter();
+        let mut expected = list.iter();
+        iter.rewind();
+        for i in [0, 1, 2, 7, 8, 100, 101, 350, 499] {
+            let target = Key::from(format!("key{:04}", i)).with_timestamp(2);
+            iter.seek_from(&target, SeekFrom::Current);
+            expected.seek(&target);
+            assert_eq!(iter.key(), expected.key());
+            assert_eq!(iter.key().unwrap().parse_timestamp(), 2);
+        }
+        iter.seek_from(Key::from("key9999").with_timestamp(1), SeekFrom::Current);
+        assert!(!iter.valid());
+
+        iter.rewind();
+        iter.next();
+        for i in 0..500 {
+            let key = iter.key().unwrap();
+            assert_eq!(key.parse_key(), format!("key{:04}", i).as_bytes());
+            assert_eq!(key.parse_timestamp(), if i == 0 { 2 } else { 3 });
+            iter.advance_to_next_user_key();
+        }
+        assert!(!iter.valid());
+        iter.advance_to_next_user_key();
+        assert!(!iter.valid());
+    }
+
+    #[test]
+    fn test_node_height() {
+        let list = SkipList::new(1 << 20);
+        let n = 1000;
+        for i in 0..n {
+            let key = Key::from(format!("key{:04}", i)).with_timestamp(1);
+            list.insert(key, Value::from("v")).unwrap();
+        }
+
+        // each level is a sorted list of the nodes which are high enough.
+        let mut towers = 0;
+        for level in 0..list.height() {
+            let mut prev: Option<&[u8]> = None;
+            let mut x = list.next_offset(list.head, level);
+            while x != 0 {
+                assert!(list.node(x).height as usize > level);
+                let key = list.key_of(x);
+                if let Some(p) = prev {
+                    assert!(compare_key_in(p, key).is_lt());
+                }
+                prev = Some(key);
+                x = list.next_offset(x, level);
+                towers += 1;
+            }
+        }
+        assert!(towers >= n);
+
+        // only the used levels are allocated.
+        let full = n * node_size(MAX_HEIGHT) as usize;
+        assert!(list.mem_size() < full / 2);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_concurrent_insert() {
+        let list = Arc::new(SkipList::new(1 << 20));
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let list = list.clone();
+                std::thread::spawn(move || {
+                    for i in 0..500 {
+                        let key = Key::from(format!("{:04}-{}", i, t)).with_timestamp(1);
+                        list.insert(key, Value::from("v")).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+
+        let mut iter = list.iter();
+        iter.rewind();
+        let mut prev: Option<KeyRef> = None;
+        let mut n = 0;
+        while let Some(k) = iter.key() {
+            if let Some(p) = prev {
+                assert!(p < k);
+            }
+            prev = Some(k);
+            n += 1;
+            iter.next();
+        }
+        assert_eq!(n, 2000);
+    }
+}
@@ -0,0 +1,122 @@
+//! Keys start with one of [`SHARED_KEY_PREFIXES`] so that generated keys often share
+//! prefixes. Values have any combination of [`OP`] bits, expirations which are zero,
+//! seconds-sized or any `u64`, and version 0 as the version is not encoded.
+//!
+//! The core types also implement [`proptest::arbitrary::Arbitrary`] with these strategies,
+//! so `any::<Key>()` works as well.
+//!
+//! [`SHARED_KEY_PREFIXES`]: constant.SHARED_KEY_PREFIXES.html
+//! [`OP`]: ../struct.OP.html
+use crate::bytes::Bytes;
+use crate::{EncodedValue, Entry, Header, Key, Value, ValueExt, OP};
+use alloc::vec::Vec;
+use proptest::arbitrary::Arbitrary;
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::sample::select;
+
+/// The prefixes of the generated keys, so that generated keys often share prefixes. The keys
+/// generated by the `arbitrary` feature start with them as well.
+pub const SHARED_KEY_PREFIXES: &[&[u8]] = crate::SHARED_KEY_PREFIXES;
+
+/// The maximum length of the generated key suffixes and value data
+const MAX_DATA_SIZE: usize = 64;
+
+/// Returns a strategy of user keys (keys without timestamps), which start with one of the
+/// shared prefixes. User keys are never empty, as the timestamp of a key with an empty user
+/// key cannot be parsed.
+pub fn user_key() -> impl Strategy<Value = Vec<u8>> {
+    (
+        select(SHARED_KEY_PREFIXES),
+        vec(any::<u8>(), 0..MAX_DATA_SIZE),
+    )
+        .prop_map(|(prefix, suffix)| {
+            let mut data = prefix.to_vec();
+            data.extend_from_slice(&suffix);
+            data
+        })
+        .prop_filter("user keys must not be empty", |data| !data.is_empty())
+}
+
+/// Returns a strategy of keys with timestamps
+pub fn key_with_timestamp() -> impl Strategy<Value = Key> {
+    (user_key(), any::<u64>()).prop_map(|(data, ts)| Key::from_with_timestamp(data, ts))
+}
+
+/// Returns a strategy of keys with or without timestamps
+pub fn key() -> impl Strategy<Value = Key> {
+    prop_oneof![user_key().prop_map(Key::from), key_with_timestamp()]
+}
+
+/// Returns a strategy of all the combinations of [`OP`] bits
+///
+/// [`OP`]: ../struct.OP.html
+pub fn op() -> impl Strategy<Value = OP> {
+    any::<u8>().prop_map(OP::from_bits_truncate)
+}
+
+/// Returns a strategy of expirations, which are zero (never expire), seconds-sized or any `u64`
+pub fn expires_at() -> impl Strategy<Value = u64> {
+    prop_oneof![Just(0), any::<u32>().prop_map(u64::from), any::<u64>()]
+}
+
+/// Returns a strategy of values
+pub fn value() -> impl Strategy<Value = Value> {
+    (
+        op(),
+        any::<u8>(),
+        expires_at(),
+        vec(any::<u8>(), 0..MAX_DATA_SIZE),
+    )
+        .prop_map(|(op, user_meta, expires_at, data)| {
+            Value::new()
+                .set_meta(op.bits())
+                .set_user_meta(user_meta)
+                .set_expires_at(expires_at)
+                .set_data(Bytes::from(data))
+        })
+}
+
+/// Returns a strategy of encoded values
+pub fn encoded_value() -> impl Strategy<Value = EncodedValue> {
+    value().prop_map(|v| v.to_encoded())
+}
+
+/// Returns a strategy of entries
+pub fn entry() -> impl Strategy<Value = Entry> {
+    (key(), value()).prop_map(|(k, v)| Entry::new_from_kv(k, v))
+}
+
+/// Returns a strategy of value log headers
+pub fn header() -> impl Strategy<Value = Header> {
+    (op(), any::<u8>(), any::<u32>(), any::<u32>(), expires_at()).prop_map(
+        |(op, user_meta, k_len, v_len, expires_at)| {
+            Header::new(op.bits(), user_meta, k_len, v_len, expires_at)
+        },
+    )
+}
+
+macro_rules! impl_arbitrary {
+    ($($ty:ty: $strategy:ident),+ $(,)?) => {
+        $(
+            impl Arbitrary for $ty {
+                type Parameters = ();
+                type Strategy = BoxedStrategy<$ty>;
+
+                #[inline]
+                fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+                    $strategy().boxed()
+                }
+            }
+        )+
+    };
+}
+
+impl_arbitrary! {
+    OP: op,
+    Key: key,
+    Value: value,
+    EncodedValue: encoded_value,
+    Entry: entry,
+    Header: header,
+}
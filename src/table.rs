@@ -0,0 +1,1135 @@
+use crate::block::{Block, BlockBuilder, BlockIterator, DEFAULT_RESTART_INTERVAL};
+use crate::bytes::{BufMut, Bytes};
+use crate::iterator::{seek_to_next_user_key, Iterator, SeekFrom};
+use crate::prefix::{PrefixExtractor, PrefixFilter, PrefixFilterBuilder};
+use crate::{
+    binary_put_uvariant_to_bufmut, checksum, compare_key_in, decode_uvarint, same_key_in, Error,
+    Key, KeyExt, KeyRef, Value, ValueExt, ValueRef, OP, TIMESTAMP_SIZE,
+};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt;
+use core::mem;
+
+/// The magic number at the end of a table ("kvstruct" in ASCII)
+pub const TABLE_MAGIC: u64 = 0x6b76_7374_7275_6374;
+
+/// The size of the table footer
+pub const TABLE_FOOTER_SIZE: usize = 3 * BLOCK_HANDLE_SIZE + 4 + 8;
+
+/// The default target size of data blocks
+pub const DEFAULT_BLOCK_SIZE: usize = 4 * 1024;
+
+/// offset (u64) + size (u32)
+const BLOCK_HANDLE_SIZE: usize = 12;
+
+/// FilterPolicy creates filters from the keys of a table, which are used to skip
+/// tables not containing a key without reading the data blocks.
+///
+/// The keys passed to the policy are user keys (without timestamp).
+pub trait FilterPolicy: Send + Sync {
+    /// Returns the name of the policy, which is stored in the table properties.
+    /// A table is only filtered by the policy with the same name.
+    fn name(&self) -> &str;
+
+    /// Appends a filter summarizing the keys to `dst`.
+    fn create_filter(&self, keys: &[&[u8]], dst: &mut Vec<u8>);
+
+    /// Returns false if the key is definitely not in the filter.
+    fn may_match(&self, key: &[u8], filter: &[u8]) -> bool;
+}
+
+/// Options to build and read tables.
+#[derive(Clone)]
+pub struct TableOptions {
+    block_size: usize,
+    restart_interval: usize,
+    filter_policy: Option<Arc<dyn FilterPolicy>>,
+    prefix_extractor: Option<Arc<dyn PrefixExtractor>>,
+}
+
+impl fmt::Debug for TableOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TableOptions")
+            .field("block_size", &self.block_size)
+            .field("restart_interval", &self.restart_interval)
+            .field(
+                "filter_policy",
+                &self.filter_policy.as_ref().map(|p| p.name()),
+            )
+            .field(
+                "prefix_extractor",
+                &self.prefix_extractor.as_ref().map(|p| p.name()),
+            )
+            .finish()
+    }
+}
+
+impl Default for TableOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TableOptions {
+    /// Returns the default options, with [`DEFAULT_BLOCK_SIZE`], [`DEFAULT_RESTART_INTERVAL`]
+    /// and no filter.
+    ///
+    /// [`DEFAULT_BLOCK_SIZE`]: constant.DEFAULT_BLOCK_SIZE.html
+    /// [`DEFAULT_RESTART_INTERVAL`]: ../block/constant.DEFAULT_RESTART_INTERVAL.html
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            block_size: DEFAULT_BLOCK_SIZE,
+            restart_interval: DEFAULT_RESTART_INTERVAL,
+            filter_policy: None,
+            prefix_extractor: None,
+        }
+    }
+
+    /// Set the target size of data blocks
+    #[inline]
+    pub fn set_block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    /// Get the target size of data blocks
+    #[inline]
+    pub fn get_block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Set the number of entries between two restart points in data blocks
+    #[inline]
+    pub fn set_restart_interval(mut self, interval: usize) -> Self {
+        self.restart_interval = interval;
+        self
+    }
+
+    /// Get the number of entries between two restart points in data blocks
+    #[inline]
+    pub fn get_restart_interval(&self) -> usize {
+        self.restart_interval
+    }
+
+    /// Set the filter policy
+    #[inline]
+    pub fn set_filter_policy(mut self, policy: Arc<dyn FilterPolicy>) -> Self {
+        self.filter_policy = Some(policy);
+        self
+    }
+
+    /// Get the filter policy
+    #[inline]
+    pub fn get_filter_policy(&self) -> Option<&Arc<dyn FilterPolicy>> {
+        self.filter_policy.as_ref()
+    }
+
+    /// Set the prefix extractor, the table records the extracted prefixes in a prefix filter.
+    #[inline]
+    pub fn set_prefix_extractor(mut self, extractor: Arc<dyn PrefixExtractor>) -> Self {
+        self.prefix_extractor = Some(extractor);
+        self
+    }
+
+    /// Get the prefix extractor
+    #[inline]
+    pub fn get_prefix_extractor(&self) -> Option<&Arc<dyn PrefixExtractor>> {
+        self.prefix_extractor.as_ref()
+    }
+}
+
+/// BlockHandle points to a block in a table.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct BlockHandle {
+    offset: u64,
+    size: u32,
+}
+
+impl BlockHandle {
+    /// Returns a BlockHandle
+    #[inline]
+    pub const fn new(offset: u64, size: u32) -> Self {
+        Self { offset, size }
+    }
+
+    /// Get the offset of the block
+    #[inline]
+    pub const fn get_offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Get the size of the block (including the trailer)
+    #[inline]
+    pub const fn get_size(&self) -> u32 {
+        self.size
+    }
+
+    /// Encodes the handle as two uvarints to the buffer.
+    #[inline]
+    pub fn encode_to(&self, buf: &mut Vec<u8>) {
+        binary_put_uvariant_to_bufmut(buf, self.offset);
+        binary_put_uvariant_to_bufmut(buf, self.size as u64);
+    }
+
+    /// Decodes a handle encoded by [`encode_to`], returns the handle and the number of bytes read.
+    ///
+    /// [`encode_to`]: #method.encode_to
+    #[inline]
+    pub fn decode(buf: &[u8]) -> Result<(Self, usize), Error> {
+        let (offset, n1) = decode_uvarint(buf)?;
+        let (size, n2) = decode_uvarint(&buf[n1..])?;
+        let size = u32::try_from(size)
+            .map_err(|_| Error::corruption("table: block size overflows u32"))?;
+        Ok((Self { offset, size }, n1 + n2))
+    }
+
+    #[inline]
+    fn encode_fixed(&self, buf: &mut Vec<u8>) {
+        buf.put_u64_le(self.offset);
+        buf.put_u32_le(self.size);
+    }
+
+    #[inline]
+    fn decode_fixed(buf: &[u8]) -> Self {
+        let mut offset = [0u8; 8];
+        offset.copy_from_slice(&buf[..8]);
+        let mut size = [0u8; 4];
+        size.copy_from_slice(&buf[8..BLOCK_HANDLE_SIZE]);
+        Self {
+            offset: u64::from_le_bytes(offset),
+            size: u32::from_le_bytes(size),
+        }
+    }
+}
+
+/// The statistics of a table, which are stored in the properties block.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct TableProperties {
+    smallest: Key,
+    largest: Key,
+    max_version: u64,
+    num_entries: u64,
+    num_deletions: u64,
+    num_data_blocks: u64,
+    filter_policy: String,
+    prefix_extractor: String,
+    prefix_filter: BlockHandle,
+}
+
+impl TableProperties {
+    /// Returns the smallest key in the table
+    #[inline]
+    pub fn smallest(&self) -> &Key {
+        &self.smallest
+    }
+
+    /// Returns the largest key in the table
+    #[inline]
+    pub fn largest(&self) -> &Key {
+        &self.largest
+    }
+
+    /// Returns the max timestamp of the keys in the table
+    #[inline]
+    pub fn max_version(&self) -> u64 {
+        self.max_version
+    }
+
+    /// Returns the number of entries in the table
+    #[inline]
+    pub fn num_entries(&self) -> u64 {
+        self.num_entries
+    }
+
+    /// Returns the number of tombstones (deleted entries) in the table
+    #[inline]
+    pub fn num_deletions(&self) -> u64 {
+        self.num_deletions
+    }
+
+    /// Returns the number of data blocks in the table
+    #[inline]
+    pub fn num_data_blocks(&self) -> u64 {
+        self.num_data_blocks
+    }
+
+    /// Returns the name of the filter policy, empty if the table has no filter
+    #[inline]
+    pub fn filter_policy(&self) -> &str {
+        self.filter_policy.as_str()
+    }
+
+    /// Returns the name of the prefix extractor, empty if the table has no prefix filter
+    #[inline]
+    pub fn prefix_extractor(&self) -> &str {
+        self.prefix_extractor.as_str()
+    }
+
+    fn encode_to(&self, buf: &mut Vec<u8>) {
+        binary_put_uvariant_to_bufmut(buf, self.max_version);
+        binary_put_uvariant_to_bufmut(buf, self.num_entries);
+        binary_put_uvariant_to_bufmut(buf, self.num_deletions);
+        binary_put_uvariant_to_bufmut(buf, self.num_data_blocks);
+        for field in [
+            self.smallest.as_slice(),
+            self.largest.as_slice(),
+            self.filter_policy.as_bytes(),
+        ] {
+            binary_put_uvariant_to_bufmut(buf, field.len() as u64);
+            buf.extend_from_slice(field);
+        }
+
+        // the optional fields
+        if !self.prefix_extractor.is_empty() {
+            binary_put_uvariant_to_bufmut(buf, self.prefix_extractor.len() as u64);
+            buf.extend_from_slice(self.prefix_extractor.as_bytes());
+            self.prefix_filter.encode_to(buf);
+        }
+    }
+
+    fn decode(mut buf: &[u8]) -> Result<Self, Error> {
+        let next_u64 = |buf: &mut &[u8]| {
+            decode_uvarint(buf).map(|(x, n)| {
+                *buf = &buf[n..];
+                x
+            })
+        };
+        let max_version = next_u64(&mut buf)?;
+        let num_entries = next_u64(&mut buf)?;
+        let num_deletions = next_u64(&mut buf)?;
+        let num_data_blocks = next_u64(&mut buf)?;
+        let mut fields = [&[][..]; 4];
+        for (idx, field) in fields.iter_mut().enumerate() {
+            // the prefix extractor and prefix filter are optional
+            if idx == 3 && buf.is_empty() {
+                break;
+            }
+            let len = next_u64(&mut buf)?;
+            if len > buf.len() as u64 {
+                return Err(Error::corruption("table: invalid properties block"));
+            }
+            let (data, rest) = buf.split_at(len as usize);
+            *field = data;
+            buf = rest;
+        }
+        let prefix_filter = if fields[3].is_empty() {
+            BlockHandle::default()
+        } else {
+            BlockHandle::decode(buf)?.0
+        };
+
+        Ok(Self {
+            smallest: Key::copy_from_slice(fields[0]),
+            largest: Key::copy_from_slice(fields[1]),
+            max_version,
+            num_entries,
+            num_deletions,
+            num_data_blocks,
+            filter_policy: String::from_utf8_lossy(fields[2]).into_owned(),
+            prefix_extractor: String::from_utf8_lossy(fields[3]).into_owned(),
+            prefix_filter,
+        })
+    }
+}
+
+/// TableBuilder builds a sorted string table (SST) in memory.
+///
+/// ```text
+/// +--------------+-----+--------------+--------------+---------------------+------------+-------------+--------+
+/// | data block 0 | ... | data block N | filter block | prefix filter block | properties | index block | footer |
+/// +--------------+-----+--------------+--------------+---------------------+------------+-------------+--------+
+///
+/// footer: | index handle | filter handle | properties handle | checksum u32 | magic u64 |
+/// ```
+///
+/// Data blocks and the index block are [`Block`]s. The index block maps a separator key,
+/// which is >= the keys of a data block and < the keys of the next one, to the [`BlockHandle`]
+/// of the data block. The filter, prefix filter and properties blocks are followed by a checksum.
+/// The filter block is empty if there is no [`FilterPolicy`], and the prefix filter block only
+/// exists with a [`PrefixExtractor`], whose handle is stored in the properties. Each handle in
+/// the footer is a u64 offset and a u32 size, the checksum is the CRC-32 (Castagnoli) of the
+/// handles, and all of them are little-endian.
+///
+/// [`Block`]: ../block/struct.Block.html
+/// [`BlockHandle`]: struct.BlockHandle.html
+/// [`FilterPolicy`]: trait.FilterPolicy.html
+/// [`PrefixExtractor`]: ../prefix/trait.PrefixExtractor.html
+pub struct TableBuilder {
+    opts: TableOptions,
+    buf: Vec<u8>,
+    data_block: BlockBuilder,
+    index_block: BlockBuilder,
+    /// the handle of the last data block, waiting for the first key of the next block.
+    pending_handle: Option<BlockHandle>,
+    last_key: Vec<u8>,
+    /// the user keys for the filter, stored as a flat buffer and the end offsets.
+    filter_keys: Vec<u8>,
+    filter_key_ends: Vec<usize>,
+    prefix_filter: Option<PrefixFilterBuilder>,
+    props: TableProperties,
+    scratch: Vec<u8>,
+}
+
+impl TableBuilder {
+    /// Returns a TableBuilder
+    pub fn new(opts: TableOptions) -> Self {
+        let data_block = BlockBuilder::new().set_restart_interval(opts.restart_interval);
+        let prefix_filter = opts.prefix_extractor.clone().map(PrefixFilterBuilder::new);
+        Self {
+            opts,
+            buf: Vec::new(),
+            data_block,
+            index_block: BlockBuilder::new().set_restart_interval(1),
+            pending_handle: None,
+            last_key: Vec::new(),
+            filter_keys: Vec::new(),
+            filter_key_ends: Vec::new(),
+            prefix_filter,
+            props: TableProperties::default(),
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Appends a key-value pair to the table.
+    ///
+    /// The keys must be added in increasing order, see [`compare_key`].
+    ///
+    /// [`compare_key`]: ../fn.compare_key.html
+    pub fn add(&mut self, key: impl KeyExt, val: impl ValueExt) {
+        let key = key.as_bytes();
+        let first = self.props.num_entries == 0;
+        debug_assert!(
+            first || compare_key_in(&self.last_key, key) == Ordering::Less,
+            "kvstructs: keys must be added to the table in increasing order"
+        );
+
+        if let Some(handle) = self.pending_handle.take() {
+            self.add_index_entry(separator(&self.last_key, key), handle);
+        }
+
+        if self.opts.filter_policy.is_some() && (first || !same_key_in(&self.last_key, key)) {
+            self.filter_keys.extend_from_slice(key.parse_key());
+            self.filter_key_ends.push(self.filter_keys.len());
+        }
+        if let Some(prefix_filter) = &mut self.prefix_filter {
+            prefix_filter.add(key);
+        }
+
+        if first {
+            self.props.smallest = Key::copy_from_slice(key);
+        }
+        self.props.max_version = self.props.max_version.max(key.parse_timestamp());
+        self.props.num_entries += 1;
+        if OP::from_bits_truncate(val.get_meta()).contains(OP::BIT_DELETE) {
+            self.props.num_deletions += 1;
+        }
+
+        self.scratch.clear();
+        val.encode_to(&mut self.scratch);
+        self.data_block.add_raw(key, &self.scratch);
+        self.last_key.clear();
+        self.last_key.extend_from_slice(key);
+
+        if self.data_block.estimated_size() >= self.opts.block_size {
+            self.flush();
+        }
+    }
+
+    /// Returns the number of entries added
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.props.num_entries as usize
+    }
+
+    /// Returns true if no entry has been added
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.props.num_entries == 0
+    }
+
+    /// Returns the size of the table if it is finished now, not including the filter.
+    #[inline]
+    pub fn estimated_size(&self) -> usize {
+        self.buf.len()
+            + self.data_block.estimated_size()
+            + self.index_block.estimated_size()
+            + TABLE_FOOTER_SIZE
+    }
+
+    /// Finishes the table and returns the encoded bytes.
+    pub fn finish(mut self) -> Bytes {
+        self.props.largest = Key::copy_from_slice(&self.last_key);
+        self.flush();
+        if let Some(handle) = self.pending_handle.take() {
+            let last_key = mem::take(&mut self.last_key);
+            self.add_index_entry(last_key, handle);
+        }
+
+        let mut filter = Vec::new();
+        if let Some(policy) = &self.opts.filter_policy {
+            let mut start = 0;
+            let keys = self
+                .filter_key_ends
+                .iter()
+                .map(|end| {
+                    let key = &self.filter_keys[start..*end];
+                    start = *end;
+                    key
+                })
+                .collect::<Vec<_>>();
+            policy.create_filter(&keys, &mut filter);
+            self.props.filter_policy = String::from(policy.name());
+        }
+        let filter_handle = write_raw_block(&mut self.buf, &filter);
+
+        if let Some(mut prefix_filter) = self.prefix_filter.take() {
+            let prefix_filter = prefix_filter.finish();
+            self.props.prefix_extractor = String::from(prefix_filter.extractor().name());
+            self.props.prefix_filter = write_raw_block(&mut self.buf, prefix_filter.as_bytes());
+        }
+
+        let mut props = Vec::new();
+        self.props.encode_to(&mut props);
+        let props_handle = write_raw_block(&mut self.buf, &props);
+
+        let index = self.index_block.finish();
+        let index_handle = BlockHandle::new(self.buf.len() as u64, index.len() as u32);
+        self.buf.extend_from_slice(&index);
+
+        let footer_offset = self.buf.len();
+        index_handle.encode_fixed(&mut self.buf);
+        filter_handle.encode_fixed(&mut self.buf);
+        props_handle.encode_fixed(&mut self.buf);
+        let crc = checksum(&self.buf[footer_offset..]);
+        self.buf.put_u32_le(crc);
+        self.buf.put_u64_le(TABLE_MAGIC);
+        Bytes::from(self.buf)
+    }
+
+    /// Finishes the current data block
+    fn flush(&mut self) {
+        if self.data_block.is_empty() {
+            return;
+        }
+        let block = self.data_block.finish();
+        self.pending_handle = Some(BlockHandle::new(self.buf.len() as u64, block.len() as u32));
+        self.buf.extend_from_slice(&block);
+        self.props.num_data_blocks += 1;
+    }
+
+    fn add_index_entry(&mut self, key: Vec<u8>, handle: BlockHandle) {
+        let mut data = Vec::new();
+        handle.encode_to(&mut data);
+        let val = Value::from(data);
+        self.scratch.clear();
+        val.encode_to(&mut self.scratch);
+        self.index_block.add_raw(&key, &self.scratch);
+    }
+}
+
+/// Returns a key k, which is `last <= k < next`, and is shorter than `last` if possible.
+fn separator(last: &[u8], next: &[u8]) -> Vec<u8> {
+    if last.len() >= TIMESTAMP_SIZE && next.len() >= TIMESTAMP_SIZE {
+        let (lu, nu) = (last.parse_key(), next.parse_key());
+        let n = lu.iter().zip(nu).take_while(|(a, b)| a == b).count();
+        if n < lu.len() && n < nu.len() && lu[n] < 0xff && lu[n] + 1 < nu[n] {
+            let mut sep = lu[..=n].to_vec();
+            sep[n] += 1;
+            // timestamp 0
+            sep.extend_from_slice(&u64::MAX.to_be_bytes());
+            return sep;
+        }
+    }
+    last.to_vec()
+}
+
+/// Appends data with the checksum, returns the handle of the block.
+#[inline]
+fn write_raw_block(buf: &mut Vec<u8>, data: &[u8]) -> BlockHandle {
+    let handle = BlockHandle::new(buf.len() as u64, (data.len() + 4) as u32);
+    buf.extend_from_slice(data);
+    buf.put_u32_le(checksum(data));
+    handle
+}
+
+/// Returns the block pointed by the handle, the block is not verified.
+#[inline]
+fn block_of(data: &Bytes, limit: usize, handle: BlockHandle) -> Result<Bytes, Error> {
+    let start = handle.offset;
+    match start.checked_add(handle.size as u64) {
+        Some(end) if end <= limit as u64 => Ok(data.slice(start as usize..end as usize)),
+        _ => Err(Error::corruption("table: block handle out of range")),
+    }
+}
+
+/// Returns the data of a block written by `write_raw_block`, after verifying the checksum.
+#[inline]
+fn read_raw_block(data: &Bytes, limit: usize, handle: BlockHandle) -> Result<Bytes, Error> {
+    let block = block_of(data, limit, handle)?;
+    if block.len() < 4 {
+        return Err(Error::corruption("table: block too short"));
+    }
+    let (payload, crc) = block.split_at(block.len() - 4);
+    let expected = u32::from_le_bytes([crc[0], crc[1], crc[2], crc[3]]);
+    let actual = checksum(payload);
+    if expected != actual {
+        return Err(Error::ChecksumMismatch { expected, actual });
+    }
+    Ok(block.slice(..payload.len()))
+}
+
+/// Table is an immutable sorted string table built by [`TableBuilder`].
+///
+/// Cloning a table is cheap, the underlying data is shared.
+///
+/// [`TableBuilder`]: struct.TableBuilder.html
+#[derive(Clone)]
+pub struct Table {
+    data: Bytes,
+    /// the end of the data blocks
+    data_end: usize,
+    index: Block,
+    filter: Bytes,
+    filter_policy: Option<Arc<dyn FilterPolicy>>,
+    prefix_filter: Option<PrefixFilter>,
+    props: TableProperties,
+}
+
+impl fmt::Debug for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Table")
+            .field("size", &self.data.len())
+            .field("properties", &self.props)
+            .finish()
+    }
+}
+
+impl Table {
+    cfg_std! {
+        /// Reads the whole table file, and opens it.
+        pub fn open(path: impl AsRef<std::path::Path>, opts: &TableOptions) -> Result<Self, Error> {
+            let data = std::fs::read(path)?;
+            Self::from_bytes(Bytes::from(data), opts)
+        }
+    }
+
+    /// Maps the table file into memory, and opens it.
+    ///
+    /// The values read from the table share the mapping instead of copying, which is kept
+    /// alive until the table and all of them are dropped.
+    ///
+    /// # Safety
+    /// The file must not be modified or truncated while it is mapped, see [`MmapFile::open`].
+    ///
+    /// [`MmapFile::open`]: ../mmap/struct.MmapFile.html#method.open
+    #[cfg(feature = "memmap2")]
+    pub unsafe fn open_mmap(
+        path: impl AsRef<std::path::Path>,
+        opts: &TableOptions,
+    ) -> Result<Self, Error> {
+        let file = crate::mmap::MmapFile::open(path)?;
+        Self::from_bytes(file.to_bytes(), opts)
+    }
+
+    /// Opens a table from the encoded bytes.
+    ///
+    /// The filter (prefix filter) is only used if the policy (prefix extractor) of the options
+    /// has the same name with the one which built the table.
+    pub fn from_bytes(data: Bytes, opts: &TableOptions) -> Result<Self, Error> {
+        if data.len() < TABLE_FOOTER_SIZE {
+            return Err(Error::corruption("table: too short"));
+        }
+
+        let footer_offset = data.len() - TABLE_FOOTER_SIZE;
+        let footer = &data[footer_offset..];
+        let mut magic = [0u8; 8];
+        magic.copy_from_slice(&footer[TABLE_FOOTER_SIZE - 8..]);
+        if u64::from_le_bytes(magic) != TABLE_MAGIC {
+            return Err(Error::corruption("table: bad magic number"));
+        }
+
+        let handles_size = 3 * BLOCK_HANDLE_SIZE;
+        let crc = &footer[handles_size..handles_size + 4];
+        let expected = u32::from_le_bytes([crc[0], crc[1], crc[2], crc[3]]);
+        let actual = checksum(&footer[..handles_size]);
+        if expected != actual {
+            return Err(Error::ChecksumMismatch { expected, actual });
+        }
+
+        let index_handle = BlockHandle::decode_fixed(footer);
+        let filter_handle = BlockHandle::decode_fixed(&footer[BLOCK_HANDLE_SIZE..]);
+        let props_handle = BlockHandle::decode_fixed(&footer[2 * BLOCK_HANDLE_SIZE..]);
+
+        let index = Block::new(block_of(&data, footer_offset, index_handle)?)?;
+        let filter = read_raw_block(&data, footer_offset, filter_handle)?;
+        let props = TableProperties::decode(&read_raw_block(&data, footer_offset, props_handle)?)?;
+        let filter_policy = opts
+            .filter_policy
+            .as_ref()
+            .filter(|p| !filter.is_empty() && p.name() == props.filter_policy)
+            .cloned();
+        let prefix_filter = match &opts.prefix_extractor {
+            Some(p) if p.name() == props.prefix_extractor => Some(PrefixFilter::from_bytes(
+                p.clone(),
+                read_raw_block(&data, footer_offset, props.prefix_filter)?,
+            )),
+            _ => None,
+        };
+
+        Ok(Self {
+            data_end: filter_handle.offset as usize,
+            data,
+            index,
+            filter,
+            filter_policy,
+            prefix_filter,
+            props,
+        })
+    }
+
+    /// Returns the size of the table in bytes
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns the properties of the table
+    #[inline]
+    pub fn properties(&self) -> &TableProperties {
+        &self.props
+    }
+
+    /// Returns false if the table definitely does not contain the user key (without timestamp).
+    ///
+    /// Always returns true if the table has no filter.
+    #[inline]
+    pub fn may_contain(&self, user_key: &[u8]) -> bool {
+        match &self.filter_policy {
+            Some(policy) => policy.may_match(user_key, &self.filter),
+            None => true,
+        }
+    }
+
+    /// Returns false if the table definitely does not contain any key beginning with the prefix
+    /// (without timestamp), which can be checked before constructing an iterator.
+    ///
+    /// Always returns true if the table has no prefix filter, or the prefix is not in the domain
+    /// of the prefix extractor.
+    #[inline]
+    pub fn may_contain_prefix(&self, prefix: &[u8]) -> bool {
+        match &self.prefix_filter {
+            Some(filter) => filter.may_contain_prefix(prefix),
+            None => true,
+        }
+    }
+
+    /// Returns the newest version of the key whose timestamp is not newer than the timestamp
+    /// of the given key.
+    pub fn get(&self, key: impl KeyExt) -> Result<Option<(Key, Value)>, Error> {
+        if !self.may_contain(key.parse_key()) {
+            return Ok(None);
+        }
+
+        let mut iter = self.iter();
+        iter.seek(key.as_key_ref());
+        if let Some(e) = iter.err.take() {
+            return Err(e);
+        }
+        Ok(iter.entry().filter(|(k, _)| k.same_key(key.as_key_ref())))
+    }
+
+    /// Returns an iterator over the table, call [`rewind`] or [`seek`] to position it.
+    ///
+    /// [`rewind`]: ../iterator/trait.Iterator.html#tymethod.rewind
+    /// [`seek`]: ../iterator/trait.Iterator.html#tymethod.seek
+    #[inline]
+    pub fn iter(&self) -> TableIterator {
+        TableIterator {
+            index: self.index.iter(),
+            table: self.clone(),
+            data: None,
+            scratch: Vec::new(),
+            err: None,
+        }
+    }
+}
+
+/// TableIterator is an [`Iterator`] over a [`Table`].
+///
+/// Errors of the blocks, including checksum mismatches, are reported by [`error`].
+///
+/// [`Iterator`]: ../iterator/trait.Iterator.html
+/// [`Table`]: struct.Table.html
+/// [`error`]: ../iterator/trait.Iterator.html#method.error
+#[derive(Debug)]
+pub struct TableIterator {
+    table: Table,
+    index: BlockIterator,
+    data: Option<BlockIterator>,
+    /// the target of advance_to_next_user_key
+    scratch: Vec<u8>,
+    err: Option<Error>,
+}
+
+impl TableIterator {
+    /// Loads the data block pointed by the current index entry into the data iterator, which
+    /// is reused across blocks. Returns the data iterator, or `None` if there is no such block.
+    fn load_block(&mut self) -> Option<&mut BlockIterator> {
+        let block = match self.index.value_ref() {
+            Some(val) => BlockHandle::decode(val.parse_value())
+                .and_then(|(h, _)| block_of(&self.table.data, self.table.data_end, h))
+                .and_then(Block::new),
+            None => {
+                self.err = self.index.take_error();
+                if let Some(data) = &mut self.data {
+                    data.invalidate(None);
+                }
+                return None;
+            }
+        };
+
+        match block {
+            Ok(block) => {
+                match &mut self.data {
+                    Some(data) => data.reset(block),
+                    None => self.data = Some(block.iter()),
+                }
+                self.data.as_mut()
+            }
+            Err(e) => {
+                self.err = Some(e);
+                if let Some(data) = &mut self.data {
+                    data.invalidate(None);
+                }
+                None
+            }
+        }
+    }
+
+    /// Moves to the first entry of the next non-empty block if the current block is exhausted.
+    fn skip_empty_blocks(&mut self) {
+        while self.err.is_none() {
+            match &mut self.data {
+                Some(data) if data.valid() => return,
+                Some(data) if data.error().is_some() => {
+                    self.err = data.take_error();
+                    return;
+                }
+                Some(_) if self.index.valid() => {}
+                _ => return,
+            }
+
+            self.index.next();
+            if let Some(data) = self.load_block() {
+                data.rewind();
+            }
+        }
+    }
+}
+
+impl Iterator<Key, Value> for TableIterator {
+    #[inline]
+    fn next(&mut self) {
+        if let Some(data) = &mut self.data {
+            data.next();
+            self.skip_empty_blocks();
+        }
+    }
+
+    fn rewind(&mut self) {
+        self.err = None;
+        self.index.rewind();
+        if let Some(data) = self.load_block() {
+            data.rewind();
+        }
+        self.skip_empty_blocks();
+    }
+
+    fn seek<Q: KeyExt>(&mut self, key: Q) {
+        self.err = None;
+        self.index.seek(key.as_key_ref());
+        if let Some(data) = self.load_block() {
+            data.seek(key);
+        }
+        self.skip_empty_blocks();
+    }
+
+    fn seek_from<Q: KeyExt>(&mut self, key: Q, from: SeekFrom) {
+        let target = key.as_bytes();
+        if let SeekFrom::Origin = from {
+            return self.seek(target);
+        }
+        let data = match &mut self.data {
+            Some(data) if self.err.is_none() => data,
+            _ => return,
+        };
+        match data.key_ref() {
+            Some(cur) if compare_key_in(cur.as_slice(), target) == Ordering::Less => {}
+            _ => return,
+        }
+
+        // the separator of the current block is >= all of its keys, so the target is in this
+        // block (or at the start of the next one) if it is not greater than the separator.
+        let in_block = self
+            .index
+            .key_ref()
+            .is_some_and(|sep| compare_key_in(sep.as_slice(), target) != Ordering::Less);
+        if in_block {
+            data.seek_from(target, SeekFrom::Current);
+        } else {
+            self.index.seek_from(target, SeekFrom::Current);
+            if let Some(data) = self.load_block() {
+                data.seek(target);
+            }
+        }
+        self.skip_empty_blocks();
+    }
+
+    #[inline]
+    fn advance_to_next_user_key(&mut self) {
+        let mut scratch = mem::take(&mut self.scratch);
+        seek_to_next_user_key(self, &mut scratch);
+        self.scratch = scratch;
+    }
+
+    #[inline]
+    fn entry(&self) -> Option<(Key, Value)> {
+        self.data.as_ref().and_then(|data| data.entry())
+    }
+
+    #[inline]
+    fn key(&self) -> Option<Key> {
+        self.data.as_ref().and_then(|data| data.key())
+    }
+
+    #[inline]
+    fn val(&self) -> Option<Value> {
+        self.data.as_ref().and_then(|data| data.val())
+    }
+
+    #[inline]
+    fn key_ref(&self) -> Option<KeyRef<'_>> {
+        self.data.as_ref().and_then(|data| data.key_ref())
+    }
+
+    #[inline]
+    fn value_ref(&self) -> Option<ValueRef<'_>> {
+        self.data.as_ref().and_then(|data| data.value_ref())
+    }
+
+    #[inline]
+    fn valid(&self) -> bool {
+        self.err.is_none() && self.data.as_ref().is_some_and(|data| data.valid())
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.table.props.num_entries as usize))
+    }
+
+    #[inline]
+    fn error(&self) -> Option<&Error> {
+        self.err.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::format;
+
+    /// A filter stores all the keys, for testing.
+    struct ExactFilter;
+
+    impl FilterPolicy for ExactFilter {
+        fn name(&self) -> &str {
+            "exact"
+        }
+
+        fn create_filter(&self, keys: &[&[u8]], dst: &mut Vec<u8>) {
+            for key in keys {
+                dst.push(key.len() as u8);
+                dst.extend_from_slice(key);
+            }
+        }
+
+        fn may_match(&self, key: &[u8], mut filter: &[u8]) -> bool {
+            while let Some((len, rest)) = filter.split_first() {
+                let (k, rest) = rest.split_at(*len as usize);
+                if k == key {
+                    return true;
+                }
+                filter = rest;
+            }
+            false
+        }
+    }
+
+    fn build(n: usize, opts: TableOptions) -> Bytes {
+        let mut builder = TableBuilder::new(opts);
+        for i in 0..n {
+            // two versions for each key
+            for ts in [2, 1] {
+                let meta = if ts == 2 && i % 10 == 0 {
+                    OP::BIT_DELETE.bits()
+                } else {
+                    0
+                };
+                let val = Value::from(format!("val{}@{}", i, ts)).set_meta(meta);
+                builder.add(Key::from(format!("key{:04}", i)).with_timestamp(ts), val);
+            }
+        }
+        builder.finish()
+    }
+
+    #[test]
+    fn test_table() {
+        let opts = TableOptions::new()
+            .set_block_size(256)
+            .set_filter_policy(Arc::new(ExactFilter))
+            .set_prefix_extractor(Arc::new(crate::prefix::FixedPrefix::new(6)));
+        let table = Table::from_bytes(build(100, opts.clone()), &opts).unwrap();
+
+        let props = table.properties();
+        assert_eq!(props.num_entries(), 200);
+        assert_eq!(props.num_deletions(), 10);
+        assert_eq!(props.max_version(), 2);
+        assert_eq!(props.smallest(), &Key::from("key0000").with_timestamp(2));
+        assert_eq!(props.largest(), &Key::from("key0099").with_timestamp(1));
+        assert!(props.num_data_blocks() > 1);
+        assert_eq!(props.filter_policy(), "exact");
+        assert_eq!(props.prefix_extractor(), "kvstructs.FixedPrefix:6");
+        assert!(table.may_contain_prefix(b"key004"));
+        assert!(table.may_contain_prefix(b"key0042"));
+        assert!(!table.may_contain_prefix(b"key019"));
+        // not in the domain of the extractor
+        assert!(table.may_contain_prefix(b"key01"));
+
+        let mut iter = table.iter();
+        iter.rewind();
+        let mut n = 0;
+        while iter.valid() {
+            let (k, v) = iter.entry().unwrap();
+            let ts = 2 - (n % 2) as u64;
+            assert_eq!(k, Key::from(format!("key{:04}", n / 2)).with_timestamp(ts));
+            assert_eq!(v.parse_value(), format!("val{}@{}", n / 2, ts).as_bytes());
+            assert_eq!(v.get_version(), ts);
+            iter.next();
+            n += 1;
+        }
+        assert_eq!(n, 200);
+        assert!(iter.status().is_ok());
+
+        let (k, v) = table
+            .get(Key::from("key0042").with_timestamp(5))
+            .unwrap()
+            .unwrap();
+        assert_eq!(k.parse_timestamp(), 2);
+        assert_eq!(v.parse_value(), b"val42@2");
+        let (_, v) = table
+            .get(Key::from("key0042").with_timestamp(1))
+            .unwrap()
+            .unwrap();
+        assert_eq!(v.parse_value(), b"val42@1");
+        assert!(table
+            .get(Key::from("key0042").with_timestamp(0))
+            .unwrap()
+            .is_none());
+        assert!(!table.may_contain(b"key0042a"));
+        assert!(table
+            .get(Key::from("key0042a").with_timestamp(5))
+            .unwrap()
+            .is_none());
+
+        // seek between the keys of two blocks
+        for i in 0..99 {
+            iter.seek(Key::from(format!("key{:04}a", i)).with_timestamp(2));
+            assert_eq!(
+                iter.key().unwrap().parse_key(),
+                format!("key{:04}", i + 1).as_bytes()
+            );
+        }
+        iter.seek(Key::from("key9999").with_timestamp(2));
+        assert!(!iter.valid());
+        assert!(iter.status().is_ok());
+
+        // without the filter policy, the filter is ignored.
+        let table = Table::from_bytes(table.data.clone(), &TableOptions::new()).unwrap();
+        assert!(table.may_contain(b"key0042a"));
+        assert!(table.may_contain_prefix(b"key019"));
+        assert!(table
+            .get(Key::from("key0042").with_timestamp(2))
+            .unwrap()
+            .is_some());
+
+        let empty =
+            Table::from_bytes(TableBuilder::new(TableOptions::new()).finish(), &opts).unwrap();
+        let mut iter = empty.iter();
+        iter.rewind();
+        assert!(!iter.valid());
+        assert!(iter.status().is_ok());
+    }
+
+    #[test]
+    fn test_table_seek_from() {
+        let opts = TableOptions::new().set_block_size(256);
+        let table = Table::from_bytes(build(100, opts.clone()), &opts).unwrap();
+        assert!(table.properties().num_data_blocks() > 2);
+        let key = |i: usize, ts: u64| Key::from(format!("key{:04}", i)).with_timestamp(ts);
+
+        let mut iter = table.iter();
+        iter.rewind();
+        for i in [1, 2, 10, 11, 50, 99] {
+            iter.seek_from(key(i, 1), SeekFrom::Current);
+            assert_eq!(iter.key().unwrap(), key(i, 1));
+        }
+        iter.seek_from(key(10, 2), SeekFrom::Current);
+        assert_eq!(iter.key().unwrap(), key(99, 1));
+        iter.seek_from(key(10, 2), SeekFrom::Origin);
+        assert_eq!(iter.key().unwrap(), key(10, 2));
+        iter.seek_from(Key::from("key0042a").with_timestamp(2), SeekFrom::Current);
+        assert_eq!(iter.key().unwrap(), key(43, 2));
+        iter.seek_from(Key::from("key9999").with_timestamp(2), SeekFrom::Current);
+        assert!(!iter.valid());
+        assert!(iter.status().is_ok());
+
+        iter.rewind();
+        for i in 0..100 {
+            assert_eq!(iter.key().unwrap(), key(i, 2));
+            iter.advance_to_next_user_key();
+        }
+        assert!(!iter.valid());
+        assert!(iter.status().is_ok());
+    }
+
+    #[test]
+    fn test_table_corruption() {
+        let opts = TableOptions::new().set_block_size(256);
+        let data = build(100, opts.clone()).to_vec();
+
+        let mut bad_magic = data.clone();
+        *bad_magic.last_mut().unwrap() ^= 0xff;
+        assert!(matches!(
+            Table::from_bytes(Bytes::from(bad_magic), &opts),
+            Err(Error::Corruption(_))
+        ));
+
+        // corrupt the second data block
+        let mut corrupted = data;
+        corrupted[300] ^= 0xff;
+        let table = Table::from_bytes(Bytes::from(corrupted), &opts).unwrap();
+        let mut iter = table.iter();
+        iter.rewind();
+        let mut n = 0;
+        while iter.valid() {
+            n += 1;
+            iter.next();
+        }
+        assert!(n > 0 && n < 200);
+        assert!(matches!(iter.status(), Err(Error::ChecksumMismatch { .. })));
+    }
+}
@@ -1,8 +1,8 @@
 use crate::raw_value_pointer::RawValuePointer;
 use crate::value_enc::EncodedValue;
 use crate::{
-    binary_uvarint, binary_uvarint_allocate, put_binary_uvariant_to_vec, EXPIRATION_OFFSET,
-    META_OFFSET, USER_META_OFFSET,
+    binary_put_uvariant_to_bufmut, binary_uvarint, EXPIRATION_OFFSET, META_OFFSET, OP,
+    USER_META_OFFSET,
 };
 use alloc::borrow::Cow;
 use alloc::boxed::Box;
@@ -10,13 +10,9 @@ use alloc::rc::Rc;
 use alloc::string::{String, ToString};
 use alloc::sync::Arc;
 use alloc::vec::Vec;
-use bytes::{Buf, BufMut, Bytes, BytesMut};
-use core::mem;
+use bytes::{BufMut, Bytes, BytesMut};
 use core::slice::from_raw_parts;
 
-/// Max size of a value information. 1 for meta, 1 for user meta, 8 for expires_at
-const MAX_VALUE_INFO_SIZE: usize = mem::size_of::<u8>() * 2 + mem::size_of::<u64>();
-
 /// Value represents the value info that can be associated with a key, but also the internal
 /// Meta field. The data in the Value is not mutable.
 ///
@@ -25,7 +21,7 @@ const MAX_VALUE_INFO_SIZE: usize = mem::size_of::<u8>() * 2 + mem::size_of::<u64
 /// **Note:**
 /// 1. `version` field will not be encoded, it is a helper field.
 /// 2. `expiration` field will be encoded as uvarient, which means after encoded, the size of
-/// this field is less or equal to 8 bytes.
+///    this field is less or equal to 8 bytes.
 ///
 /// ```text
 /// +----------+-----------------+--------------------+--------------------+--------------------+
@@ -46,11 +42,8 @@ pub struct Value {
 
 impl From<Value> for Bytes {
     fn from(v: Value) -> Self {
-        let mut b = BytesMut::with_capacity(MAX_VALUE_INFO_SIZE + v.value.len());
-        b.put_u8(v.meta);
-        b.put_u8(v.user_meta);
-        b.put_u64(v.expires_at);
-        b.extend(v.value);
+        let mut b = BytesMut::with_capacity(v.encoded_size() as usize);
+        v.encode_to(&mut b);
         b.freeze()
     }
 }
@@ -159,7 +152,7 @@ impl Value {
 
 impl ValueExt for Value {
     #[inline]
-    fn as_value_ref(&self) -> ValueRef {
+    fn as_value_ref(&self) -> ValueRef<'_> {
         ValueRef {
             meta: self.meta,
             user_meta: self.user_meta,
@@ -243,7 +236,7 @@ pub trait ValueExt {
     ///
     /// [`ValueRef`]: struct.ValueRef.html
     #[inline]
-    fn as_value_ref(&self) -> ValueRef {
+    fn as_value_ref(&self) -> ValueRef<'_> {
         ValueRef {
             meta: self.get_meta(),
             user_meta: self.get_user_meta(),
@@ -270,6 +263,17 @@ pub trait ValueExt {
     /// Returns the expiration time (unix timestamp) for this value
     fn get_expires_at(&self) -> u64;
 
+    /// Returns whether the value has been deleted, or has expired at `now` (unix timestamp).
+    /// A value whose expires_at is 0 never expires.
+    #[inline]
+    fn is_deleted_or_expired(&self, now: u64) -> bool {
+        if OP::from_bits_truncate(self.get_meta()).contains(OP::BIT_DELETE) {
+            return true;
+        }
+        let expires_at = self.get_expires_at();
+        expires_at != 0 && expires_at <= now
+    }
+
     /// Returns the size of the Value when encoded
     #[inline]
     fn encoded_size(&self) -> u32 {
@@ -278,6 +282,18 @@ pub trait ValueExt {
         (sz + enc) as u32
     }
 
+    /// Encodes the value into the buffer without intermediate allocation, the buffer grows
+    /// by [`encoded_size`] bytes. This function will copy the value.
+    ///
+    /// [`encoded_size`]: #method.encoded_size
+    #[inline]
+    fn encode_to(&self, buf: &mut impl BufMut) {
+        buf.put_u8(self.get_meta());
+        buf.put_u8(self.get_user_meta());
+        binary_put_uvariant_to_bufmut(buf, self.get_expires_at());
+        buf.put_slice(self.parse_value());
+    }
+
     /// Encode to a mutable slice. This function will copy the value.
     /// Use [`to_encoded`], if you want a shallow copy when encoded.
     ///
@@ -285,41 +301,30 @@ pub trait ValueExt {
     /// This function panics if the remaining capacity of slice is less than encoded size.
     ///
     /// [`to_encoded`]: #method.to_encoded
+    #[inline]
     fn encode(&self, mut buf: &mut [u8]) {
-        buf.put_u8(self.get_meta());
-        buf.put_u8(self.get_user_meta());
-        buf.put_slice(binary_uvarint_allocate(self.get_expires_at()).as_slice());
-        buf.put_slice(self.parse_value());
+        self.encode_to(&mut buf)
     }
 
     /// Encode to [`EncodedValue`].
     ///
     /// This function may be optimized by the underlying type to avoid actual copies.
-    /// For example, [`Value`] implementation will do a shallow copy (ref-count increment)
+    /// For example, [`EncodedValue`] implementation will do a shallow copy (ref-count increment)
     ///
     /// [`EncodedValue`]: struct.EncodedValue.html
-    /// [`Value`]: struct.Value.html
     #[inline]
     fn to_encoded(&self) -> EncodedValue {
-        let mut data = Vec::with_capacity(MAX_VALUE_INFO_SIZE);
-        data.push(self.get_meta());
-        data.push(self.get_user_meta());
-        put_binary_uvariant_to_vec(data.as_mut(), self.get_expires_at());
-
-        let expires_sz = data.len() - 2;
-        let meta = Bytes::from(data);
-        let val = self.parse_value_to_bytes();
-        let enc_len = meta.len() + val.len();
-
+        let mut data = BytesMut::with_capacity(self.encoded_size() as usize);
+        self.encode_to(&mut data);
         EncodedValue {
-            data: meta.chain(val).copy_to_bytes(enc_len),
-            expires_sz: expires_sz as u8,
+            data: data.freeze(),
+            expires_sz: size_variant(self.get_expires_at()) as u8,
         }
     }
 
     /// Decodes byte slice to value ref.
     #[inline]
-    fn decode_value_ref(src: &[u8]) -> ValueRef {
+    fn decode_value_ref(src: &[u8]) -> ValueRef<'_> {
         let meta = src[META_OFFSET];
         let user_meta = src[USER_META_OFFSET];
         let (expires_at, sz) = binary_uvarint(&src[EXPIRATION_OFFSET..]);
@@ -455,7 +460,7 @@ impl<'a> ValueRef<'a> {
 
 impl<'a> ValueExt for ValueRef<'a> {
     #[inline]
-    fn as_value_ref(&self) -> ValueRef {
+    fn as_value_ref(&self) -> ValueRef<'_> {
         *self
     }
 
@@ -508,7 +513,7 @@ impl<const N: usize> ValueExt for [u8; N] {
     }
 }
 
-impl<'a> ValueExt for &'a [u8] {
+impl ValueExt for &[u8] {
     fn parse_value(&self) -> &[u8] {
         self
     }
@@ -10,7 +10,7 @@ use core::ops::{Deref, DerefMut};
 /// **Note:**
 /// 1. `version` field will not be encoded, it is a helper field.
 /// 2. `expiration` field will be encoded as uvarient, which means after encoded, the size of
-/// this field is less or equal to 8 bytes.
+///    this field is less or equal to 8 bytes.
 ///
 /// ```text
 /// +----------+-----------------+--------------------+--------------------+--------------------+
@@ -59,7 +59,7 @@ impl DerefMut for ValueMut {
 
 impl ValueExt for ValueMut {
     #[inline]
-    fn as_value_ref(&self) -> ValueRef {
+    fn as_value_ref(&self) -> ValueRef<'_> {
         ValueRef {
             meta: self.meta,
             user_meta: self.user_meta,
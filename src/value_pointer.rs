@@ -0,0 +1,137 @@
+use crate::{Error, ValueExt, OP};
+
+/// The size of an encoded [`ValuePointer`].
+///
+/// [`ValuePointer`]: struct.ValuePointer.html
+pub const VALUE_POINTER_SIZE: usize = 12;
+
+/// ValuePointer points to a value stored in a value log file, it is stored as the data of
+/// a [`Value`] with [`OP::BIT_VALUE_POINTER`] set.
+///
+/// The encoded pointer looks like
+///
+/// ```text
+/// +----------+----------+----------+
+/// |   fid    |   len    |  offset  |
+/// +----------+----------+----------+
+/// | u32 (LE) | u32 (LE) | u32 (LE) |
+/// +----------+----------+----------+
+/// ```
+///
+/// [`Value`]: ../struct.Value.html
+/// [`OP::BIT_VALUE_POINTER`]: ../struct.OP.html#associatedconstant.BIT_VALUE_POINTER
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct ValuePointer {
+    fid: u32,
+    len: u32,
+    offset: u32,
+}
+
+impl ValuePointer {
+    /// Returns a ValuePointer to `len` bytes at `offset` in the value log file `fid`.
+    #[inline]
+    pub const fn new(fid: u32, len: u32, offset: u32) -> Self {
+        Self { fid, len, offset }
+    }
+
+    /// Get the id of the value log file
+    #[inline]
+    pub const fn get_fid(&self) -> u32 {
+        self.fid
+    }
+
+    /// Get the length of the record in the value log file
+    #[inline]
+    pub const fn get_len(&self) -> u32 {
+        self.len
+    }
+
+    /// Get the offset of the record in the value log file
+    #[inline]
+    pub const fn get_offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// Returns true if the pointer points to nothing
+    #[inline]
+    pub const fn is_zero(&self) -> bool {
+        self.fid == 0 && self.len == 0 && self.offset == 0
+    }
+
+    /// Encodes the pointer
+    #[inline]
+    pub fn encode(&self) -> [u8; VALUE_POINTER_SIZE] {
+        let mut buf = [0; VALUE_POINTER_SIZE];
+        buf[..4].copy_from_slice(&self.fid.to_le_bytes());
+        buf[4..8].copy_from_slice(&self.len.to_le_bytes());
+        buf[8..].copy_from_slice(&self.offset.to_le_bytes());
+        buf
+    }
+
+    /// Decodes a pointer from the encoded form
+    #[inline]
+    pub fn decode(src: &[u8]) -> Result<Self, Error> {
+        if src.len() != VALUE_POINTER_SIZE {
+            return Err(Error::corruption("value pointer: bad length"));
+        }
+        let u32_at = |i: usize| u32::from_le_bytes([src[i], src[i + 1], src[i + 2], src[i + 3]]);
+        Ok(Self {
+            fid: u32_at(0),
+            len: u32_at(4),
+            offset: u32_at(8),
+        })
+    }
+
+    /// Decodes the pointer stored in the value, returns `None` if the value does not have
+    /// [`OP::BIT_VALUE_POINTER`] set.
+    ///
+    /// [`OP::BIT_VALUE_POINTER`]: ../struct.OP.html#associatedconstant.BIT_VALUE_POINTER
+    #[inline]
+    pub fn from_value(val: &impl ValueExt) -> Result<Option<Self>, Error> {
+        if OP::from_bits_truncate(val.get_meta()).contains(OP::BIT_VALUE_POINTER) {
+            Self::decode(val.parse_value()).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl PartialOrd for ValuePointer {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ValuePointer {
+    /// Pointers are ordered by their positions in the value log.
+    #[inline]
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.fid
+            .cmp(&other.fid)
+            .then(self.offset.cmp(&other.offset))
+            .then(self.len.cmp(&other.len))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bytes::Bytes;
+    use crate::Value;
+
+    #[test]
+    fn test_value_pointer() {
+        let vp = ValuePointer::new(3, 100, 4096);
+        assert_eq!(ValuePointer::decode(&vp.encode()).unwrap(), vp);
+        assert!(ValuePointer::decode(&[0; 11]).is_err());
+        assert!(ValuePointer::default().is_zero());
+        assert!(vp < ValuePointer::new(3, 1, 4097));
+        assert!(vp > ValuePointer::new(2, 1, 8192));
+
+        let val = Value::new().set_data(Bytes::copy_from_slice(&vp.encode()));
+        assert_eq!(ValuePointer::from_value(&val).unwrap(), None);
+        let val = val.set_meta(OP::BIT_VALUE_POINTER.bits());
+        assert_eq!(ValuePointer::from_value(&val).unwrap(), Some(vp));
+    }
+}
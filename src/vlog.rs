@@ -0,0 +1,740 @@
+use crate::bytes::{BufMut, Bytes};
+use crate::value_pointer::ValuePointer;
+use crate::{
+    checksum, decode_uvarint, Error, Header, Key, KeyExt, KeyRef, Value, ValueExt, ValueRef,
+    CASTAGNOLI, MAX_HEADER_SIZE,
+};
+
+/// The size of the checksum at the end of a value log record.
+pub const VLOG_CRC_SIZE: usize = 4;
+
+/// VlogRecord is a key-value pair stored in a value log file.
+///
+/// The encoded record looks like
+///
+/// ```text
+/// +--------+-----+-------+-----------+
+/// | header | key | value | checksum  |
+/// +--------+-----+-------+-----------+
+/// |        |     |       | u32 (BE)  |
+/// +--------+-----+-------+-----------+
+/// ```
+///
+/// where the header is an encoded [`Header`], and the checksum is the CRC-32 (Castagnoli)
+/// of the header, the key and the value.
+///
+/// [`Header`]: ../struct.Header.html
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct VlogRecord {
+    key: Key,
+    value: Value,
+    offset: u32,
+    len: u32,
+}
+
+impl VlogRecord {
+    /// Encodes a record of the key (with timestamp) and value, appends it to dst without
+    /// intermediate allocation and returns the length of the record.
+    pub fn encode_to(key: impl KeyExt, val: impl ValueExt, dst: &mut impl BufMut) -> u32 {
+        let key = key.as_bytes();
+        let data = val.parse_value();
+        let header = Header::new(
+            val.get_meta(),
+            val.get_user_meta(),
+            key.len() as u32,
+            data.len() as u32,
+            val.get_expires_at(),
+        );
+
+        let mut h = [0; MAX_HEADER_SIZE];
+        let h_len = header.encode_to(&mut h.as_mut_slice());
+        let mut digest = CASTAGNOLI.digest();
+        digest.update(&h[..h_len]);
+        digest.update(key);
+        digest.update(data);
+
+        dst.put_slice(&h[..h_len]);
+        dst.put_slice(key);
+        dst.put_slice(data);
+        dst.put_u32(digest.finalize());
+        (h_len + key.len() + data.len() + VLOG_CRC_SIZE) as u32
+    }
+
+    /// Get the key (with timestamp)
+    #[inline]
+    pub fn get_key(&self) -> &Key {
+        &self.key
+    }
+
+    /// Get the value, whose version is the timestamp of the key
+    #[inline]
+    pub fn get_value(&self) -> &Value {
+        &self.value
+    }
+
+    /// Get the offset of the record in the value log file
+    #[inline]
+    pub fn get_offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// Get the length of the encoded record
+    #[inline]
+    pub fn get_len(&self) -> u32 {
+        self.len
+    }
+
+    /// Returns the key and value
+    #[inline]
+    pub fn into_kv(self) -> (Key, Value) {
+        (self.key, self.value)
+    }
+}
+
+impl VlogRecord {
+    /// Decodes the record at `offset` of the value log, returns `None` at the end of the log.
+    ///
+    /// The key and the value share the memory of the log instead of copying, so they can
+    /// outlive it, e.g. the log is a memory-mapped file converted by [`MmapFile::to_bytes`].
+    ///
+    /// [`MmapFile::to_bytes`]: ../mmap/struct.MmapFile.html#method.to_bytes
+    pub fn decode_bytes(log: &Bytes, offset: u32) -> Result<Option<Self>, Error> {
+        Ok(decode_record(log, offset)?.map(|info| {
+            let key = Key::from(log.slice(info.key.0..info.key.1));
+            let value = Value::with_all_fields(
+                info.header.get_meta(),
+                info.header.get_user_meta(),
+                info.header.get_expires_at(),
+                key.parse_timestamp(),
+                log.slice(info.value.0..info.value.1),
+            );
+            VlogRecord {
+                key,
+                value,
+                offset,
+                len: (info.end - offset as usize) as u32,
+            }
+        }))
+    }
+
+    /// Reads the record pointed by the value pointer, see [`decode_bytes`].
+    ///
+    /// The id of the value log file is not checked.
+    ///
+    /// [`decode_bytes`]: #method.decode_bytes
+    pub fn read_bytes(log: &Bytes, vp: ValuePointer) -> Result<Self, Error> {
+        match Self::decode_bytes(log, vp.get_offset())? {
+            Some(record) if record.len == vp.get_len() => Ok(record),
+            Some(_) => Err(Error::corruption("vlog: value pointer length mismatch")),
+            None => Err(Error::corruption("vlog: value pointer out of range")),
+        }
+    }
+}
+
+/// VlogRecordRef is a [`VlogRecord`] borrowing the memory of the value log, e.g. a
+/// memory-mapped file, the key and value refs live as long as the log.
+///
+/// [`VlogRecord`]: struct.VlogRecord.html
+#[derive(Debug, Copy, Clone)]
+pub struct VlogRecordRef<'a> {
+    key: KeyRef<'a>,
+    value: ValueRef<'a>,
+    offset: u32,
+    len: u32,
+}
+
+impl<'a> VlogRecordRef<'a> {
+    /// Decodes the record at `offset` of the value log, returns `None` at the end of the log.
+    /// A zero key length also ends the log, which is left by preallocation.
+    pub fn decode(log: &'a [u8], offset: u32) -> Result<Option<Self>, Error> {
+        Ok(decode_record(log, offset)?.map(|info| {
+            let key = KeyRef::new(&log[info.key.0..info.key.1]);
+            let value = ValueRef::new(
+                info.header.get_meta(),
+                info.header.get_user_meta(),
+                info.header.get_expires_at(),
+                key.parse_timestamp(),
+                &log[info.value.0..info.value.1],
+            );
+            VlogRecordRef {
+                key,
+                value,
+                offset,
+                len: (info.end - offset as usize) as u32,
+            }
+        }))
+    }
+
+    /// Reads the record pointed by the value pointer.
+    ///
+    /// The id of the value log file is not checked.
+    pub fn read(log: &'a [u8], vp: ValuePointer) -> Result<Self, Error> {
+        match Self::decode(log, vp.get_offset())? {
+            Some(record) if record.len == vp.get_len() => Ok(record),
+            Some(_) => Err(Error::corruption("vlog: value pointer length mismatch")),
+            None => Err(Error::corruption("vlog: value pointer out of range")),
+        }
+    }
+
+    /// Get the key (with timestamp)
+    #[inline]
+    pub fn get_key(&self) -> KeyRef<'a> {
+        self.key
+    }
+
+    /// Get the value, whose version is the timestamp of the key
+    #[inline]
+    pub fn get_value(&self) -> ValueRef<'a> {
+        self.value
+    }
+
+    /// Get the offset of the record in the value log file
+    #[inline]
+    pub fn get_offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// Get the length of the encoded record
+    #[inline]
+    pub fn get_len(&self) -> u32 {
+        self.len
+    }
+
+    /// Copies the key and the value to a [`VlogRecord`]
+    ///
+    /// [`VlogRecord`]: struct.VlogRecord.html
+    #[inline]
+    pub fn to_record(&self) -> VlogRecord {
+        VlogRecord {
+            key: self.key.to_key(),
+            value: self.value.to_value(),
+            offset: self.offset,
+            len: self.len,
+        }
+    }
+}
+
+/// The decoded header of a record, the ranges are the offsets in the log.
+struct RecordInfo {
+    header: Header,
+    key: (usize, usize),
+    value: (usize, usize),
+    end: usize,
+}
+
+/// Decodes and verifies the record at offset of the value log.
+fn decode_record(log: &[u8], offset: u32) -> Result<Option<RecordInfo>, Error> {
+    let start = offset as usize;
+    let buf = match log.get(start..) {
+        Some(buf) if !buf.is_empty() => buf,
+        _ => return Ok(None),
+    };
+    if buf.len() < 2 {
+        return Err(Error::corruption("vlog: truncated header"));
+    }
+
+    let (k_len, n1) = decode_uvarint(&buf[2..])?;
+    let (v_len, n2) = decode_uvarint(&buf[2 + n1..])?;
+    let (expires_at, n3) = decode_uvarint(&buf[2 + n1 + n2..])?;
+    if k_len == 0 {
+        return Ok(None);
+    }
+
+    let header_len = 2 + n1 + n2 + n3;
+    let crc_offset = (header_len as u64)
+        .checked_add(k_len)
+        .and_then(|n| n.checked_add(v_len))
+        .filter(|n| *n + VLOG_CRC_SIZE as u64 <= buf.len() as u64)
+        .ok_or_else(|| Error::corruption("vlog: truncated record"))? as usize;
+
+    let crc = &buf[crc_offset..crc_offset + VLOG_CRC_SIZE];
+    let expected = u32::from_be_bytes([crc[0], crc[1], crc[2], crc[3]]);
+    let actual = checksum(&buf[..crc_offset]);
+    if expected != actual {
+        return Err(Error::ChecksumMismatch { expected, actual });
+    }
+
+    let key_start = start + header_len;
+    let val_start = key_start + k_len as usize;
+    Ok(Some(RecordInfo {
+        header: Header::new(buf[0], buf[1], k_len as u32, v_len as u32, expires_at),
+        key: (key_start, val_start),
+        value: (val_start, start + crc_offset),
+        end: start + crc_offset + VLOG_CRC_SIZE,
+    }))
+}
+
+cfg_std! {
+    use crate::bytes::BytesMut;
+    use alloc::vec::Vec;
+    use crate::{ByteReader, OP};
+    use crate::batch::WriteBatch;
+    use std::io::{ErrorKind, Read};
+
+    /// VlogIterator reads the [`VlogRecord`]s of a value log file one by one.
+    ///
+    /// [`VlogRecord`]: struct.VlogRecord.html
+    #[derive(Debug)]
+    pub struct VlogIterator<R> {
+        reader: R,
+        fid: u32,
+        hash_buf: BytesMut,
+    }
+
+    impl<R: ByteReader + Read> VlogIterator<R> {
+        /// Returns a VlogIterator reading the value log file `fid` from the current position
+        /// of the reader.
+        #[inline]
+        pub fn new(reader: R, fid: u32) -> Self {
+            Self {
+                reader,
+                fid,
+                hash_buf: BytesMut::new(),
+            }
+        }
+
+        /// Get the id of the value log file
+        #[inline]
+        pub fn get_fid(&self) -> u32 {
+            self.fid
+        }
+
+        /// Reads the next record, returns `None` at the end of the file. A zero key length
+        /// also ends the file, which is left by preallocation.
+        pub fn next_record(&mut self) -> Result<Option<VlogRecord>, Error> {
+            let start = self.reader.position();
+            self.hash_buf.clear();
+            let header = match Header::decode_reader(&mut self.reader, &mut self.hash_buf) {
+                Ok((_, h)) => h,
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                    return if self.reader.position() == start {
+                        Ok(None)
+                    } else {
+                        Err(Error::corruption("vlog: truncated header"))
+                    };
+                }
+                Err(e) => return Err(e.into()),
+            };
+            if header.get_key_len() == 0 {
+                return Ok(None);
+            }
+
+            let key = self.read_exact(header.get_key_len())?;
+            let data = self.read_exact(header.get_value_len())?;
+            self.hash_buf.put_slice(&key);
+            self.hash_buf.put_slice(&data);
+
+            let mut crc = [0; VLOG_CRC_SIZE];
+            self.reader.read_exact(&mut crc).map_err(|e| match e.kind() {
+                ErrorKind::UnexpectedEof => Error::corruption("vlog: truncated record"),
+                _ => e.into(),
+            })?;
+            let expected = u32::from_be_bytes(crc);
+            let actual = checksum(&self.hash_buf);
+            if expected != actual {
+                return Err(Error::ChecksumMismatch { expected, actual });
+            }
+
+            let key = Key::from(key);
+            let value = Value::with_all_fields(
+                header.get_meta(),
+                header.get_user_meta(),
+                header.get_expires_at(),
+                key.parse_timestamp(),
+                Bytes::from(data),
+            );
+            Ok(Some(VlogRecord {
+                key,
+                value,
+                offset: start as u32,
+                len: (self.reader.position() - start) as u32,
+            }))
+        }
+
+        fn read_exact(&mut self, len: u32) -> Result<Vec<u8>, Error> {
+            // do not trust the length to allocate.
+            let mut buf = Vec::new();
+            (&mut self.reader).take(len as u64).read_to_end(&mut buf)?;
+            if buf.len() != len as usize {
+                return Err(Error::corruption("vlog: truncated record"));
+            }
+            Ok(buf)
+        }
+    }
+
+    /// GcStats is the result of scanning (a part of) a value log file.
+    #[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+    pub struct GcStats {
+        records: usize,
+        bytes: u64,
+        live_records: usize,
+        live_bytes: u64,
+    }
+
+    impl GcStats {
+        /// Get the number of scanned records
+        #[inline]
+        pub fn get_records(&self) -> usize {
+            self.records
+        }
+
+        /// Get the size of scanned records
+        #[inline]
+        pub fn get_bytes(&self) -> u64 {
+            self.bytes
+        }
+
+        /// Get the number of live records
+        #[inline]
+        pub fn get_live_records(&self) -> usize {
+            self.live_records
+        }
+
+        /// Get the size of live records
+        #[inline]
+        pub fn get_live_bytes(&self) -> u64 {
+            self.live_bytes
+        }
+
+        /// Returns the fraction of scanned bytes which can be reclaimed
+        #[inline]
+        pub fn discard_ratio(&self) -> f64 {
+            if self.bytes == 0 {
+                return 0.0;
+            }
+            (self.bytes - self.live_bytes) as f64 / self.bytes as f64
+        }
+
+        #[inline]
+        fn add(&mut self, len: u32, live: bool) {
+            self.records += 1;
+            self.bytes += len as u64;
+            if live {
+                self.live_records += 1;
+                self.live_bytes += len as u64;
+            }
+        }
+    }
+
+    /// GcPlan is the result of [`VlogGc::run`].
+    ///
+    /// [`VlogGc::run`]: struct.VlogGc.html#method.run
+    #[derive(Debug, Clone)]
+    pub struct GcPlan {
+        stats: GcStats,
+        rewrite: bool,
+        batch: WriteBatch,
+    }
+
+    impl GcPlan {
+        /// Get the stats of the file
+        #[inline]
+        pub fn get_stats(&self) -> &GcStats {
+            &self.stats
+        }
+
+        /// Returns true if the file should be rewritten and then deleted
+        #[inline]
+        pub fn should_rewrite(&self) -> bool {
+            self.rewrite
+        }
+
+        /// Returns the live entries to rewrite, which is empty if the file should be kept.
+        #[inline]
+        pub fn get_batch(&self) -> &WriteBatch {
+            &self.batch
+        }
+
+        /// Returns the live entries to rewrite, which is empty if the file should be kept.
+        #[inline]
+        pub fn into_batch(self) -> WriteBatch {
+            self.batch
+        }
+    }
+
+    /// VlogGc finds the live records in a value log file, and decides whether the file
+    /// should be rewritten.
+    ///
+    /// A record is live if the current value of its key (returned by the `live` callback,
+    /// with the version set) is the same version, is neither deleted nor expired, and is a
+    /// [`ValuePointer`] to the record itself.
+    ///
+    /// [`ValuePointer`]: ../value_pointer/struct.ValuePointer.html
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    pub struct VlogGc {
+        discard_ratio: f64,
+        now: u64,
+    }
+
+    impl Default for VlogGc {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl VlogGc {
+        /// Returns a VlogGc rewriting files with at least half of their bytes discardable.
+        #[inline]
+        pub fn new() -> Self {
+            Self {
+                discard_ratio: 0.5,
+                now: std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs(),
+            }
+        }
+
+        /// Set the minimal discard ratio for rewriting a file
+        #[inline]
+        pub fn set_discard_ratio(mut self, ratio: f64) -> Self {
+            self.discard_ratio = ratio;
+            self
+        }
+
+        /// Get the minimal discard ratio for rewriting a file
+        #[inline]
+        pub fn get_discard_ratio(&self) -> f64 {
+            self.discard_ratio
+        }
+
+        /// Set the time (unix timestamp) used to check the expiration of values
+        #[inline]
+        pub fn set_now(mut self, now: u64) -> Self {
+            self.now = now;
+            self
+        }
+
+        /// Returns true if the stats reach the discard ratio
+        #[inline]
+        pub fn should_rewrite(&self, stats: &GcStats) -> bool {
+            stats.records > 0 && stats.discard_ratio() >= self.discard_ratio
+        }
+
+        /// Scans the whole file, and collects the live entries if it should be rewritten.
+        ///
+        /// The rewritten values carry the data inline, with the value pointer and transaction
+        /// bits cleared.
+        pub fn run<R, F>(&self, iter: &mut VlogIterator<R>, mut live: F) -> Result<GcPlan, Error>
+        where
+            R: ByteReader + Read,
+            F: FnMut(&Key) -> Result<Option<Value>, Error>,
+        {
+            let mut stats = GcStats::default();
+            let mut batch = WriteBatch::new();
+            while let Some(record) = iter.next_record()? {
+                let is_live = self.is_live(iter.fid, &record.key, record.offset, &mut live)?;
+                stats.add(record.len, is_live);
+                if is_live {
+                    let (key, val) = record.into_kv();
+                    let meta = OP::from_bits_truncate(val.get_meta())
+                        - (OP::BIT_VALUE_POINTER | OP::BIT_TXN | OP::BIT_FIN_TXN);
+                    batch.put(key, val.set_meta(meta.bits()));
+                }
+            }
+
+            let rewrite = self.should_rewrite(&stats);
+            if !rewrite {
+                batch.clear();
+            }
+            Ok(GcPlan {
+                stats,
+                rewrite,
+                batch,
+            })
+        }
+
+        /// Estimates the discard ratio of the value log file `fid` without a full scan, e.g.
+        /// over a [`MmapFile`]: the records before `skip` bytes are not read, then at most
+        /// `max_records` records or `max_bytes` bytes are checked. Callers usually pick a
+        /// random `skip`.
+        ///
+        /// If `skip` is not the offset of a record, the following offsets are tried until a
+        /// record passes its checksum, that record must end within `max_bytes` bytes from
+        /// `skip`.
+        ///
+        /// [`MmapFile`]: ../mmap/struct.MmapFile.html
+        pub fn sample<F>(
+            &self,
+            log: &[u8],
+            fid: u32,
+            skip: u32,
+            max_bytes: u64,
+            max_records: usize,
+            mut live: F,
+        ) -> Result<GcStats, Error>
+        where
+            F: FnMut(&Key) -> Result<Option<Value>, Error>,
+        {
+            let mut stats = GcStats::default();
+            let mut offset = match first_record_from(log, skip, max_bytes) {
+                Some(offset) => offset,
+                None => return Ok(stats),
+            };
+            while stats.records < max_records && stats.bytes < max_bytes {
+                let record = match VlogRecordRef::decode(log, offset)? {
+                    Some(record) => record,
+                    None => break,
+                };
+                let key = record.get_key().to_key();
+                let is_live = self.is_live(fid, &key, offset, &mut live)?;
+                stats.add(record.len, is_live);
+                offset += record.len;
+            }
+            Ok(stats)
+        }
+
+        fn is_live<F>(&self, fid: u32, key: &Key, offset: u32, live: &mut F) -> Result<bool, Error>
+        where
+            F: FnMut(&Key) -> Result<Option<Value>, Error>,
+        {
+            let current = match live(key)? {
+                Some(v) => v,
+                None => return Ok(false),
+            };
+            if current.get_version() != key.parse_timestamp()
+                || current.is_deleted_or_expired(self.now)
+                || OP::from_bits_truncate(current.get_meta()).contains(OP::BIT_FIN_TXN)
+            {
+                return Ok(false);
+            }
+            Ok(match ValuePointer::from_value(&current)? {
+                // the value is stored in the LSM tree.
+                None => false,
+                Some(vp) => vp.get_fid() == fid && vp.get_offset() == offset,
+            })
+        }
+    }
+
+    /// Returns the offset of the first record at or after the offset, which may point into
+    /// a record.
+    ///
+    /// If the offset is not a record, only the records which end within `window` bytes from
+    /// it are looked for. The lengths of a candidate are checked against the window before its
+    /// checksum, so the garbage headers inside a large value do not checksum the rest of
+    /// the file at every offset.
+    fn first_record_from(log: &[u8], mut offset: u32, window: u64) -> Option<u32> {
+        if let Ok(Some(_)) = VlogRecordRef::decode(log, offset) {
+            return Some(offset);
+        }
+        let end = (offset as u64).saturating_add(window).min(log.len() as u64) as usize;
+        let log = &log[..end];
+        while (offset as usize) < end {
+            if let Ok(Some(_)) = VlogRecordRef::decode(log, offset) {
+                return Some(offset);
+            }
+            offset += 1;
+        }
+        None
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+    use crate::bytes::Bytes;
+    use crate::value_pointer::ValuePointer;
+    use crate::OP;
+    use std::collections::HashMap;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_vlog_gc() {
+        const FID: u32 = 7;
+        let mut data = Vec::new();
+        let mut lsm = HashMap::new();
+        for i in 0..100u64 {
+            let key = Key::from(format!("key{:03}", i % 50).into_bytes()).with_timestamp(i);
+            let val = Value::new()
+                .set_meta(OP::BIT_TXN.bits())
+                .set_user_meta(3)
+                .set_data(Bytes::from(vec![i as u8; 100]));
+            let offset = data.len() as u32;
+            let len = VlogRecord::encode_to(&key, val, &mut data);
+            let vp = ValuePointer::new(FID, len, offset);
+            // the newer version wins
+            lsm.insert(
+                key.parse_key().to_vec(),
+                Value::new()
+                    .set_meta(OP::BIT_VALUE_POINTER.bits())
+                    .set_version(i)
+                    .set_data(Bytes::copy_from_slice(&vp.encode())),
+            );
+        }
+        // deletes the last 10 keys
+        for i in 40..50u64 {
+            lsm.insert(
+                format!("key{:03}", i).into_bytes(),
+                Value::new()
+                    .set_meta(OP::BIT_DELETE.bits())
+                    .set_version(200),
+            );
+        }
+        let live = |k: &Key| Ok(lsm.get(k.parse_key()).cloned());
+
+        let gc = VlogGc::new().set_now(0);
+        let mut iter = VlogIterator::new(Cursor::new(data.clone()), FID);
+        let plan = gc.run(&mut iter, live).unwrap();
+        let stats = plan.get_stats();
+        assert_eq!(stats.get_records(), 100);
+        assert_eq!(stats.get_live_records(), 40);
+        assert_eq!(stats.get_bytes(), data.len() as u64);
+        assert!(plan.should_rewrite());
+        let batch = plan.into_batch();
+        assert_eq!(batch.len(), 40);
+        let (k, v) = batch.iter().next().unwrap();
+        assert_eq!(k.parse_key(), b"key000");
+        assert_eq!(k.parse_timestamp(), 50);
+        assert_eq!(v.get_meta(), 0);
+        assert_eq!(v.get_user_meta(), 3);
+        assert_eq!(v.parse_value(), &[50; 100][..]);
+
+        let gc = gc.set_discard_ratio(0.7);
+        let mut iter = VlogIterator::new(Cursor::new(data.clone()), FID);
+        let plan = gc.run(&mut iter, live).unwrap();
+        assert!(!plan.should_rewrite());
+        assert!(plan.get_batch().is_empty());
+
+        // only the second half, all records of which are the latest versions but 10.
+        let half = data.len() as u32 / 2;
+        let stats = gc.sample(&data, FID, half, u64::MAX, 20, live).unwrap();
+        assert_eq!(stats.get_records(), 20);
+        assert_eq!(stats.get_live_records(), 20);
+        assert_eq!(stats.discard_ratio(), 0.0);
+        // the first half is not read
+        let mut garbage = data.clone();
+        garbage[..half as usize].fill(0xff);
+        let sampled = gc.sample(&garbage, FID, half, u64::MAX, 20, live).unwrap();
+        assert_eq!(sampled, stats);
+        // from the middle of a record, the sample starts at the next one
+        let stats = gc
+            .sample(&data, FID, half + 1, u64::MAX, 100, live)
+            .unwrap();
+        assert_eq!(stats.get_records(), 49);
+        assert_eq!(stats.get_live_records(), 39);
+        let stats = gc.sample(&data, FID, half, 1, 100, live).unwrap();
+        assert_eq!(stats.get_records(), 1);
+        // the next record does not end within max_bytes
+        let next = VlogRecordRef::decode(&data, half).unwrap().unwrap().len as u64;
+        let stats = gc.sample(&data, FID, half + 1, next + 100, 100, live);
+        assert_eq!(stats.unwrap().get_records(), 0);
+        let stats = gc.sample(&data, FID, half + 1, 2 * next, 100, live);
+        assert_eq!(stats.unwrap().get_records(), 2);
+        let stats = gc.sample(&data, FID, data.len() as u32, u64::MAX, 100, live);
+        assert_eq!(stats.unwrap().get_records(), 0);
+
+        // corruption
+        let mut bad = data.clone();
+        bad[20] ^= 0xff;
+        let mut iter = VlogIterator::new(Cursor::new(bad.clone()), FID);
+        assert!(iter.next_record().is_err());
+        let mut iter = VlogIterator::new(Cursor::new(&data[..data.len() - 1]), FID);
+        assert!(gc.run(&mut iter, live).is_err());
+        assert!(VlogRecordRef::decode(&bad, 0).is_err());
+        let last = data.len() as u32 - 1;
+        assert!(
+            VlogRecordRef::read(&data[..last as usize], ValuePointer::new(FID, 1, last)).is_err()
+        );
+        assert!(VlogRecordRef::read(&data, ValuePointer::new(FID, 1, 0)).is_err());
+    }
+}
@@ -0,0 +1,659 @@
+use crate::batch::WriteBatch;
+use crate::bytes::{Bytes, BytesMut};
+use crate::{Entry, Error, CASTAGNOLI};
+use alloc::vec::Vec;
+
+/// The size of the physical blocks of the write-ahead log.
+pub const WAL_BLOCK_SIZE: usize = 32 * 1024;
+
+/// The size of a fragment header, checksum (u32) + length (u16) + type (u8).
+pub const WAL_HEADER_SIZE: usize = 7;
+
+/// RecordType is the type of a fragment in the write-ahead log.
+///
+/// A record fitting in the rest of a block is written as one [`RecordType::Full`] fragment,
+/// otherwise it is split into a [`RecordType::First`], zero or more [`RecordType::Middle`]
+/// and a [`RecordType::Last`] fragment.
+///
+/// [`RecordType::Full`]: enum.RecordType.html#variant.Full
+/// [`RecordType::First`]: enum.RecordType.html#variant.First
+/// [`RecordType::Middle`]: enum.RecordType.html#variant.Middle
+/// [`RecordType::Last`]: enum.RecordType.html#variant.Last
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[repr(u8)]
+pub enum RecordType {
+    /// Reserved for preallocated files
+    Zero = 0,
+    /// The whole record
+    Full = 1,
+    /// The first fragment of a record
+    First = 2,
+    /// The fragments between the first and the last one
+    Middle = 3,
+    /// The last fragment of a record
+    Last = 4,
+}
+
+impl RecordType {
+    #[inline]
+    fn from_u8(ty: u8) -> Option<Self> {
+        match ty {
+            0 => Some(RecordType::Zero),
+            1 => Some(RecordType::Full),
+            2 => Some(RecordType::First),
+            3 => Some(RecordType::Middle),
+            4 => Some(RecordType::Last),
+            _ => None,
+        }
+    }
+}
+
+/// RecoveryMode decides what [`LogReader`] does when it finds a corruption.
+///
+/// [`LogReader`]: struct.LogReader.html
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub enum RecoveryMode {
+    /// Returns an error on any corruption, including a truncated record at the end of the log.
+    #[default]
+    Strict,
+    /// Drops the corrupted fragments and records, and keeps reading.
+    SkipCorrupted,
+    /// Stops at a corruption in the last block or a truncated record at the end of the log,
+    /// and ignores the rest of the log. It is used when the tail of the log may have been torn
+    /// by a crash. Corruptions before the tail are errors, as in [`RecoveryMode::Strict`].
+    ///
+    /// [`RecoveryMode::Strict`]: enum.RecoveryMode.html#variant.Strict
+    TolerateTail,
+}
+
+/// LogSink is the destination of a [`LogWriter`].
+///
+/// [`LogWriter`]: struct.LogWriter.html
+pub trait LogSink {
+    /// Appends all the bytes
+    fn append(&mut self, data: &[u8]) -> Result<(), Error>;
+
+    /// Makes the appended bytes durable
+    #[inline]
+    fn sync(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl LogSink for Vec<u8> {
+    #[inline]
+    fn append(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.extend_from_slice(data);
+        Ok(())
+    }
+}
+
+impl LogSink for BytesMut {
+    #[inline]
+    fn append(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.extend_from_slice(data);
+        Ok(())
+    }
+}
+
+impl<S: LogSink + ?Sized> LogSink for &mut S {
+    #[inline]
+    fn append(&mut self, data: &[u8]) -> Result<(), Error> {
+        (**self).append(data)
+    }
+
+    #[inline]
+    fn sync(&mut self) -> Result<(), Error> {
+        (**self).sync()
+    }
+}
+
+cfg_std! {
+    use std::io::Write;
+
+    impl LogSink for std::fs::File {
+        #[inline]
+        fn append(&mut self, data: &[u8]) -> Result<(), Error> {
+            self.write_all(data).map_err(From::from)
+        }
+
+        #[inline]
+        fn sync(&mut self) -> Result<(), Error> {
+            self.sync_data().map_err(From::from)
+        }
+    }
+
+    impl LogSink for std::io::BufWriter<std::fs::File> {
+        #[inline]
+        fn append(&mut self, data: &[u8]) -> Result<(), Error> {
+            self.write_all(data).map_err(From::from)
+        }
+
+        #[inline]
+        fn sync(&mut self) -> Result<(), Error> {
+            self.flush()?;
+            self.get_ref().sync_data().map_err(From::from)
+        }
+    }
+}
+
+/// LogWriter appends records to a write-ahead log.
+///
+/// The log is a sequence of [`WAL_BLOCK_SIZE`] blocks, records are split into fragments which
+/// never cross the block boundary. Each fragment looks like
+///
+/// ```text
+/// +-----------+----------+--------+---------+
+/// | checksum  |  length  |  type  | payload |
+/// +-----------+----------+--------+---------+
+/// | u32 (LE)  | u16 (LE) |   u8   |         |
+/// +-----------+----------+--------+---------+
+/// ```
+///
+/// The checksum is the CRC-32 (Castagnoli) of the type and the payload. When the rest of a
+/// block cannot hold a header, it is filled with zeros.
+///
+/// [`WAL_BLOCK_SIZE`]: constant.WAL_BLOCK_SIZE.html
+#[derive(Debug)]
+pub struct LogWriter<W> {
+    dst: W,
+    block_offset: usize,
+}
+
+impl<W: LogSink> LogWriter<W> {
+    /// Returns a LogWriter writing a new log to dst
+    #[inline]
+    pub fn new(dst: W) -> Self {
+        Self {
+            dst,
+            block_offset: 0,
+        }
+    }
+
+    /// Returns a LogWriter appending to an existing log, which has `len` bytes.
+    #[inline]
+    pub fn with_len(dst: W, len: u64) -> Self {
+        Self {
+            dst,
+            block_offset: (len % WAL_BLOCK_SIZE as u64) as usize,
+        }
+    }
+
+    /// Returns the destination
+    #[inline]
+    pub fn get_ref(&self) -> &W {
+        &self.dst
+    }
+
+    /// Returns the destination
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.dst
+    }
+
+    /// Returns the destination
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.dst
+    }
+
+    /// Appends a record. If the destination returns an error, the log may end with a
+    /// partially written record, which is detected by [`LogReader`].
+    ///
+    /// [`LogReader`]: struct.LogReader.html
+    pub fn add_record(&mut self, record: &[u8]) -> Result<(), Error> {
+        let mut left = record;
+        let mut begin = true;
+        loop {
+            let leftover = WAL_BLOCK_SIZE - self.block_offset;
+            if leftover < WAL_HEADER_SIZE {
+                self.dst.append(&[0; WAL_HEADER_SIZE][..leftover])?;
+                self.block_offset = 0;
+            }
+
+            let avail = WAL_BLOCK_SIZE - self.block_offset - WAL_HEADER_SIZE;
+            let len = left.len().min(avail);
+            let end = len == left.len();
+            let ty = match (begin, end) {
+                (true, true) => RecordType::Full,
+                (true, false) => RecordType::First,
+                (false, false) => RecordType::Middle,
+                (false, true) => RecordType::Last,
+            };
+            self.emit(ty, &left[..len])?;
+            left = &left[len..];
+            begin = false;
+            if end {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Appends the encoded batch as a record
+    #[inline]
+    pub fn add_batch(&mut self, batch: &WriteBatch) -> Result<(), Error> {
+        self.add_record(batch.as_bytes())
+    }
+
+    /// Appends the entry as a record, which is encoded as a [`WriteBatch`] of one entry.
+    ///
+    /// [`WriteBatch`]: ../batch/struct.WriteBatch.html
+    #[inline]
+    pub fn add_entry(&mut self, ent: &Entry) -> Result<(), Error> {
+        let mut batch = WriteBatch::new();
+        batch.put_entry(ent);
+        self.add_batch(&batch)
+    }
+
+    /// Makes the appended records durable
+    #[inline]
+    pub fn sync(&mut self) -> Result<(), Error> {
+        self.dst.sync()
+    }
+
+    fn emit(&mut self, ty: RecordType, payload: &[u8]) -> Result<(), Error> {
+        let mut header = [0u8; WAL_HEADER_SIZE];
+        header[..4].copy_from_slice(&fragment_checksum(ty as u8, payload).to_le_bytes());
+        header[4..6].copy_from_slice(&(payload.len() as u16).to_le_bytes());
+        header[6] = ty as u8;
+        self.dst.append(&header)?;
+        self.dst.append(payload)?;
+        self.block_offset += WAL_HEADER_SIZE + payload.len();
+        Ok(())
+    }
+}
+
+#[inline]
+fn fragment_checksum(ty: u8, payload: &[u8]) -> u32 {
+    let mut digest = CASTAGNOLI.digest();
+    digest.update(&[ty]);
+    digest.update(payload);
+    digest.finalize()
+}
+
+enum Physical {
+    Fragment(RecordType, Bytes),
+    Eof,
+    Corrupted(Error),
+}
+
+/// LogReader reads the records written by [`LogWriter`].
+///
+/// [`LogWriter`]: struct.LogWriter.html
+#[derive(Debug)]
+pub struct LogReader {
+    data: Bytes,
+    mode: RecoveryMode,
+    pos: usize,
+    /// the offset of the last read fragment
+    fragment_start: usize,
+    /// the offset of the first fragment of the pending record
+    record_start: usize,
+    /// the fragments of the pending record
+    scratch: Vec<u8>,
+    in_record: bool,
+    eof: bool,
+    dropped: usize,
+}
+
+impl LogReader {
+    /// Returns a LogReader over the whole log
+    #[inline]
+    pub fn new(data: Bytes, mode: RecoveryMode) -> Self {
+        Self {
+            data,
+            mode,
+            pos: 0,
+            fragment_start: 0,
+            record_start: 0,
+            scratch: Vec::new(),
+            in_record: false,
+            eof: false,
+            dropped: 0,
+        }
+    }
+
+    cfg_std! {
+        /// Reads the whole log file
+        pub fn open(path: impl AsRef<std::path::Path>, mode: RecoveryMode) -> Result<Self, Error> {
+            let data = std::fs::read(path)?;
+            Ok(Self::new(Bytes::from(data), mode))
+        }
+    }
+
+    /// Returns the recovery mode
+    #[inline]
+    pub fn get_recovery_mode(&self) -> RecoveryMode {
+        self.mode
+    }
+
+    /// Returns the number of bytes dropped because of corruptions
+    #[inline]
+    pub fn dropped_bytes(&self) -> usize {
+        self.dropped
+    }
+
+    /// Reads the next record, returns `None` at the end of the log.
+    pub fn read_record(&mut self) -> Result<Option<Bytes>, Error> {
+        loop {
+            if self.eof {
+                return Ok(None);
+            }
+
+            match self.read_physical() {
+                Physical::Fragment(RecordType::Full, payload) => {
+                    if self.in_record {
+                        self.drop_pending()?;
+                        if self.eof {
+                            return Ok(None);
+                        }
+                    }
+                    return Ok(Some(payload));
+                }
+                Physical::Fragment(RecordType::First, payload) => {
+                    if self.in_record {
+                        self.drop_pending()?;
+                        if self.eof {
+                            return Ok(None);
+                        }
+                    }
+                    self.in_record = true;
+                    self.record_start = self.fragment_start;
+                    self.scratch.clear();
+                    self.scratch.extend_from_slice(&payload);
+                }
+                Physical::Fragment(ty, payload) => {
+                    if !self.in_record {
+                        let n = WAL_HEADER_SIZE + payload.len();
+                        let e = Error::corruption("wal: missing the first fragment");
+                        self.corrupted(e, n, false)?;
+                        continue;
+                    }
+                    self.scratch.extend_from_slice(&payload);
+                    if ty == RecordType::Last {
+                        self.in_record = false;
+                        return Ok(Some(Bytes::from(core::mem::take(&mut self.scratch))));
+                    }
+                }
+                Physical::Eof => {
+                    if self.in_record {
+                        self.in_record = false;
+                        let n = self.data.len() - self.record_start;
+                        self.corrupted(Error::corruption("wal: truncated record"), n, true)?;
+                    }
+                    self.eof = true;
+                }
+                Physical::Corrupted(e) => {
+                    // the rest of the block cannot be trusted, neither can the pending record.
+                    let block_end = self.block_end();
+                    let mut n = block_end - self.fragment_start;
+                    if self.in_record {
+                        self.in_record = false;
+                        self.scratch.clear();
+                        n += self.fragment_start - self.record_start;
+                    }
+                    self.pos = block_end;
+                    self.corrupted(e, n, block_end == self.data.len())?;
+                }
+            }
+        }
+    }
+
+    /// Reads the next record and decodes it as a [`WriteBatch`], returns `None` at the
+    /// end of the log. Records failing to decode are handled by the recovery mode.
+    ///
+    /// [`WriteBatch`]: ../batch/struct.WriteBatch.html
+    pub fn read_batch(&mut self) -> Result<Option<WriteBatch>, Error> {
+        while let Some(record) = self.read_record()? {
+            match WriteBatch::decode(&record) {
+                Ok(batch) => return Ok(Some(batch)),
+                Err(e) => self.corrupted(e, record.len(), false)?,
+            }
+        }
+        Ok(None)
+    }
+
+    #[inline]
+    fn block_end(&self) -> usize {
+        let end = (self.pos / WAL_BLOCK_SIZE + 1) * WAL_BLOCK_SIZE;
+        end.min(self.data.len())
+    }
+
+    fn read_physical(&mut self) -> Physical {
+        loop {
+            let block_end = self.block_end();
+            self.fragment_start = self.pos;
+            let leftover = block_end - self.pos;
+            if leftover == 0 {
+                return Physical::Eof;
+            }
+
+            if leftover < WAL_HEADER_SIZE {
+                if block_end == self.data.len() && !block_end.is_multiple_of(WAL_BLOCK_SIZE) {
+                    // the writer pads the block only before writing the next header.
+                    return Physical::Corrupted(Error::corruption("wal: truncated header"));
+                }
+                self.pos = block_end;
+                continue;
+            }
+
+            let header = &self.data[self.pos..self.pos + WAL_HEADER_SIZE];
+            let expected = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+            let len = u16::from_le_bytes([header[4], header[5]]) as usize;
+            let ty = header[6];
+
+            if ty == RecordType::Zero as u8 && len == 0 && expected == 0 {
+                // preallocated space
+                self.pos = block_end;
+                continue;
+            }
+
+            let start = self.pos + WAL_HEADER_SIZE;
+            if start + len > block_end {
+                return Physical::Corrupted(Error::corruption("wal: bad fragment length"));
+            }
+
+            let payload = self.data.slice(start..start + len);
+            let actual = fragment_checksum(ty, &payload);
+            if actual != expected {
+                return Physical::Corrupted(Error::ChecksumMismatch { expected, actual });
+            }
+
+            self.pos = start + len;
+            return match RecordType::from_u8(ty) {
+                Some(RecordType::Zero) | None => {
+                    Physical::Corrupted(Error::corruption("wal: unknown fragment type"))
+                }
+                Some(ty) => Physical::Fragment(ty, payload),
+            };
+        }
+    }
+
+    #[inline]
+    fn drop_pending(&mut self) -> Result<(), Error> {
+        self.in_record = false;
+        let n = self.fragment_start - self.record_start;
+        self.corrupted(
+            Error::corruption("wal: missing the last fragment"),
+            n,
+            false,
+        )
+    }
+
+    /// Handles a corruption by the recovery mode, `tail` is true if the corruption reaches
+    /// the end of the log.
+    #[inline]
+    fn corrupted(&mut self, e: Error, dropped: usize, tail: bool) -> Result<(), Error> {
+        self.dropped += dropped;
+        match self.mode {
+            RecoveryMode::SkipCorrupted => Ok(()),
+            RecoveryMode::TolerateTail if tail => {
+                self.eof = true;
+                Ok(())
+            }
+            RecoveryMode::Strict | RecoveryMode::TolerateTail => {
+                self.eof = true;
+                Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Key, KeyExt, Value, ValueExt};
+    use alloc::vec;
+
+    fn record(i: usize, len: usize) -> Vec<u8> {
+        (0..len).map(|j| (i * 31 + j) as u8).collect()
+    }
+
+    fn read_all(data: &[u8], mode: RecoveryMode) -> Result<Vec<Bytes>, Error> {
+        let mut reader = LogReader::new(Bytes::copy_from_slice(data), mode);
+        let mut records = Vec::new();
+        while let Some(r) = reader.read_record()? {
+            records.push(r);
+        }
+        Ok(records)
+    }
+
+    #[test]
+    fn test_wal() {
+        let lens = [
+            0,
+            1,
+            100,
+            WAL_BLOCK_SIZE - 2 * WAL_HEADER_SIZE - 101,
+            3,
+            WAL_BLOCK_SIZE * 2 + 5,
+            10,
+        ];
+        let mut writer = LogWriter::new(Vec::new());
+        for (i, len) in lens.iter().enumerate() {
+            writer.add_record(&record(i, *len)).unwrap();
+        }
+        writer.sync().unwrap();
+        let data = writer.into_inner();
+
+        let records = read_all(&data, RecoveryMode::Strict).unwrap();
+        assert_eq!(records.len(), lens.len());
+        for (i, len) in lens.iter().enumerate() {
+            assert_eq!(records[i].as_ref(), record(i, *len).as_slice());
+        }
+
+        // appends to the existing log
+        let mut writer = LogWriter::with_len(data.clone(), data.len() as u64);
+        writer.add_record(b"appended").unwrap();
+        let records = read_all(writer.get_ref(), RecoveryMode::Strict).unwrap();
+        assert_eq!(records.last().unwrap().as_ref(), b"appended");
+
+        // batches and entries
+        let mut writer = LogWriter::new(Vec::new());
+        let mut batch = WriteBatch::new();
+        batch.put(Key::from("a").with_timestamp(1), Value::from("va"));
+        batch.delete(Key::from("b").with_timestamp(2));
+        writer.add_batch(&batch).unwrap();
+        writer
+            .add_entry(&Entry::new_from_kv(
+                Key::from("c").with_timestamp(3),
+                Value::from("vc"),
+            ))
+            .unwrap();
+        let mut reader = LogReader::new(Bytes::from(writer.into_inner()), RecoveryMode::Strict);
+        assert_eq!(reader.read_batch().unwrap().unwrap(), batch);
+        let batch = reader.read_batch().unwrap().unwrap();
+        let (k, v) = batch.iter().next().unwrap();
+        assert_eq!(k.parse_key(), b"c");
+        assert_eq!(v.parse_value(), b"vc");
+        assert!(reader.read_batch().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_wal_recovery() {
+        let mut writer = LogWriter::new(Vec::new());
+        writer.add_record(&record(0, 100)).unwrap();
+        writer.add_record(&record(1, WAL_BLOCK_SIZE)).unwrap();
+        writer.add_record(&record(2, 100)).unwrap();
+        let data = writer.into_inner();
+
+        // corrupt the payload of the first record
+        let mut bad = data.clone();
+        bad[WAL_HEADER_SIZE + 10] ^= 0xff;
+        assert!(matches!(
+            read_all(&bad, RecoveryMode::Strict),
+            Err(Error::ChecksumMismatch { .. })
+        ));
+        // not at the tail
+        assert!(matches!(
+            read_all(&bad, RecoveryMode::TolerateTail),
+            Err(Error::ChecksumMismatch { .. })
+        ));
+        // the first block is dropped, so is the first fragment of the second record.
+        let mut reader = LogReader::new(Bytes::from(bad), RecoveryMode::SkipCorrupted);
+        assert_eq!(
+            reader.read_record().unwrap().unwrap().as_ref(),
+            record(2, 100)
+        );
+        assert!(reader.read_record().unwrap().is_none());
+        assert_eq!(reader.dropped_bytes(), data.len() - 100 - WAL_HEADER_SIZE);
+
+        // a corruption in the last block
+        let mut bad = data.clone();
+        let n = bad.len();
+        bad[n - 10] ^= 0xff;
+        assert!(read_all(&bad, RecoveryMode::Strict).is_err());
+        let records = read_all(&bad, RecoveryMode::TolerateTail).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].as_ref(), record(1, WAL_BLOCK_SIZE));
+
+        // truncated tail
+        let torn = &data[..data.len() - 50];
+        assert!(read_all(torn, RecoveryMode::Strict).is_err());
+        let records = read_all(torn, RecoveryMode::TolerateTail).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].as_ref(), record(1, WAL_BLOCK_SIZE));
+
+        // the last fragment of the second record is lost
+        let torn = &data[..WAL_BLOCK_SIZE + 10];
+        assert_eq!(read_all(torn, RecoveryMode::TolerateTail).unwrap().len(), 1);
+        assert!(read_all(torn, RecoveryMode::Strict).is_err());
+
+        // preallocated zeros are ignored
+        let mut padded = data.clone();
+        padded.extend_from_slice(&vec![0; WAL_BLOCK_SIZE]);
+        assert_eq!(read_all(&padded, RecoveryMode::Strict).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_wal_corrupted_middle_fragment() {
+        let mut writer = LogWriter::new(Vec::new());
+        writer.add_record(&record(0, 100)).unwrap();
+        // First | Middle | Last
+        writer.add_record(&record(1, WAL_BLOCK_SIZE * 2)).unwrap();
+        writer.add_record(&record(2, 100)).unwrap();
+        let data = writer.into_inner();
+        assert!(data.len() > WAL_BLOCK_SIZE * 2);
+
+        let mut bad = data.clone();
+        bad[WAL_BLOCK_SIZE + WAL_HEADER_SIZE + 10] ^= 0xff;
+        assert!(read_all(&bad, RecoveryMode::Strict).is_err());
+        assert!(read_all(&bad, RecoveryMode::TolerateTail).is_err());
+
+        // the first and the last fragment are not spliced into a record.
+        let mut reader = LogReader::new(Bytes::from(bad), RecoveryMode::SkipCorrupted);
+        assert_eq!(
+            reader.read_record().unwrap().unwrap().as_ref(),
+            record(0, 100)
+        );
+        assert_eq!(
+            reader.read_record().unwrap().unwrap().as_ref(),
+            record(2, 100)
+        );
+        assert!(reader.read_record().unwrap().is_none());
+        assert_eq!(
+            reader.dropped_bytes(),
+            data.len() - 2 * (WAL_HEADER_SIZE + 100)
+        );
+    }
+}
@@ -0,0 +1,325 @@
+#![cfg(feature = "proptest")]
+
+use kvstructs::backup::{BackupOptions, BackupWriter, RestoreReader};
+use kvstructs::batch::WriteBatch;
+use kvstructs::bytes::{Bytes, BytesMut};
+use kvstructs::discard::DiscardStats;
+use kvstructs::iterator::Iterator as _;
+use kvstructs::manifest::{TableMeta, VersionEdit};
+use kvstructs::strategy::*;
+use kvstructs::table::{BlockHandle, Table, TableBuilder, TableOptions};
+use kvstructs::value_pointer::ValuePointer;
+use kvstructs::vlog::{VlogIterator, VlogRecord, VlogRecordRef};
+use kvstructs::wal::{LogReader, LogWriter, RecoveryMode, WAL_BLOCK_SIZE};
+use kvstructs::*;
+use proptest::collection::{btree_map, btree_set, vec};
+use proptest::prelude::*;
+use std::io::Cursor;
+
+/// Compares the encoded fields of two values, the version is not encoded.
+fn assert_same_value(a: &impl ValueExt, b: &impl ValueExt) {
+    assert_eq!(a.get_meta(), b.get_meta());
+    assert_eq!(a.get_user_meta(), b.get_user_meta());
+    assert_eq!(a.get_expires_at(), b.get_expires_at());
+    assert_eq!(a.parse_value(), b.parse_value());
+}
+
+fn encode_value(v: &impl ValueExt) -> Vec<u8> {
+    let mut buf = vec![0; v.encoded_size() as usize];
+    v.encode(&mut buf);
+    buf
+}
+
+proptest! {
+    #[test]
+    fn key_timestamp(data in user_key(), ts in any::<u64>(), other in any::<u64>()) {
+        let key = Key::from_with_timestamp(data.clone(), ts);
+        prop_assert_eq!(key.len(), data.len() + 8);
+        prop_assert_eq!(key.parse_key(), data.as_slice());
+        prop_assert_eq!(key.parse_timestamp(), ts);
+        prop_assert_eq!(key.parse_new_key(), Key::from(data.clone()));
+        prop_assert_eq!(key.as_key_ref().to_key(), key.clone());
+        let mut buf = Vec::new();
+        key.encode_to(&mut buf);
+        prop_assert_eq!(buf.as_slice(), key.as_bytes());
+
+        // newer versions sort first
+        let other_key = Key::from_with_timestamp(data, other);
+        prop_assert_eq!(compare_key(&key, &other_key), other.cmp(&ts));
+        prop_assert!(same_key(&key, &other_key));
+    }
+
+    #[test]
+    fn value_encoding(v in value()) {
+        let buf = encode_value(&v);
+        prop_assert_eq!(buf.len(), v.encoded_size() as usize);
+        assert_same_value(&Value::decode_value_ref(&buf), &v);
+        prop_assert_eq!(Value::decode_value(&buf), v.clone());
+        prop_assert_eq!(<Value as ValueExt>::decode_bytes(Bytes::from(buf.clone())), v.clone());
+
+        let enc = v.to_encoded();
+        prop_assert_eq!(enc.len(), buf.len());
+        prop_assert_eq!(enc.clone().leak_data(), Bytes::from(buf.clone()));
+        prop_assert_eq!(enc.decode_value(), v.clone());
+
+        // appends to the buffer
+        let mut buf_mut = BytesMut::from(&b"prefix"[..]);
+        v.encode_to(&mut buf_mut);
+        prop_assert_eq!(&buf_mut[..6], b"prefix");
+        prop_assert_eq!(&buf_mut[6..], buf.as_slice());
+    }
+
+    #[test]
+    fn encoded_value_decoding(enc in encoded_value()) {
+        let v = enc.decode_value();
+        assert_same_value(&enc, &v);
+        prop_assert_eq!(encode_value(&enc), encode_value(&v));
+        prop_assert_eq!(v.to_encoded(), enc);
+    }
+
+    #[test]
+    fn header_encoding(h in header()) {
+        let (n, buf) = h.encode();
+        prop_assert_eq!(n, buf.len());
+        prop_assert!(n <= MAX_HEADER_SIZE);
+        prop_assert_eq!(Header::decode(&buf), (n, h));
+        prop_assert_eq!(h.encode_to_bytes(), (n, Bytes::from(buf.clone())));
+        let mut fixed = [0; MAX_HEADER_SIZE];
+        prop_assert_eq!(h.encode_to(&mut fixed.as_mut_slice()), n);
+        prop_assert_eq!(&fixed[..n], buf.as_slice());
+
+        let mut updated = Header::new(0, 0, 0, 0, 0);
+        prop_assert_eq!(updated.update(&buf), n);
+        prop_assert_eq!(updated, h);
+
+        let mut hash_buf = Default::default();
+        let (read, decoded) = Header::decode_reader(&mut Cursor::new(buf.clone()), &mut hash_buf).unwrap();
+        prop_assert_eq!(read, n);
+        prop_assert_eq!(decoded, h);
+        prop_assert_eq!(hash_buf.as_ref(), buf.as_slice());
+    }
+
+    #[test]
+    fn entry_encoded_value(ent in entry()) {
+        prop_assert_eq!(&ent.encoded_value().decode_value(), ent.get_value());
+        let mut buf = BytesMut::new();
+        let len = ent.encode_to(&mut buf);
+        prop_assert_eq!(len as usize, buf.len());
+        let mut log = Vec::new();
+        prop_assert_eq!(VlogRecord::encode_to(ent.get_key(), ent.get_value().clone(), &mut log), len);
+        prop_assert_eq!(buf.as_ref(), log.as_slice());
+
+        let (k, v) = ent.clone().leak_rawkv();
+        prop_assert_eq!(Entry::new_from_kv(k, v), ent);
+    }
+
+    #[test]
+    fn write_batch(entries in vec(entry(), 0..32)) {
+        let mut batch = WriteBatch::new();
+        for ent in &entries {
+            batch.put_entry(ent);
+        }
+        prop_assert_eq!(batch.len(), entries.len());
+
+        let decoded = WriteBatch::decode(batch.as_bytes()).unwrap();
+        prop_assert_eq!(&decoded, &batch);
+        let mut n = 0;
+        for ((k, v), ent) in decoded.iter().zip(&entries) {
+            prop_assert_eq!(k.as_bytes(), ent.get_key().as_bytes());
+            assert_same_value(&v, ent.get_value());
+            n += 1;
+        }
+        prop_assert_eq!(n, entries.len());
+    }
+
+    #[test]
+    fn wal_records(records in vec(vec(any::<u8>(), 0..WAL_BLOCK_SIZE), 0..8)) {
+        let mut writer = LogWriter::new(Vec::new());
+        for r in &records {
+            writer.add_record(r).unwrap();
+        }
+        let mut reader = LogReader::new(Bytes::from(writer.into_inner()), RecoveryMode::Strict);
+        for r in &records {
+            prop_assert_eq!(reader.read_record().unwrap().unwrap(), Bytes::from(r.clone()));
+        }
+        prop_assert!(reader.read_record().unwrap().is_none());
+    }
+
+    #[test]
+    fn version_edit(
+        next_file_number in any::<u64>(),
+        last_sequence in any::<u64>(),
+        deleted in vec((0..7u32, any::<u64>()), 0..8),
+        added in vec((0..7u32, any::<u64>(), any::<u64>(), key_with_timestamp(), key_with_timestamp()), 0..8),
+    ) {
+        let mut edit = VersionEdit::new();
+        edit.set_next_file_number(next_file_number);
+        edit.set_last_sequence(last_sequence);
+        for (level, id) in deleted {
+            edit.delete_table(level, id);
+        }
+        for (level, id, size, smallest, largest) in added {
+            edit.add_table(level, TableMeta::new(id, size, smallest, largest));
+        }
+
+        let mut buf = Vec::new();
+        edit.encode_to(&mut buf);
+        prop_assert_eq!(VersionEdit::decode(&buf).unwrap(), edit);
+    }
+
+    #[test]
+    fn value_pointer(fid in any::<u32>(), len in any::<u32>(), offset in any::<u32>()) {
+        let vp = ValuePointer::new(fid, len, offset);
+        prop_assert_eq!(ValuePointer::decode(&vp.encode()).unwrap(), vp);
+
+        let val = Value::new()
+            .set_meta(OP::BIT_VALUE_POINTER.bits())
+            .set_data(Bytes::copy_from_slice(&vp.encode()));
+        prop_assert_eq!(ValuePointer::from_value(&val).unwrap(), Some(vp));
+    }
+
+    #[test]
+    fn discard_stats(stats in btree_map(any::<u32>(), 1..u64::MAX, 0..16), ts in any::<u64>()) {
+        let mut ds = DiscardStats::new();
+        for (fid, discarded) in &stats {
+            ds.add(*fid, *discarded);
+        }
+        prop_assert_eq!(DiscardStats::decode(&ds.encode()).unwrap(), ds.clone());
+        prop_assert_eq!(DiscardStats::from_value(ds.to_entry(ts).get_value()).unwrap(), ds);
+    }
+
+    #[test]
+    fn block_handle(offset in any::<u64>(), size in any::<u32>()) {
+        let handle = BlockHandle::new(offset, size);
+        let mut buf = Vec::new();
+        handle.encode_to(&mut buf);
+        prop_assert_eq!(BlockHandle::decode(&buf).unwrap(), (handle, buf.len()));
+    }
+
+    #[test]
+    fn vlog_records(entries in vec((key_with_timestamp(), value()), 0..16)) {
+        let mut data = Vec::new();
+        let mut positions = Vec::new();
+        for (k, v) in &entries {
+            let offset = data.len() as u32;
+            positions.push((offset, VlogRecord::encode_to(k, v.clone(), &mut data)));
+        }
+
+        let log = Bytes::from(data.clone());
+        let mut iter = VlogIterator::new(Cursor::new(data.clone()), 1);
+        for ((k, v), (offset, len)) in entries.iter().zip(positions) {
+            let record = iter.next_record().unwrap().unwrap();
+            prop_assert_eq!(record.get_key(), k);
+            assert_same_value(record.get_value(), v);
+            prop_assert_eq!(record.get_offset(), offset);
+            prop_assert_eq!(record.get_len(), len);
+
+            let vp = ValuePointer::new(1, len, offset);
+            prop_assert_eq!(&VlogRecord::read_bytes(&log, vp).unwrap(), &record);
+            let record_ref = VlogRecordRef::read(&data, vp).unwrap();
+            prop_assert_eq!(record_ref.to_record(), record);
+        }
+        prop_assert!(iter.next_record().unwrap().is_none());
+        prop_assert!(VlogRecordRef::decode(&data, data.len() as u32).unwrap().is_none());
+    }
+
+    #[test]
+    fn pb_kv_list(entries in vec(entry(), 0..16), stream_id in any::<u32>(), alloc_ref in any::<u64>()) {
+        let mut list = pb::KVList::new();
+        for ent in &entries {
+            list.push(pb::KV::from_entry(ent).set_stream_id(stream_id));
+        }
+        list.set_alloc_ref(alloc_ref);
+
+        let decoded = pb::KVList::decode(&list.encode()).unwrap();
+        prop_assert_eq!(&decoded, &list);
+        for (kv, ent) in decoded.as_slice().iter().zip(&entries) {
+            prop_assert_eq!(kv.get_stream_id(), stream_id);
+            let (k, v) = kv.to_entry().leak_rawkv();
+            prop_assert_eq!(k.parse_key(), ent.get_key().parse_key());
+            prop_assert_eq!(k.parse_timestamp(), ent.get_key().parse_timestamp());
+            assert_same_value(&v, ent.get_value());
+        }
+    }
+
+    #[test]
+    fn backup_restore(entries in vec(entry(), 0..64), since in 0..4u64, batch_size in 1..512usize) {
+        let opts = BackupOptions::new().set_since(since).set_batch_size(batch_size);
+        let mut w = BackupWriter::new(Cursor::new(Vec::new()), opts).unwrap();
+        let mut kept = Vec::new();
+        for ent in &entries {
+            if w.add(ent.get_key(), ent.get_value().clone()).unwrap() {
+                kept.push(ent);
+            }
+        }
+        let data = w.finish().unwrap().into_inner();
+
+        let mut r = RestoreReader::new(data.as_slice()).unwrap();
+        prop_assert_eq!(r.get_header().get_since(), since);
+        prop_assert_eq!(r.get_header().get_count(), kept.len() as u64);
+        let mut restored = Vec::new();
+        while let Some(batch) = r.next_batch().unwrap() {
+            restored.extend(batch);
+        }
+        prop_assert_eq!(restored.len(), kept.len());
+        for (a, b) in restored.iter().zip(kept) {
+            prop_assert!(a.get_key().parse_timestamp() > since);
+            prop_assert_eq!(a.get_key().parse_key(), b.get_key().parse_key());
+            assert_same_value(a.get_value(), b.get_value());
+        }
+    }
+
+    #[test]
+    fn table(keys in btree_set(key_with_timestamp(), 1..64), v in value()) {
+        let mut keys = keys.into_iter().collect::<Vec<_>>();
+        keys.sort_by(|a, b| compare_key(a, b));
+        keys.dedup_by(|a, b| a.as_bytes() == b.as_bytes());
+
+        let opts = TableOptions::default();
+        let mut builder = TableBuilder::new(opts.clone());
+        for k in &keys {
+            builder.add(k, v.clone());
+        }
+        let table = Table::from_bytes(builder.finish(), &opts).unwrap();
+        let mut iter = table.iter();
+        iter.rewind();
+        for k in &keys {
+            let (key, val) = iter.entry().unwrap();
+            prop_assert_eq!(key.as_bytes(), k.as_bytes());
+            assert_same_value(&val, &v);
+            iter.next();
+        }
+        prop_assert!(!iter.valid());
+    }
+}
+
+#[cfg(feature = "serde")]
+proptest! {
+    #[test]
+    fn serde_formats(ent in entry(), h in header(), op in op()) {
+        let buf = postcard::to_allocvec(&ent).unwrap();
+        prop_assert_eq!(postcard::from_bytes::<Entry>(&buf).unwrap(), ent.clone());
+        let json = serde_json::to_string(&ent).unwrap();
+        prop_assert_eq!(serde_json::from_str::<Entry>(&json).unwrap(), ent);
+
+        let buf = postcard::to_allocvec(&h).unwrap();
+        prop_assert_eq!(postcard::from_bytes::<Header>(&buf).unwrap(), h);
+        let json = serde_json::to_string(&op).unwrap();
+        prop_assert_eq!(serde_json::from_str::<OP>(&json).unwrap(), op);
+    }
+}
+
+#[cfg(feature = "rkyv")]
+proptest! {
+    #[test]
+    fn rkyv_archive(entries in vec(entry(), 0..16)) {
+        use rkyv::rancor::Error;
+
+        let buf = rkyv::to_bytes::<Error>(&entries).unwrap();
+        let archived = rkyv::access::<rkyv::Archived<Vec<Entry>>, Error>(&buf).unwrap();
+        for (a, ent) in archived.iter().zip(&entries) {
+            prop_assert!(a == ent);
+        }
+        prop_assert_eq!(rkyv::deserialize::<Vec<Entry>, Error>(archived).unwrap(), entries);
+    }
+}
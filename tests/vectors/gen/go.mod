@@ -0,0 +1,5 @@
+module github.com/al8n/kvstructs/tests/vectors/gen
+
+go 1.21
+
+require github.com/dgraph-io/badger/v4 v4.2.0
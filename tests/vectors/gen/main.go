@@ -0,0 +1,166 @@
+// Command gen writes the Badger conformance vectors used by tests/conformance.rs.
+//
+// Run it from this directory, it writes keys.bin, values.bin, headers.bin and
+// entries.bin in the parent directory:
+//
+//	go mod tidy
+//	go run .
+//
+// The cases must stay in the same order as KEY_CASES, VALUE_CASES, HEADER_CASES and
+// ENTRY_CASES in tests/conformance.rs.
+package main
+
+import (
+	"bytes"
+	"encoding/binary"
+	"hash/crc32"
+	"math"
+	"os"
+	"path/filepath"
+
+	"github.com/dgraph-io/badger/v4/y"
+)
+
+type keyCase struct {
+	key []byte
+	ts  uint64
+}
+
+type valueCase struct {
+	meta, userMeta byte
+	expiresAt      uint64
+	value          []byte
+}
+
+type headerCase struct {
+	meta, userMeta byte
+	klen, vlen     uint32
+	expiresAt      uint64
+}
+
+type entryCase struct {
+	key            []byte
+	ts             uint64
+	meta, userMeta byte
+	expiresAt      uint64
+	value          []byte
+}
+
+var keyCases = []keyCase{
+	{[]byte(""), 7},
+	{[]byte("a"), 0},
+	{[]byte("a"), 1},
+	{[]byte("key"), math.MaxUint64},
+	{[]byte("user/0001"), 1 << 32},
+	{[]byte("\x00\xff"), 42},
+}
+
+var valueCases = []valueCase{
+	{0, 0, 0, []byte("")},
+	{1, 0, 0, []byte("")},
+	{0x42, 7, 127, []byte("v")},
+	{0x40, 0xff, 128, []byte("hello")},
+	{0x80, 0, 1700000000, []byte("value")},
+	{0xff, 0xff, math.MaxUint64, []byte("\x00\x01\x02")},
+}
+
+var headerCases = []headerCase{
+	{0, 0, 0, 0, 0},
+	{0x40, 1, 10, 20, 0},
+	{2, 0, 127, 128, 16384},
+	{0xff, 0xff, math.MaxUint32, math.MaxUint32, math.MaxUint64},
+}
+
+var entryCases = []entryCase{
+	{[]byte("a"), 1, 0, 0, 0, []byte("b")},
+	{[]byte("key"), 42, 0x40, 3, 1700000000, []byte("value")},
+	{[]byte("user/0001"), math.MaxUint64, 0x80, 0, 0, []byte("")},
+	{[]byte("k"), 5, 1, 0, 0, bytes.Repeat([]byte("x"), 200)},
+}
+
+// header and its Encode are copied from Badger's structs.go, they are not exported.
+type header struct {
+	klen      uint32
+	vlen      uint32
+	expiresAt uint64
+	meta      byte
+	userMeta  byte
+}
+
+const maxHeaderSize = 22
+
+func (h header) Encode(out []byte) int {
+	out[0], out[1] = h.meta, h.userMeta
+	index := 2
+	index += binary.PutUvarint(out[index:], uint64(h.klen))
+	index += binary.PutUvarint(out[index:], uint64(h.vlen))
+	index += binary.PutUvarint(out[index:], h.expiresAt)
+	return index
+}
+
+// encodeEntry follows logFile.encodeEntry of Badger without encryption: the header,
+// the key, the value and the big endian CRC-32 (Castagnoli) of all of them.
+func encodeEntry(buf *bytes.Buffer, c entryCase) {
+	key := y.KeyWithTs(c.key, c.ts)
+	h := header{
+		klen:      uint32(len(key)),
+		vlen:      uint32(len(c.value)),
+		expiresAt: c.expiresAt,
+		meta:      c.meta,
+		userMeta:  c.userMeta,
+	}
+	start := buf.Len()
+	var enc [maxHeaderSize]byte
+	sz := h.Encode(enc[:])
+	buf.Write(enc[:sz])
+	buf.Write(key)
+	buf.Write(c.value)
+
+	var crc [crc32.Size]byte
+	binary.BigEndian.PutUint32(crc[:], crc32.Checksum(buf.Bytes()[start:], y.CastagnoliCrcTable))
+	buf.Write(crc[:])
+}
+
+// writeVector writes v prefixed by its length (u32 LE).
+func writeVector(buf *bytes.Buffer, v []byte) {
+	var l [4]byte
+	binary.LittleEndian.PutUint32(l[:], uint32(len(v)))
+	buf.Write(l[:])
+	buf.Write(v)
+}
+
+func write(name string, buf *bytes.Buffer) {
+	y.Check(os.WriteFile(filepath.Join("..", name), buf.Bytes(), 0o644))
+}
+
+func main() {
+	var keys bytes.Buffer
+	for _, c := range keyCases {
+		writeVector(&keys, y.KeyWithTs(c.key, c.ts))
+	}
+	write("keys.bin", &keys)
+
+	var values bytes.Buffer
+	for _, c := range valueCases {
+		v := y.ValueStruct{Meta: c.meta, UserMeta: c.userMeta, ExpiresAt: c.expiresAt, Value: c.value}
+		out := make([]byte, v.EncodedSize())
+		v.Encode(out)
+		writeVector(&values, out)
+	}
+	write("values.bin", &values)
+
+	var headers bytes.Buffer
+	for _, c := range headerCases {
+		h := header{klen: c.klen, vlen: c.vlen, expiresAt: c.expiresAt, meta: c.meta, userMeta: c.userMeta}
+		var out [maxHeaderSize]byte
+		sz := h.Encode(out[:])
+		writeVector(&headers, out[:sz])
+	}
+	write("headers.bin", &headers)
+
+	var entries bytes.Buffer
+	for _, c := range entryCases {
+		encodeEntry(&entries, c)
+	}
+	write("entries.bin", &entries)
+}